@@ -0,0 +1,140 @@
+//! A bump allocator that splits one large block of memory, such as the Daisy's SDRAM, into
+//! disjoint buffers with their own owners.
+//!
+//! Buffers are handed out for the life of the memory and never freed, which suits delay lines,
+//! reverbs and sample memory that are set up once at startup. It has no hardware dependencies, so
+//! it can be tested on the host.
+
+#![no_std]
+
+use core::mem;
+
+use log::info;
+
+/// The Cortex-M7's data cache line, in bytes. Buffers aligned to it don't share a line with their
+/// neighbours, so cache maintenance for DMA on one never touches another.
+pub const CACHE_LINE: usize = 32;
+
+/// Most buffers an `Arena` can hand out, each kept for its usage report.
+pub const MAX_ALLOCATIONS: usize = 16;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ArenaError {
+    /// The alignment isn't a power of two, or is finer than a sample.
+    InvalidAlignment,
+    /// Not enough memory is left for the buffer and its alignment padding.
+    OutOfMemory,
+    /// `MAX_ALLOCATIONS` buffers have already been handed out.
+    TooManyAllocations,
+}
+
+/// A record of one buffer handed out by an `Arena`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Allocation {
+    pub name: &'static str,
+    /// Where the buffer starts, in samples from the start of the arena.
+    pub offset: usize,
+    /// Length in samples.
+    pub len: usize,
+}
+
+const EMPTY: Allocation = Allocation {
+    name: "",
+    offset: 0,
+    len: 0,
+};
+
+/// Hands out named, aligned, zeroed buffers from the front of a block of memory until it runs out.
+pub struct Arena<'a> {
+    /// The memory not handed out yet.
+    free: &'a mut [f32],
+    capacity: usize,
+    /// Address of the start of the memory.
+    base: usize,
+    allocations: [Allocation; MAX_ALLOCATIONS],
+    count: usize,
+}
+
+impl<'a> Arena<'a> {
+    pub fn new(memory: &'a mut [f32]) -> Arena<'a> {
+        Arena {
+            capacity: memory.len(),
+            base: memory.as_ptr() as usize,
+            free: memory,
+            allocations: [EMPTY; MAX_ALLOCATIONS],
+            count: 0,
+        }
+    }
+
+    /// Takes a zeroed buffer of `len` samples, aligned to a cache line.
+    pub fn alloc(&mut self, name: &'static str, len: usize) -> Result<&'a mut [f32], ArenaError> {
+        self.alloc_aligned(name, len, CACHE_LINE)
+    }
+
+    /// Takes a zeroed buffer of `len` samples, starting at an address that's a multiple of `align`
+    /// bytes. Nothing is taken if it fails.
+    pub fn alloc_aligned(&mut self, name: &'static str, len: usize, align: usize) -> Result<&'a mut [f32], ArenaError> {
+        if !align.is_power_of_two() || align < mem::size_of::<f32>() {
+            return Err(ArenaError::InvalidAlignment);
+        }
+        if self.count == MAX_ALLOCATIONS {
+            return Err(ArenaError::TooManyAllocations);
+        }
+
+        // The free memory always starts on a sample, so the padding is a whole number of them.
+        let address = self.free.as_ptr() as usize;
+        let padding = address.wrapping_neg() & (align - 1);
+        let padding = padding / mem::size_of::<f32>();
+        if padding > self.free.len() || len > self.free.len() - padding {
+            return Err(ArenaError::OutOfMemory);
+        }
+
+        let free = mem::take(&mut self.free);
+        let (buffer, rest) = free[padding..].split_at_mut(len);
+        self.free = rest;
+        buffer.iter_mut().for_each(|x| *x = 0.0);
+
+        self.allocations[self.count] = Allocation {
+            name,
+            offset: (buffer.as_ptr() as usize - self.base) / mem::size_of::<f32>(),
+            len,
+        };
+        self.count += 1;
+
+        Ok(buffer)
+    }
+
+    /// Total size of the memory, in samples.
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Samples taken so far, including alignment padding.
+    pub fn used(&self) -> usize {
+        self.capacity - self.free.len()
+    }
+
+    /// Samples left to hand out, before any alignment padding.
+    pub fn remaining(&self) -> usize {
+        self.free.len()
+    }
+
+    /// The buffers handed out so far, in order.
+    pub fn allocations(&self) -> &[Allocation] {
+        &self.allocations[..self.count]
+    }
+
+    /// Logs each buffer and the total in use, which the firmware's logger sends over RTT.
+    pub fn report(&self) {
+        const KIB: usize = 1024 / mem::size_of::<f32>();
+        info!("SDRAM: {} of {} KiB used", self.used() / KIB, self.capacity / KIB);
+        for allocation in self.allocations() {
+            info!(
+                "  {:#010x} {:>9} samples  {}",
+                self.base + allocation.offset * mem::size_of::<f32>(),
+                allocation.len,
+                allocation.name
+            );
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use sdram_arena::{Allocation, Arena, ArenaError, CACHE_LINE, MAX_ALLOCATIONS};
+
+/// Memory that starts on a cache line, as the SDRAM does, filled with garbage.
+#[repr(C, align(4096))]
+struct Memory([f32; 4096]);
+
+fn memory() -> Box<Memory> {
+    Box::new(Memory([1.0; 4096]))
+}
+
+#[test]
+fn buffers_are_disjoint_and_zeroed() {
+    let mut memory = memory();
+    let mut arena = Arena::new(&mut memory.0);
+
+    let a = arena.alloc("a", 100).unwrap();
+    let b = arena.alloc("b", 200).unwrap();
+    let c = arena.alloc("c", 8).unwrap();
+    assert_eq!((a.len(), b.len(), c.len()), (100, 200, 8));
+    assert!(a.iter().chain(b.iter()).chain(c.iter()).all(|&x| x == 0.0));
+
+    // Writing one doesn't touch the others.
+    a.iter_mut().for_each(|x| *x = 2.0);
+    b.iter_mut().for_each(|x| *x = 3.0);
+    assert!(c.iter().all(|&x| x == 0.0));
+    assert!(a.iter().all(|&x| x == 2.0));
+
+    let a_end = a.as_ptr() as usize + a.len() * 4;
+    assert!(a_end <= b.as_ptr() as usize);
+    assert!(b.as_ptr() as usize + b.len() * 4 <= c.as_ptr() as usize);
+}
+
+#[test]
+fn buffers_are_aligned() {
+    let mut memory = memory();
+    let mut arena = Arena::new(&mut memory.0);
+
+    // An odd length leaves the next buffer needing padding.
+    let a = arena.alloc("a", 3).unwrap();
+    assert_eq!(a.as_ptr() as usize % CACHE_LINE, 0);
+    let b = arena.alloc("b", 5).unwrap();
+    assert_eq!(b.as_ptr() as usize % CACHE_LINE, 0);
+    let c = arena.alloc_aligned("c", 1, 4).unwrap();
+    let d = arena.alloc_aligned("d", 10, 1024).unwrap();
+    assert_eq!(d.as_ptr() as usize % 1024, 0);
+
+    assert_eq!(
+        arena.allocations(),
+        &[
+            Allocation { name: "a", offset: 0, len: 3 },
+            Allocation { name: "b", offset: 8, len: 5 },
+            Allocation { name: "c", offset: 13, len: 1 },
+            Allocation { name: "d", offset: 256, len: 10 },
+        ]
+    );
+    // The padding counts as used.
+    assert_eq!(arena.used(), 266);
+    assert_eq!(arena.remaining(), 4096 - 266);
+    assert_eq!(c.as_ptr() as usize - b.as_ptr() as usize, 5 * 4);
+}
+
+#[test]
+fn invalid_alignments_are_refused() {
+    let mut memory = memory();
+    let mut arena = Arena::new(&mut memory.0);
+
+    for &align in &[0, 1, 2, 6, 12, 48] {
+        assert_eq!(arena.alloc_aligned("bad", 10, align), Err(ArenaError::InvalidAlignment), "{}", align);
+    }
+    assert_eq!(arena.used(), 0);
+    assert!(arena.allocations().is_empty());
+}
+
+#[test]
+fn running_out_takes_nothing() {
+    let mut memory = memory();
+    let mut arena = Arena::new(&mut memory.0);
+
+    arena.alloc("a", 1).unwrap();
+    // Only 4096 - 8 samples are left once the padding is counted.
+    assert_eq!(arena.alloc("too big", 4089), Err(ArenaError::OutOfMemory));
+    assert_eq!(arena.alloc_aligned("too big", usize::MAX, 4), Err(ArenaError::OutOfMemory));
+    assert_eq!(arena.used(), 1);
+    assert_eq!(arena.allocations().len(), 1);
+
+    // An exact fit still works, and leaves nothing.
+    assert_eq!(arena.alloc("rest", 4088).unwrap().len(), 4088);
+    assert_eq!(arena.remaining(), 0);
+    assert_eq!(arena.alloc_aligned("empty", 0, 4).unwrap().len(), 0);
+    assert_eq!(arena.alloc_aligned("more", 1, 4), Err(ArenaError::OutOfMemory));
+}
+
+#[test]
+fn allocation_count_is_bounded() {
+    let mut memory = memory();
+    let mut arena = Arena::new(&mut memory.0);
+
+    for _ in 0..MAX_ALLOCATIONS {
+        arena.alloc("small", 1).unwrap();
+    }
+    assert_eq!(arena.alloc("one too many", 1), Err(ArenaError::TooManyAllocations));
+    assert_eq!(arena.allocations().len(), MAX_ALLOCATIONS);
+    assert_eq!(arena.capacity(), 4096);
+}
@@ -1,7 +1,10 @@
 #![no_main]
 #![no_std]
 
-use log::info;
+use log::{info, warn};
+
+use cortex_m::peripheral::DWT;
+use rtic::Mutex;
 
 use stm32h7xx_hal::prelude::*;
 use stm32h7xx_hal::stm32;
@@ -12,11 +15,18 @@ use libdaisy::gpio::*;
 use libdaisy::hid;
 use libdaisy::logger;
 
+use libdsp::delay::StereoDelay;
 use libdsp::oscillators::{Oscillator, OscillatorMode};
+use libdsp::reverb::{self, Reverb};
+use libdsp::traits::{MonoGenerator, StereoProcessor};
+
+use sdram_arena::Arena;
 
 mod gpio;
 mod system;
 
+/// Longest delay time, in samples per channel. Two seconds at 48 kHz.
+const DELAY_LENGTH: usize = 2 * 48000;
 
 #[rtic::app(
     device = stm32h7xx_hal::stm32,
@@ -29,6 +39,11 @@ const APP: () = {
         buffer: audio::AudioBuffer,
         seed_led: hid::Led<SeedLed>,
         osc: Oscillator,
+        delay: StereoDelay<'static>,
+        reverb: Reverb<'static>,
+        /// The most cycles the reverb has taken over a sample since the last report.
+        #[init(0)]
+        reverb_cycles: u32,
         timer2: Timer<stm32::TIM2>,
         lcd: system::LCD
     }
@@ -48,28 +63,55 @@ const APP: () = {
         // TODO: Check sample rate
         let osc = Oscillator::new(OscillatorMode::Saw, 440.0, 48000.0);
 
+        // Buffers too big for the internal RAM are carved out of the SDRAM.
+        let mut sdram = Arena::new(system.sdram);
+        let delay_left = sdram.alloc("delay left", DELAY_LENGTH).unwrap();
+        let delay_right = sdram.alloc("delay right", DELAY_LENGTH).unwrap();
+        let reverb_memory = sdram.alloc("reverb", Reverb::memory_len(48000.0)).unwrap();
+        sdram.report();
+
+        let mut delay = StereoDelay::new(delay_left, delay_right, 48000.0);
+        delay.set_time(0.375);
+        delay.set_mix(0.3);
+
+        let mut reverb = Reverb::new(reverb_memory, 48000.0).unwrap();
+        reverb.set_mix(0.25);
+
         init::LateResources {
             audio: system.audio,
             buffer,
             seed_led,
             osc,
+            delay,
+            reverb,
             timer2: system.timer2,
             lcd: system.ili9341
         }
     }
 
     // Interrupt handler for audio
-    #[task( binds = DMA1_STR1, resources = [audio, buffer, osc], priority = 8 )]
+    #[task( binds = DMA1_STR1, resources = [audio, buffer, osc, delay, reverb, reverb_cycles], priority = 8 )]
     fn audio_handler(ctx: audio_handler::Context) {
         let audio = ctx.resources.audio;
         let buffer = ctx.resources.buffer;
         let osc = ctx.resources.osc;
+        let delay = ctx.resources.delay;
+        let reverb = ctx.resources.reverb;
 
         if audio.get_stereo(buffer) {
-            for (left, _right) in buffer {
-                let right = osc.tick_poly_blep();
-                audio.push_stereo((*left, right)).unwrap();
+            let mut block = [0.0; audio::BLOCK_SIZE_MAX];
+            osc.fill(&mut block);
+
+            let mut worst = 0;
+            for ((left, _right), right) in buffer.iter().zip(block.iter()) {
+                let delayed = delay.tick((*left, *right));
+                let start = DWT::get_cycle_count();
+                let output = reverb.tick(delayed);
+                worst = worst.max(DWT::get_cycle_count().wrapping_sub(start));
+                audio.push_stereo(output).unwrap();
             }
+            let reverb_cycles = ctx.resources.reverb_cycles;
+            *reverb_cycles = worst.max(*reverb_cycles);
         } else {
             info!("Error reading data!");
         }
@@ -84,10 +126,24 @@ const APP: () = {
         }
     }
 
-    #[task( binds = TIM2, resources = [timer2, seed_led, osc] )]
-    fn interface_handler(ctx: interface_handler::Context) {
+    #[task( binds = TIM2, resources = [timer2, seed_led, osc, reverb_cycles] )]
+    fn interface_handler(mut ctx: interface_handler::Context) {
+        static mut TICKS: u32 = 0;
+
         ctx.resources.timer2.clear_irq();
         let seed_led = ctx.resources.seed_led;
         seed_led.update();
+
+        // Once a second, report the reverb's worst sample against its budget.
+        *TICKS += 1;
+        if *TICKS == 1000 {
+            *TICKS = 0;
+            let cycles = ctx.resources.reverb_cycles.lock(|cycles| core::mem::replace(cycles, 0));
+            if cycles > reverb::CYCLE_BUDGET {
+                warn!("Reverb took {} cycles per sample, over its budget of {}", cycles, reverb::CYCLE_BUDGET);
+            } else {
+                info!("Reverb took {} cycles per sample", cycles);
+            }
+        }
     }
 };
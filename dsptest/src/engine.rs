@@ -0,0 +1,148 @@
+use libdsp::traits::MonoGenerator;
+use libdsp::voices::{StealMode, SubtractiveVoice, VoiceManager, VoiceMode};
+
+use crate::synthmessage::SynthMessage;
+
+pub const VOICE_COUNT: usize = 8;
+
+/// Velocity used for notes from the computer keyboard, which has no velocity of its own.
+const KEYBOARD_VELOCITY: u8 = 100;
+
+/// Headroom so that a full chord doesn't clip when the voices are summed.
+const OUTPUT_GAIN: f32 = 0.25;
+
+/// Frames rendered at a time, so that any callback size can be served from a fixed buffer.
+const RENDER_BLOCK_SIZE: usize = 256;
+
+/// The libdsp voice engine, driven by `SynthMessage`s and rendered into interleaved buffers.
+pub struct SynthEngine {
+    voices: VoiceManager<SubtractiveVoice, VOICE_COUNT>,
+    mono_buffer: [f32; RENDER_BLOCK_SIZE],
+}
+
+impl SynthEngine {
+    pub fn new(sample_rate: f32) -> SynthEngine {
+        let voices = [(); VOICE_COUNT].map(|_| SubtractiveVoice::new(sample_rate));
+
+        SynthEngine {
+            voices: VoiceManager::new(voices, VoiceMode::Poly(StealMode::Oldest)),
+            mono_buffer: [0.0; RENDER_BLOCK_SIZE],
+        }
+    }
+
+    pub fn handle_message(&mut self, message: SynthMessage) {
+        match message {
+            SynthMessage::NoteOn(note) => self.note_on(note, KEYBOARD_VELOCITY),
+            SynthMessage::NoteOff(note) => self.note_off(note),
+        }
+    }
+
+    pub fn note_on(&mut self, note: u8, velocity: u8) {
+        self.voices.note_on(note, velocity);
+    }
+
+    pub fn note_off(&mut self, note: u8) {
+        self.voices.note_off(note);
+    }
+
+    pub fn set_sustain(&mut self, sustain: bool) {
+        self.voices.set_sustain(sustain);
+    }
+
+    /// Renders into an interleaved buffer, writing the same signal to every channel.
+    pub fn render(&mut self, data: &mut [f32], channels: usize) {
+        // Blocks of a fixed size, so the audio thread never allocates whatever the callback size.
+        for block in data.chunks_mut(RENDER_BLOCK_SIZE * channels) {
+            let mono = &mut self.mono_buffer[..block.len() / channels];
+            self.voices.fill(mono);
+
+            for (frame, &x) in block.chunks_mut(channels).zip(mono.iter()) {
+                for sample in frame.iter_mut() {
+                    *sample = x * OUTPUT_GAIN;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use libdsp::voices::Voice;
+
+    const SAMPLE_RATE: f32 = 48000.0;
+    const CHANNELS: usize = 2;
+
+    /// Renders `seconds` of stereo output, checking that both channels carry the same signal.
+    fn render_mono(engine: &mut SynthEngine, seconds: f32) -> Vec<f32> {
+        // An odd buffer size, so that rendering crosses the internal block boundaries.
+        let mut data = vec![0.0; (seconds * SAMPLE_RATE) as usize * CHANNELS];
+        for chunk in data.chunks_mut(1000 * CHANNELS) {
+            engine.render(chunk, CHANNELS);
+        }
+
+        data.chunks(CHANNELS)
+            .map(|frame| {
+                assert_eq!(frame[0], frame[1]);
+                frame[0]
+            })
+            .collect()
+    }
+
+    fn peak(samples: &[f32]) -> f32 {
+        samples.iter().fold(0.0, |peak, x| peak.max(x.abs()))
+    }
+
+    #[test]
+    fn note_on_plays_at_the_midi_pitch() {
+        let mut engine = SynthEngine::new(SAMPLE_RATE);
+        assert_eq!(peak(&render_mono(&mut engine, 0.1)), 0.0);
+
+        engine.handle_message(SynthMessage::NoteOn(69));
+        let output = render_mono(&mut engine, 1.0);
+        assert!(peak(&output) > 0.05, "peak {}", peak(&output));
+
+        // Count rising zero crossings over the last half second, past the attack and decay.
+        let held = &output[output.len() / 2..];
+        let crossings = held.windows(2).filter(|w| w[0] < 0.0 && w[1] >= 0.0).count();
+        let frequency = crossings as f32 * SAMPLE_RATE / held.len() as f32;
+        assert!((frequency - 440.0).abs() < 4.0, "frequency {}", frequency);
+    }
+
+    #[test]
+    fn note_off_releases_to_silence() {
+        let mut engine = SynthEngine::new(SAMPLE_RATE);
+        engine.handle_message(SynthMessage::NoteOn(60));
+        render_mono(&mut engine, 0.2);
+
+        engine.handle_message(SynthMessage::NoteOff(60));
+        // Still sounding early in the 0.3 s release, and silent once it has run its course.
+        let release = render_mono(&mut engine, 1.0);
+        assert!(peak(&release[..2400]) > 0.01);
+        assert_eq!(peak(&release[release.len() / 2..]), 0.0);
+        assert!(engine.voices.voices().iter().all(|voice| !voice.is_active()));
+    }
+
+    #[test]
+    fn voices_are_stolen_when_all_are_busy() {
+        let mut engine = SynthEngine::new(SAMPLE_RATE);
+        for note in 60..60 + VOICE_COUNT as u8 {
+            engine.note_on(note, 100);
+            render_mono(&mut engine, 0.01);
+        }
+        let held: Vec<_> = (0..VOICE_COUNT).map(|voice| engine.voices.note_of(voice)).collect();
+        assert!(held.iter().all(Option::is_some));
+
+        // One more note takes over the oldest voice and leaves the others playing.
+        let extra = 60 + VOICE_COUNT as u8;
+        engine.note_on(extra, 100);
+        let notes: Vec<_> = (0..VOICE_COUNT).map(|voice| engine.voices.note_of(voice)).collect();
+        assert!(!notes.contains(&Some(60)));
+        assert!(notes.contains(&Some(extra)));
+        for note in 61..extra {
+            assert!(notes.contains(&Some(note)), "note {} was stolen", note);
+        }
+        assert!(engine.voices.voices().iter().all(|voice| voice.is_active()));
+        assert!(peak(&render_mono(&mut engine, 0.05)) > 0.05);
+    }
+}
@@ -5,38 +5,50 @@ use synthui::SynthUI;
 
 use std::sync::mpsc;
 
+use crate::engine::SynthEngine;
 use crate::synthui::AppFlags;
 
+mod engine;
+mod render;
 mod synthui;
 pub mod synthmessage;
 
+const SAMPLE_RATE: u32 = 48000;
+
 fn main() -> iced::Result {
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("render") {
+        let result = render::parse_args(&args[2..]).and_then(|options| render::run(&options));
+        if let Err(error) = result {
+            eprintln!("{}", error);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
     let host = cpal::default_host();
         let device = host.default_output_device()
             .expect("Failed to open output device");
     
         let config = cpal::StreamConfig {
             channels: 2,
-            sample_rate: cpal::SampleRate(48000),
+            sample_rate: cpal::SampleRate(SAMPLE_RATE),
             buffer_size: cpal::BufferSize::Default
         };
 
         // Set up a channel to communicate with the audio thread
         let (tx, rx) = mpsc::channel::<SynthMessage>();
 
+        let channels = config.channels as usize;
+        let mut engine = SynthEngine::new(SAMPLE_RATE as f32);
+
         let stream = device.build_output_stream(&config,
             move | data: &mut [f32], _: &cpal::OutputCallbackInfo | {
                 for msg in rx.try_iter() {
-                    println!("{:?}", msg);
+                    engine.handle_message(msg);
                 }
 
-                for i in 0..data.len() {
-                    if i > 0 {
-                        data[i] = (rand::random::<f32>() + data[i-1]) / 2.0;
-                    } else {
-                        data[i] = (rand::random::<f32>() + data[data.len() - 1]) / 2.0;
-                    }
-                }
+                engine.render(data, channels);
             },
             move |err| {
                 eprintln!("an error occurred on stream: {}", err);
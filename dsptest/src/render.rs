@@ -0,0 +1,498 @@
+//! Headless rendering of note scripts and MIDI files to WAV, for listening to DSP changes on
+//! machines without a sound card.
+//!
+//! Note scripts have one event per line, as the time in seconds followed by the event:
+//!
+//! ```text
+//! # time  event
+//! 0.0     on 60 100
+//! 0.5     off 60
+//! 1.0     sustain on
+//! ```
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use midly::{MetaMessage, MidiMessage, Smf, Timing, TrackEventKind};
+
+use crate::engine::SynthEngine;
+
+/// Silence rendered after the last event when no duration is given, so releases can finish.
+const RELEASE_TAIL_SECONDS: f64 = 2.0;
+
+const CHANNELS: usize = 2;
+
+/// Frames rendered and written at a time, so long renders don't need the whole file in memory.
+const RENDER_BLOCK_FRAMES: usize = 4096;
+
+/// The most audio data a WAV file can hold: its chunk sizes are 32-bit, and the header needs room.
+const MAX_WAV_DATA_BYTES: u64 = u32::MAX as u64 - 1024;
+
+const USAGE: &str = "usage: dsptest render <script.txt | file.mid> -o <out.wav> \
+[--sample-rate <hz>] [--duration <seconds>] [--format <16 | 24 | 32f>]";
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum WavFormat {
+    Int16,
+    Int24,
+    Float32,
+}
+
+#[derive(Debug)]
+pub struct RenderOptions {
+    pub input: PathBuf,
+    pub output: PathBuf,
+    pub sample_rate: u32,
+    pub duration: Option<f64>,
+    pub format: WavFormat,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Event {
+    NoteOn(u8, u8),
+    NoteOff(u8),
+    Sustain(bool),
+}
+
+/// An event and its time in seconds from the start of the render.
+type TimedEvent = (f64, Event);
+
+/// Parses the arguments following `render` on the command line.
+pub fn parse_args(args: &[String]) -> Result<RenderOptions, String> {
+    let mut input = None;
+    let mut output = None;
+    let mut sample_rate = crate::SAMPLE_RATE;
+    let mut duration = None;
+    let mut format = WavFormat::Int16;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{} needs a value\n{}", arg, USAGE));
+        match arg.as_str() {
+            "-o" | "--output" => output = Some(PathBuf::from(value()?)),
+            "--sample-rate" => {
+                sample_rate = value()?
+                    .parse()
+                    .ok()
+                    .filter(|&rate: &u32| rate > 0)
+                    .ok_or_else(|| format!("invalid sample rate\n{}", USAGE))?
+            }
+            "--duration" => {
+                duration = Some(
+                    value()?
+                        .parse()
+                        .ok()
+                        .filter(|&seconds: &f64| seconds.is_finite() && seconds >= 0.0)
+                        .ok_or_else(|| format!("invalid duration\n{}", USAGE))?,
+                )
+            }
+            "--format" => {
+                format = match value()?.as_str() {
+                    "16" => WavFormat::Int16,
+                    "24" => WavFormat::Int24,
+                    "32f" => WavFormat::Float32,
+                    other => return Err(format!("unknown format '{}'\n{}", other, USAGE)),
+                }
+            }
+            _ if input.is_none() && !arg.starts_with('-') => input = Some(PathBuf::from(arg)),
+            _ => return Err(format!("unexpected argument '{}'\n{}", arg, USAGE)),
+        }
+    }
+
+    Ok(RenderOptions {
+        input: input.ok_or_else(|| USAGE.to_string())?,
+        output: output.ok_or_else(|| USAGE.to_string())?,
+        sample_rate,
+        duration,
+        format,
+    })
+}
+
+pub fn run(options: &RenderOptions) -> Result<(), String> {
+    let events = load_events(&options.input)?;
+    let duration = options
+        .duration
+        .unwrap_or_else(|| events.last().map(|e| e.0).unwrap_or(0.0) + RELEASE_TAIL_SECONDS);
+    let total_frames = frames_for_duration(duration, options.sample_rate, options.format)?;
+
+    let path = &options.output;
+    let error = |e: hound::Error| format!("{}: {}", path.display(), e);
+    let mut writer = hound::WavWriter::create(path, wav_spec(options.sample_rate, options.format)).map_err(error)?;
+    render(&events, options.sample_rate, total_frames, |block| {
+        write_samples(&mut writer, block, options.format)
+    })
+    .map_err(error)?;
+    writer.finalize().map_err(error)?;
+
+    println!(
+        "Rendered {:.2}s to {}",
+        total_frames as f64 / options.sample_rate as f64,
+        options.output.display()
+    );
+
+    Ok(())
+}
+
+/// The number of frames to render for a duration, if they fit in a WAV file of the given format.
+fn frames_for_duration(duration: f64, sample_rate: u32, format: WavFormat) -> Result<usize, String> {
+    let bytes_per_frame = (CHANNELS * wav_spec(sample_rate, format).bits_per_sample as usize / 8) as u64;
+    let max_frames = MAX_WAV_DATA_BYTES / bytes_per_frame;
+    let frames = (duration * sample_rate as f64).round();
+    if frames > max_frames as f64 {
+        return Err(format!(
+            "{:.0}s is too long for a WAV file: at most {:.0}s fits at this sample rate and format",
+            duration,
+            (max_frames / sample_rate as u64) as f64
+        ));
+    }
+
+    Ok(frames as usize)
+}
+
+fn load_events(path: &Path) -> Result<Vec<TimedEvent>, String> {
+    let is_midi = path
+        .extension()
+        .map(|e| e.eq_ignore_ascii_case("mid") || e.eq_ignore_ascii_case("midi"))
+        .unwrap_or(false);
+
+    let mut events = if is_midi {
+        let bytes = fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        parse_midi(&bytes)?
+    } else {
+        let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        parse_script(&text)?
+    };
+
+    // Stable, so simultaneous events keep their order in the file.
+    events.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    Ok(events)
+}
+
+fn parse_script(text: &str) -> Result<Vec<TimedEvent>, String> {
+    let mut events = Vec::new();
+
+    for (number, line) in text.lines().enumerate() {
+        let line = line.split('#').next().unwrap().trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let error = |message: &str| format!("line {}: {}", number + 1, message);
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let number_at = |i: usize| -> Result<u8, String> {
+            fields
+                .get(i)
+                .ok_or_else(|| error("missing value"))?
+                .parse()
+                .map_err(|_| error("expected a number from 0 to 127"))
+                .and_then(|n: u8| if n < 128 { Ok(n) } else { Err(error("expected a number from 0 to 127")) })
+        };
+
+        let time: f64 = fields[0].parse().map_err(|_| error("expected a time in seconds"))?;
+        if !time.is_finite() {
+            return Err(error("expected a time in seconds"));
+        }
+        if time < 0.0 {
+            return Err(error("times can't be negative"));
+        }
+
+        let event = match fields.get(1).copied() {
+            Some("on") => Event::NoteOn(number_at(2)?, if fields.len() > 3 { number_at(3)? } else { 100 }),
+            Some("off") => Event::NoteOff(number_at(2)?),
+            Some("sustain") => match fields.get(2).copied() {
+                Some("on") => Event::Sustain(true),
+                Some("off") => Event::Sustain(false),
+                _ => return Err(error("expected 'sustain on' or 'sustain off'")),
+            },
+            _ => return Err(error("expected 'on', 'off' or 'sustain'")),
+        };
+
+        events.push((time, event));
+    }
+
+    Ok(events)
+}
+
+fn parse_midi(bytes: &[u8]) -> Result<Vec<TimedEvent>, String> {
+    let smf = Smf::parse(bytes).map_err(|e| format!("invalid MIDI file: {}", e))?;
+
+    // Merge every track onto one timeline of absolute ticks.
+    let mut track_events = Vec::new();
+    for track in smf.tracks.iter() {
+        let mut tick: u64 = 0;
+        for event in track.iter() {
+            tick += event.delta.as_int() as u64;
+            track_events.push((tick, event.kind));
+        }
+    }
+    track_events.sort_by_key(|&(tick, _)| tick);
+
+    // Tempo changes apply to everything after them, so walk the timeline converting ticks to
+    // seconds one span at a time.
+    let mut seconds_per_tick = match smf.header.timing {
+        Timing::Metrical(ticks_per_beat) => 0.5 / ticks_per_beat.as_int() as f64,
+        Timing::Timecode(fps, subframes) => 1.0 / (fps.as_f32() as f64 * subframes as f64),
+    };
+    if !seconds_per_tick.is_finite() {
+        return Err("invalid MIDI file: zero ticks per beat or frame".to_string());
+    }
+    let mut last_tick = 0;
+    let mut time = 0.0;
+    let mut events = Vec::new();
+
+    for (tick, kind) in track_events {
+        time += (tick - last_tick) as f64 * seconds_per_tick;
+        last_tick = tick;
+
+        match kind {
+            TrackEventKind::Meta(MetaMessage::Tempo(micros_per_beat)) => {
+                if let Timing::Metrical(ticks_per_beat) = smf.header.timing {
+                    seconds_per_tick = micros_per_beat.as_int() as f64 / 1e6 / ticks_per_beat.as_int() as f64;
+                }
+            }
+            TrackEventKind::Midi { message, .. } => {
+                let event = match message {
+                    MidiMessage::NoteOn { key, vel } if vel.as_int() > 0 => Event::NoteOn(key.as_int(), vel.as_int()),
+                    MidiMessage::NoteOn { key, .. } | MidiMessage::NoteOff { key, .. } => Event::NoteOff(key.as_int()),
+                    MidiMessage::Controller { controller, value } if controller.as_int() == 64 => {
+                        Event::Sustain(value.as_int() >= 64)
+                    }
+                    _ => continue,
+                };
+                events.push((time, event));
+            }
+            _ => {}
+        }
+    }
+
+    Ok(events)
+}
+
+/// Renders `total_frames` frames of the events as interleaved stereo, passing each block to `write`
+/// as it's finished.
+fn render<E>(
+    events: &[TimedEvent],
+    sample_rate: u32,
+    total_frames: usize,
+    mut write: impl FnMut(&[f32]) -> Result<(), E>,
+) -> Result<(), E> {
+    let mut engine = SynthEngine::new(sample_rate as f32);
+    let mut block = vec![0.0; RENDER_BLOCK_FRAMES * CHANNELS];
+    let mut events = events.iter().peekable();
+    let mut frame = 0;
+
+    while frame < total_frames {
+        let block_end = (frame + RENDER_BLOCK_FRAMES).min(total_frames);
+        let mut offset = 0;
+
+        // Split the block at each event that falls inside it.
+        while frame + offset < block_end {
+            let next_event = events
+                .peek()
+                .map(|&&(time, _)| (time * sample_rate as f64).round() as usize)
+                .unwrap_or(usize::MAX);
+            if next_event <= frame + offset {
+                match events.next().unwrap().1 {
+                    Event::NoteOn(note, velocity) => engine.note_on(note, velocity),
+                    Event::NoteOff(note) => engine.note_off(note),
+                    Event::Sustain(sustain) => engine.set_sustain(sustain),
+                }
+                continue;
+            }
+
+            let end = next_event.min(block_end) - frame;
+            engine.render(&mut block[offset * CHANNELS..end * CHANNELS], CHANNELS);
+            offset = end;
+        }
+
+        write(&block[..offset * CHANNELS])?;
+        frame = block_end;
+    }
+
+    Ok(())
+}
+
+fn wav_spec(sample_rate: u32, format: WavFormat) -> hound::WavSpec {
+    let (bits_per_sample, sample_format) = match format {
+        WavFormat::Int16 => (16, hound::SampleFormat::Int),
+        WavFormat::Int24 => (24, hound::SampleFormat::Int),
+        WavFormat::Float32 => (32, hound::SampleFormat::Float),
+    };
+
+    hound::WavSpec {
+        channels: CHANNELS as u16,
+        sample_rate,
+        bits_per_sample,
+        sample_format,
+    }
+}
+
+fn write_samples<W: std::io::Write + std::io::Seek>(
+    writer: &mut hound::WavWriter<W>,
+    samples: &[f32],
+    format: WavFormat,
+) -> Result<(), hound::Error> {
+    for &x in samples {
+        let x = x.clamp(-1.0, 1.0);
+        match format {
+            WavFormat::Int16 => writer.write_sample((x * i16::MAX as f32) as i16),
+            WavFormat::Int24 => writer.write_sample((x * 8_388_607.0) as i32),
+            WavFormat::Float32 => writer.write_sample(x),
+        }?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn parses_options() {
+        let options = parse_args(&args("notes.txt -o out.wav")).unwrap();
+        assert_eq!(options.input, PathBuf::from("notes.txt"));
+        assert_eq!(options.output, PathBuf::from("out.wav"));
+        assert_eq!(options.sample_rate, crate::SAMPLE_RATE);
+        assert_eq!(options.duration, None);
+        assert_eq!(options.format, WavFormat::Int16);
+
+        let options =
+            parse_args(&args("--sample-rate 96000 song.mid --duration 2.5 --output out.wav --format 32f")).unwrap();
+        assert_eq!(options.input, PathBuf::from("song.mid"));
+        assert_eq!(options.sample_rate, 96000);
+        assert_eq!(options.duration, Some(2.5));
+        assert_eq!(options.format, WavFormat::Float32);
+    }
+
+    #[test]
+    fn rejects_bad_options() {
+        for line in &[
+            "notes.txt",
+            "-o out.wav",
+            "notes.txt -o",
+            "notes.txt -o out.wav --sample-rate 0",
+            "notes.txt -o out.wav --sample-rate -48000",
+            "notes.txt -o out.wav --duration -1",
+            "notes.txt -o out.wav --duration inf",
+            "notes.txt -o out.wav --duration nan",
+            "notes.txt -o out.wav --format 8",
+            "notes.txt -o out.wav extra.txt",
+        ] {
+            assert!(parse_args(&args(line)).is_err(), "{}", line);
+        }
+    }
+
+    #[test]
+    fn parses_scripts() {
+        let script = "\
+            # A chord\n\
+            0.0 on 60 90\n\
+            \n\
+            0.0 on 64   # default velocity\n\
+            0.25 sustain on\n\
+            0.5 off 60\n\
+            1 sustain off\n";
+        assert_eq!(
+            parse_script(script).unwrap(),
+            vec![
+                (0.0, Event::NoteOn(60, 90)),
+                (0.0, Event::NoteOn(64, 100)),
+                (0.25, Event::Sustain(true)),
+                (0.5, Event::NoteOff(60)),
+                (1.0, Event::Sustain(false)),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_bad_scripts() {
+        for line in &[
+            "nan on 60",
+            "inf on 60",
+            "-inf off 60",
+            "-0.5 on 60",
+            "soon on 60",
+            "0.0 on 128",
+            "0.0 on",
+            "0.0 play 60",
+            "0.0 sustain",
+        ] {
+            let error = parse_script(&format!("0.0 on 60\n{}", line)).unwrap_err();
+            assert!(error.starts_with("line 2:"), "{}: {}", line, error);
+        }
+    }
+
+    /// A format 0 MIDI file with the given division and track events.
+    fn midi_file(division: u16, track: &[u8]) -> Vec<u8> {
+        let mut bytes = b"MThd\x00\x00\x00\x06\x00\x00\x00\x01".to_vec();
+        bytes.extend_from_slice(&division.to_be_bytes());
+        bytes.extend_from_slice(b"MTrk");
+        bytes.extend_from_slice(&(track.len() as u32 + 4).to_be_bytes());
+        bytes.extend_from_slice(track);
+        bytes.extend_from_slice(b"\x00\xff\x2f\x00");
+        bytes
+    }
+
+    #[test]
+    fn parses_midi() {
+        let track = [
+            0x00, 0x90, 60, 100, // note on at 0
+            0x83, 0x60, 0x80, 60, 64, // note off a beat later, at the default 120 bpm
+            0x00, 0xff, 0x51, 0x03, 0x0f, 0x42, 0x40, // 60 bpm from here
+            0x83, 0x60, 0x90, 62, 0, // note on with zero velocity, a note off, a second later
+            0x00, 0xb0, 64, 127, // sustain pedal down
+            0x00, 0xb0, 1, 127, // mod wheel, ignored
+        ];
+        assert_eq!(
+            parse_midi(&midi_file(480, &track)).unwrap(),
+            vec![
+                (0.0, Event::NoteOn(60, 100)),
+                (0.5, Event::NoteOff(60)),
+                (1.5, Event::NoteOff(62)),
+                (1.5, Event::Sustain(true)),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_bad_midi() {
+        assert!(parse_midi(b"not a MIDI file").is_err());
+        assert!(parse_midi(&midi_file(0, &[0x00, 0x90, 60, 100])).is_err());
+    }
+
+    #[test]
+    fn renders_in_blocks_with_events_on_their_frames() {
+        let events = [(0.1, Event::NoteOn(60, 100)), (0.15, Event::NoteOff(60))];
+        let mut samples = Vec::new();
+        let mut blocks = 0;
+        render(&events, 48000, 10_000, |block| {
+            assert!(block.len() <= RENDER_BLOCK_FRAMES * CHANNELS);
+            samples.extend_from_slice(block);
+            blocks += 1;
+            Ok::<_, ()>(())
+        })
+        .unwrap();
+
+        assert_eq!(blocks, 3);
+        assert_eq!(samples.len(), 10_000 * CHANNELS);
+        // Silent up to the note on at frame 4800, in the second block, and sounding straight after.
+        assert!(samples[..4800 * CHANNELS].iter().all(|&x| x == 0.0));
+        assert!(samples[4800 * CHANNELS..4900 * CHANNELS].iter().any(|&x| x != 0.0));
+    }
+
+    #[test]
+    fn rejects_renders_too_long_for_wav() {
+        assert_eq!(frames_for_duration(60.0, 48000, WavFormat::Int16), Ok(2_880_000));
+        assert_eq!(frames_for_duration(0.0, 48000, WavFormat::Float32), Ok(0));
+        // About three hours of 32-bit stereo fit at 48 kHz.
+        assert!(frames_for_duration(11_000.0, 48000, WavFormat::Float32).is_ok());
+        assert!(frames_for_duration(11_300.0, 48000, WavFormat::Float32).is_err());
+        assert!(frames_for_duration(1e9, 48000, WavFormat::Int16).is_err());
+    }
+}
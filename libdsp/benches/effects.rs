@@ -0,0 +1,97 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use libdsp::delay::StereoDelay;
+use libdsp::distortion::{Antialiasing, Distortion, DistortionKind};
+use libdsp::modulation::{Chorus, Flanger, Phaser};
+use libdsp::reverb::Reverb;
+use libdsp::traits::{MonoProcessor, StereoProcessor};
+
+const BLOCK_SIZE: usize = 256;
+const SAMPLE_RATE: f32 = 48000.0;
+
+/// Effects run a block of stereo noise at a time, as the firmware's audio callback does. Their
+/// per-sample cost is fixed, so the input only matters in keeping the output from going silent.
+fn effect_blocks(c: &mut Criterion) {
+    let mut group = c.benchmark_group("effect");
+
+    let mut left = [0.0; BLOCK_SIZE];
+    let mut right = [0.0; BLOCK_SIZE];
+    let mut seed = 1u32;
+    for (l, r) in left.iter_mut().zip(right.iter_mut()) {
+        seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+        *l = (seed >> 8) as f32 / 8_388_608.0 - 1.0;
+        *r = -*l;
+    }
+
+    group.bench_function("reverb", |b| {
+        let mut memory = vec![0.0; Reverb::memory_len(SAMPLE_RATE)];
+        let mut reverb = Reverb::new(&mut memory, SAMPLE_RATE).unwrap();
+        b.iter(|| {
+            let (mut l, mut r) = (left, right);
+            reverb.process(&mut l, &mut r);
+            black_box((&l, &r));
+        })
+    });
+
+    group.bench_function("stereo_delay", |b| {
+        let (mut delay_left, mut delay_right) = (vec![0.0; 96000], vec![0.0; 96000]);
+        let mut delay = StereoDelay::new(&mut delay_left, &mut delay_right, SAMPLE_RATE);
+        b.iter(|| {
+            let (mut l, mut r) = (left, right);
+            delay.process(&mut l, &mut r);
+            black_box((&l, &r));
+        })
+    });
+
+    group.bench_function("chorus", |b| {
+        let mut memory = vec![0.0; Chorus::memory_len(SAMPLE_RATE)];
+        let mut chorus = Chorus::new(&mut memory, SAMPLE_RATE).unwrap();
+        chorus.set_voices(4);
+        b.iter(|| {
+            let (mut l, mut r) = (left, right);
+            chorus.process(&mut l, &mut r);
+            black_box((&l, &r));
+        })
+    });
+
+    group.bench_function("flanger", |b| {
+        let mut memory = vec![0.0; Flanger::memory_len(SAMPLE_RATE)];
+        let mut flanger = Flanger::new(&mut memory, SAMPLE_RATE).unwrap();
+        flanger.set_feedback(0.7);
+        b.iter(|| {
+            let (mut l, mut r) = (left, right);
+            flanger.process(&mut l, &mut r);
+            black_box((&l, &r));
+        })
+    });
+
+    group.bench_function("phaser", |b| {
+        let mut phaser = Phaser::new(SAMPLE_RATE);
+        phaser.set_stages(12);
+        b.iter(|| {
+            let (mut l, mut r) = (left, right);
+            phaser.process(&mut l, &mut r);
+            black_box((&l, &r));
+        })
+    });
+
+    for &(name, antialiasing) in &[
+        ("distortion_adaa", Antialiasing::Adaa),
+        ("distortion_oversampled", Antialiasing::Oversampled),
+    ] {
+        group.bench_function(name, |b| {
+            let mut distortion = Distortion::new(DistortionKind::Tanh, SAMPLE_RATE);
+            distortion.set_antialiasing(antialiasing);
+            distortion.set_drive(4.0);
+            b.iter(|| {
+                let mut l = left;
+                distortion.process(&mut l);
+                black_box(&l);
+            })
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, effect_blocks);
+criterion_main!(benches);
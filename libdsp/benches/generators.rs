@@ -0,0 +1,47 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use libdsp::oscillators::{Oscillator, OscillatorMode};
+use libdsp::traits::MonoGenerator;
+
+const BLOCK_SIZE: usize = 256;
+
+const MODES: [(&str, OscillatorMode); 4] = [
+    ("sine", OscillatorMode::Sine),
+    ("saw", OscillatorMode::Saw),
+    ("square", OscillatorMode::Square),
+    ("triangle", OscillatorMode::Triangle),
+];
+
+/// Generators are driven through trait objects, as a voice engine holding mixed generators would,
+/// so the per-sample path pays for a call per sample the way it does on the firmware.
+fn oscillator_blocks(c: &mut Criterion) {
+    let mut group = c.benchmark_group("oscillator");
+
+    for &(name, mode) in MODES.iter() {
+        group.bench_with_input(BenchmarkId::new("tick", name), &mode, |b, &mode| {
+            let mut osc = Oscillator::new(mode, 440.0, 48000.0);
+            let generator: &mut dyn MonoGenerator = black_box(&mut osc);
+            let mut buffer = [0.0; BLOCK_SIZE];
+            b.iter(|| {
+                for x in buffer.iter_mut() {
+                    *x = generator.tick();
+                }
+                black_box(&buffer);
+            })
+        });
+
+        group.bench_with_input(BenchmarkId::new("fill", name), &mode, |b, &mode| {
+            let mut osc = Oscillator::new(mode, 440.0, 48000.0);
+            let generator: &mut dyn MonoGenerator = black_box(&mut osc);
+            let mut buffer = [0.0; BLOCK_SIZE];
+            b.iter(|| {
+                generator.fill(&mut buffer);
+                black_box(&buffer);
+            })
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, oscillator_blocks);
+criterion_main!(benches);
@@ -0,0 +1,177 @@
+//! Spectral quality measurements for rendered audio, for comparing oscillator algorithms and
+//! checking noise colours on the host. Only built with the `analysis` feature.
+
+use std::f64::consts::PI;
+use std::vec::Vec;
+
+use rustfft::num_complex::Complex;
+use rustfft::FftPlanner;
+
+use super::oscillators::{GenericOscillator, OscillatorMode};
+use super::sample::Sample;
+
+/// FFT length used by `analyze_oscillator`.
+pub const ANALYSIS_LENGTH: usize = 1 << 16;
+
+/// Bins either side of a harmonic counted as part of it. Covers the Blackman-Harris main lobe.
+const HARMONIC_HALF_WIDTH: usize = 6;
+
+/// Bins at the bottom of the spectrum excluded as DC leakage.
+const DC_BINS: usize = 6;
+
+/// Segment length `spectral_slope` averages its spectrum over.
+const SLOPE_SEGMENT: usize = 4096;
+
+/// Bands per octave `spectral_slope` fits its line to.
+const SLOPE_BANDS_PER_OCTAVE: f64 = 3.0;
+
+#[derive(Debug, Clone, Copy)]
+pub struct SpectralReport {
+    /// Energy between DC and the fundamental, relative to the harmonic energy. Nothing belongs
+    /// there in a periodic signal, so it's a direct measure of aliasing.
+    pub alias_below_fundamental_db: f64,
+    /// Harmonic energy relative to everything else except DC.
+    pub harmonic_to_noise_db: f64,
+    /// Mean of the signal.
+    pub dc_offset: f64,
+}
+
+/// Which `Oscillator` rendering path to analyse.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum OscillatorPath {
+    Naive,
+    PolyBlep,
+}
+
+/// Measures a signal with a known fundamental. Uses the largest power-of-two length that fits.
+pub fn analyze<S: Sample>(signal: &[S], fundamental: f64, sample_rate: f64) -> SpectralReport {
+    let length = if signal.len().is_power_of_two() {
+        signal.len()
+    } else {
+        signal.len().next_power_of_two() / 2
+    };
+    let signal = &signal[..length];
+
+    let dc_offset = signal.iter().map(|&x| x.to_f64()).sum::<f64>() / length as f64;
+
+    // Blackman-Harris, whose sidelobes sit far enough down not to mask the aliasing.
+    let mut buffer: Vec<Complex<f64>> = signal
+        .iter()
+        .enumerate()
+        .map(|(i, &x)| {
+            let w = 2.0 * PI * i as f64 / length as f64;
+            let window = 0.35875 - 0.48829 * w.cos() + 0.14128 * (2.0 * w).cos() - 0.01168 * (3.0 * w).cos();
+            Complex::new((x.to_f64() - dc_offset) * window, 0.0)
+        })
+        .collect();
+    FftPlanner::new().plan_fft_forward(length).process(&mut buffer);
+
+    let power: Vec<f64> = buffer[..length / 2].iter().map(|c| c.norm_sqr()).collect();
+    let bin_width = sample_rate / length as f64;
+
+    let mut is_harmonic = vec![false; power.len()];
+    let mut harmonic = fundamental;
+    while harmonic < sample_rate / 2.0 {
+        let centre = (harmonic / bin_width).round() as usize;
+        let start = centre.saturating_sub(HARMONIC_HALF_WIDTH);
+        let end = (centre + HARMONIC_HALF_WIDTH + 1).min(power.len());
+        is_harmonic[start..end].iter_mut().for_each(|h| *h = true);
+        harmonic += fundamental;
+    }
+
+    let fundamental_bin = (fundamental / bin_width).round() as usize;
+    let mut harmonic_energy = 0.0;
+    let mut noise_energy = 0.0;
+    let mut below_fundamental = 0.0;
+    for (bin, &p) in power.iter().enumerate().skip(DC_BINS) {
+        if is_harmonic[bin] {
+            harmonic_energy += p;
+        } else {
+            noise_energy += p;
+            if bin < fundamental_bin {
+                below_fundamental += p;
+            }
+        }
+    }
+
+    let db = |ratio: f64| 10.0 * ratio.max(1e-30).log10();
+    SpectralReport {
+        alias_below_fundamental_db: db(below_fundamental / harmonic_energy),
+        harmonic_to_noise_db: db(harmonic_energy / noise_energy),
+        dc_offset,
+    }
+}
+
+/// Renders `ANALYSIS_LENGTH` samples of an oscillator, after letting it settle, and measures them.
+pub fn analyze_oscillator<S: Sample>(
+    mode: OscillatorMode,
+    path: OscillatorPath,
+    frequency: S,
+    sample_rate: S,
+) -> SpectralReport {
+    let mut osc = GenericOscillator::new(mode, frequency, sample_rate);
+    let mut tick = || match path {
+        OscillatorPath::Naive => osc.tick_naive(),
+        OscillatorPath::PolyBlep => osc.tick_poly_blep(),
+    };
+
+    // The PolyBLEP triangle's leaky integrator takes a while to settle.
+    for _ in 0..sample_rate.to_f64() as usize {
+        tick();
+    }
+    let signal: Vec<S> = (0..ANALYSIS_LENGTH).map(|_| tick()).collect();
+
+    analyze(&signal, frequency.to_f64(), sample_rate.to_f64())
+}
+
+/// Measures how a broadband signal's power spectral density falls with frequency, between `low`
+/// and `high` Hz, in dB/octave: 0 for white noise, -3 for pink and -6 for brown.
+///
+/// The spectrum is averaged over overlapping Hann-windowed segments, then over third-octave bands
+/// so every octave counts equally, and a straight line fitted to the bands' levels.
+pub fn spectral_slope<S: Sample>(signal: &[S], sample_rate: f64, low: f64, high: f64) -> f64 {
+    let window: Vec<f64> = (0..SLOPE_SEGMENT)
+        .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f64 / SLOPE_SEGMENT as f64).cos())
+        .collect();
+    let fft = FftPlanner::new().plan_fft_forward(SLOPE_SEGMENT);
+
+    let mut power = vec![0.0; SLOPE_SEGMENT / 2];
+    let mut start = 0;
+    while start + SLOPE_SEGMENT <= signal.len() {
+        let mut buffer: Vec<Complex<f64>> = signal[start..start + SLOPE_SEGMENT]
+            .iter()
+            .zip(window.iter())
+            .map(|(&x, &w)| Complex::new(x.to_f64() * w, 0.0))
+            .collect();
+        fft.process(&mut buffer);
+        for (p, c) in power.iter_mut().zip(buffer.iter()) {
+            *p += c.norm_sqr();
+        }
+        start += SLOPE_SEGMENT / 2;
+    }
+
+    let bin_width = sample_rate / SLOPE_SEGMENT as f64;
+    let bands = ((high / low).log2() * SLOPE_BANDS_PER_OCTAVE).floor() as usize;
+    let mut points = Vec::new();
+    for band in 0..bands {
+        let band_low = low * (band as f64 / SLOPE_BANDS_PER_OCTAVE).exp2();
+        let band_high = low * ((band + 1) as f64 / SLOPE_BANDS_PER_OCTAVE).exp2();
+        let bins = (band_low / bin_width).ceil() as usize..(band_high / bin_width).ceil() as usize;
+        if bins.is_empty() {
+            continue;
+        }
+
+        let density = power[bins.clone()].iter().sum::<f64>() / bins.len() as f64;
+        let octave = (band as f64 + 0.5) / SLOPE_BANDS_PER_OCTAVE;
+        points.push((octave, 10.0 * density.max(1e-30).log10()));
+    }
+
+    // Least-squares line through the bands' levels against their position in octaves.
+    let n = points.len() as f64;
+    let mean_x = points.iter().map(|p| p.0).sum::<f64>() / n;
+    let mean_y = points.iter().map(|p| p.1).sum::<f64>() / n;
+    let covariance: f64 = points.iter().map(|p| (p.0 - mean_x) * (p.1 - mean_y)).sum();
+    let variance: f64 = points.iter().map(|p| (p.0 - mean_x) * (p.0 - mean_x)).sum();
+
+    covariance / variance
+}
@@ -0,0 +1,343 @@
+//! Second-order IIR sections with the RBJ Audio EQ Cookbook designs, for EQ and tone shaping.
+
+use super::math;
+use super::sample::Sample;
+use super::traits::MonoProcessor;
+
+/// The responses `BiquadCoefficients::design` can produce.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum BiquadKind {
+    Lowpass,
+    Highpass,
+    /// Band pass with 0 dB gain at the centre frequency.
+    Bandpass,
+    Notch,
+    /// Flat magnitude, with the phase turning through 360 degrees around the frequency.
+    Allpass,
+    /// A bell boosting or cutting by the gain around the frequency.
+    Peaking,
+    /// Boosts or cuts by the gain below the frequency.
+    LowShelf,
+    /// Boosts or cuts by the gain above the frequency.
+    HighShelf,
+}
+
+/// Coefficients of a biquad's transfer function, normalised so a0 is 1:
+///
+/// H(z) = (b0 + b1 z^-1 + b2 z^-2) / (1 + a1 z^-1 + a2 z^-2)
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct BiquadCoefficients<S: Sample = f32> {
+    pub b0: S,
+    pub b1: S,
+    pub b2: S,
+    pub a1: S,
+    pub a2: S,
+}
+
+impl<S: Sample> BiquadCoefficients<S> {
+    /// Passes the input straight through.
+    pub const IDENTITY: BiquadCoefficients<S> = BiquadCoefficients {
+        b0: S::ONE,
+        b1: S::ZERO,
+        b2: S::ZERO,
+        a1: S::ZERO,
+        a2: S::ZERO,
+    };
+
+    /// Designs a section from the RBJ cookbook formulas. `gain_db` only applies to the peaking and
+    /// shelving kinds, and for the shelves `q` sets the steepness of the transition, with
+    /// 1/sqrt(2) the steepest that doesn't overshoot.
+    pub fn design(kind: BiquadKind, frequency: S, q: S, gain_db: S, sample_rate: S) -> BiquadCoefficients<S> {
+        let (one, two, half) = (S::ONE, S::TWO, S::HALF);
+
+        // Keep the frequency clear of DC and Nyquist, where the designs degenerate.
+        let frequency = frequency.clamp(S::from_f64(1e-3) * sample_rate, S::from_f64(0.49) * sample_rate);
+        let q = q.max(S::from_f64(1e-3));
+        let (sin, cos) = math::sin_cos(S::TWO_PI * frequency / sample_rate);
+        let alpha = sin / (two * q);
+        // The square root of the linear gain, as the peaking and shelving designs use it.
+        let a = S::from_f64(10.0).powf(gain_db / S::from_f64(40.0));
+
+        let (b0, b1, b2, a0, a1, a2) = match kind {
+            BiquadKind::Lowpass => {
+                let b1 = one - cos;
+                (half * b1, b1, half * b1, one + alpha, -two * cos, one - alpha)
+            }
+            BiquadKind::Highpass => {
+                let b1 = -(one + cos);
+                (-half * b1, b1, -half * b1, one + alpha, -two * cos, one - alpha)
+            }
+            BiquadKind::Bandpass => (alpha, S::ZERO, -alpha, one + alpha, -two * cos, one - alpha),
+            BiquadKind::Notch => (one, -two * cos, one, one + alpha, -two * cos, one - alpha),
+            BiquadKind::Allpass => (one - alpha, -two * cos, one + alpha, one + alpha, -two * cos, one - alpha),
+            BiquadKind::Peaking => (
+                one + alpha * a,
+                -two * cos,
+                one - alpha * a,
+                one + alpha / a,
+                -two * cos,
+                one - alpha / a,
+            ),
+            BiquadKind::LowShelf => {
+                let s = two * a.sqrt() * alpha;
+                (
+                    a * ((a + one) - (a - one) * cos + s),
+                    two * a * ((a - one) - (a + one) * cos),
+                    a * ((a + one) - (a - one) * cos - s),
+                    (a + one) + (a - one) * cos + s,
+                    -two * ((a - one) + (a + one) * cos),
+                    (a + one) + (a - one) * cos - s,
+                )
+            }
+            BiquadKind::HighShelf => {
+                let s = two * a.sqrt() * alpha;
+                (
+                    a * ((a + one) + (a - one) * cos + s),
+                    -two * a * ((a - one) + (a + one) * cos),
+                    a * ((a + one) + (a - one) * cos - s),
+                    (a + one) - (a - one) * cos + s,
+                    two * ((a - one) - (a + one) * cos),
+                    (a + one) - (a - one) * cos - s,
+                )
+            }
+        };
+
+        BiquadCoefficients {
+            b0: b0 / a0,
+            b1: b1 / a0,
+            b2: b2 / a0,
+            a1: a1 / a0,
+            a2: a2 / a0,
+        }
+    }
+}
+
+/// A biquad in Direct Form I, which keeps the input and output histories separately.
+///
+/// Its states can't overflow internally, which suits fixed coefficients with high gain, but
+/// changing the coefficients while it runs can click.
+pub struct DirectFormI<S: Sample = f32> {
+    coefficients: BiquadCoefficients<S>,
+    x1: S,
+    x2: S,
+    y1: S,
+    y2: S,
+}
+
+impl<S: Sample> DirectFormI<S> {
+    pub fn new(coefficients: BiquadCoefficients<S>) -> DirectFormI<S> {
+        DirectFormI {
+            coefficients,
+            x1: S::ZERO,
+            x2: S::ZERO,
+            y1: S::ZERO,
+            y2: S::ZERO,
+        }
+    }
+
+    pub fn set_coefficients(&mut self, coefficients: BiquadCoefficients<S>) {
+        self.coefficients = coefficients;
+    }
+
+    pub fn coefficients(&self) -> BiquadCoefficients<S> {
+        self.coefficients
+    }
+
+    pub fn reset(&mut self) {
+        self.x1 = S::ZERO;
+        self.x2 = S::ZERO;
+        self.y1 = S::ZERO;
+        self.y2 = S::ZERO;
+    }
+}
+
+impl<S: Sample> MonoProcessor<S> for DirectFormI<S> {
+    fn tick(&mut self, input: S) -> S {
+        let c = &self.coefficients;
+        let output = c.b0 * input + c.b1 * self.x1 + c.b2 * self.x2 - c.a1 * self.y1 - c.a2 * self.y2;
+
+        self.x2 = self.x1;
+        self.x1 = input;
+        self.y2 = self.y1;
+        self.y1 = output;
+
+        output
+    }
+}
+
+/// A biquad in Transposed Direct Form II, with two states instead of four.
+///
+/// It has the better floating-point behaviour of the two forms, and copes better with
+/// coefficients changing while it runs.
+pub struct TransposedDirectFormII<S: Sample = f32> {
+    coefficients: BiquadCoefficients<S>,
+    s1: S,
+    s2: S,
+}
+
+impl<S: Sample> TransposedDirectFormII<S> {
+    pub fn new(coefficients: BiquadCoefficients<S>) -> TransposedDirectFormII<S> {
+        TransposedDirectFormII {
+            coefficients,
+            s1: S::ZERO,
+            s2: S::ZERO,
+        }
+    }
+
+    pub fn set_coefficients(&mut self, coefficients: BiquadCoefficients<S>) {
+        self.coefficients = coefficients;
+    }
+
+    pub fn coefficients(&self) -> BiquadCoefficients<S> {
+        self.coefficients
+    }
+
+    pub fn reset(&mut self) {
+        self.s1 = S::ZERO;
+        self.s2 = S::ZERO;
+    }
+}
+
+impl<S: Sample> MonoProcessor<S> for TransposedDirectFormII<S> {
+    fn tick(&mut self, input: S) -> S {
+        let c = &self.coefficients;
+        let output = c.b0 * input + self.s1;
+        self.s1 = c.b1 * input - c.a1 * output + self.s2;
+        self.s2 = c.b2 * input - c.a2 * output;
+
+        output
+    }
+}
+
+/// A cookbook biquad set up by its frequency, Q and gain, running in Transposed Direct Form II.
+pub struct Biquad<S: Sample = f32> {
+    kind: BiquadKind,
+    sample_rate: S,
+    frequency: S,
+    q: S,
+    gain_db: S,
+    filter: TransposedDirectFormII<S>,
+}
+
+impl<S: Sample> Biquad<S> {
+    pub fn new(kind: BiquadKind, frequency: S, q: S, sample_rate: S) -> Biquad<S> {
+        let mut biquad = Biquad {
+            kind,
+            sample_rate,
+            frequency,
+            q,
+            gain_db: S::ZERO,
+            filter: TransposedDirectFormII::new(BiquadCoefficients::IDENTITY),
+        };
+        biquad.update_coefficients();
+
+        biquad
+    }
+
+    fn update_coefficients(&mut self) {
+        self.filter.set_coefficients(BiquadCoefficients::design(
+            self.kind,
+            self.frequency,
+            self.q,
+            self.gain_db,
+            self.sample_rate,
+        ));
+    }
+
+    pub fn set_kind(&mut self, kind: BiquadKind) {
+        self.kind = kind;
+        self.update_coefficients();
+    }
+
+    pub fn set_frequency(&mut self, frequency: S) {
+        self.frequency = frequency;
+        self.update_coefficients();
+    }
+
+    pub fn set_q(&mut self, q: S) {
+        self.q = q;
+        self.update_coefficients();
+    }
+
+    /// Sets the boost or cut in dB of the peaking and shelving kinds.
+    pub fn set_gain(&mut self, gain_db: S) {
+        self.gain_db = gain_db;
+        self.update_coefficients();
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: S) {
+        self.sample_rate = sample_rate;
+        self.update_coefficients();
+    }
+
+    pub fn coefficients(&self) -> BiquadCoefficients<S> {
+        self.filter.coefficients()
+    }
+
+    pub fn reset(&mut self) {
+        self.filter.reset();
+    }
+}
+
+impl<S: Sample> MonoProcessor<S> for Biquad<S> {
+    fn tick(&mut self, input: S) -> S {
+        self.filter.tick(input)
+    }
+}
+
+/// `N` biquad sections in series, for filters of order 2N.
+pub struct BiquadCascade<const N: usize, S: Sample = f32> {
+    sections: [TransposedDirectFormII<S>; N],
+}
+
+impl<const N: usize, S: Sample> BiquadCascade<N, S> {
+    pub fn new(coefficients: [BiquadCoefficients<S>; N]) -> BiquadCascade<N, S> {
+        BiquadCascade {
+            sections: coefficients.map(TransposedDirectFormII::new),
+        }
+    }
+
+    /// A Butterworth low or high pass of order 2N, maximally flat in the passband and 3 dB down at
+    /// `frequency`. Any other kind passes the input through.
+    pub fn butterworth(kind: BiquadKind, frequency: S, sample_rate: S) -> BiquadCascade<N, S> {
+        let mut cascade = BiquadCascade::new([BiquadCoefficients::IDENTITY; N]);
+        cascade.set_butterworth(kind, frequency, sample_rate);
+
+        cascade
+    }
+
+    /// Redesigns the cascade as `butterworth` would, keeping its state.
+    pub fn set_butterworth(&mut self, kind: BiquadKind, frequency: S, sample_rate: S) {
+        if kind != BiquadKind::Lowpass && kind != BiquadKind::Highpass {
+            self.sections.iter_mut().for_each(|s| s.set_coefficients(BiquadCoefficients::IDENTITY));
+            return;
+        }
+
+        // Each section takes one conjugate pair of the Butterworth poles, which sit evenly spaced
+        // round a semicircle.
+        let order = (2 * N) as f64;
+        for (k, section) in self.sections.iter_mut().enumerate() {
+            let (_, cos) = math::sin_cos(S::from_f64(core::f64::consts::PI * (2 * k + 1) as f64 / (2.0 * order)));
+            let q = S::ONE / (S::TWO * cos);
+            section.set_coefficients(BiquadCoefficients::design(kind, frequency, q, S::ZERO, sample_rate));
+        }
+    }
+
+    pub fn set_section(&mut self, index: usize, coefficients: BiquadCoefficients<S>) {
+        self.sections[index].set_coefficients(coefficients);
+    }
+
+    pub fn sections(&self) -> &[TransposedDirectFormII<S>; N] {
+        &self.sections
+    }
+
+    pub fn reset(&mut self) {
+        self.sections.iter_mut().for_each(|s| s.reset());
+    }
+}
+
+impl<const N: usize, S: Sample> MonoProcessor<S> for BiquadCascade<N, S> {
+    fn tick(&mut self, input: S) -> S {
+        self.sections.iter_mut().fold(input, |x, section| section.tick(x))
+    }
+}
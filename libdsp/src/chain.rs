@@ -0,0 +1,171 @@
+//! Combinators for building signal chains out of generators and processors.
+
+use super::sample::Sample;
+use super::traits::{MonoGenerator, MonoProcessor, StereoGenerator, StereoProcessor};
+
+/// A generator followed by a processor, which together act as a generator.
+pub struct Chain<G, P> {
+    pub generator: G,
+    pub processor: P,
+}
+
+impl<G, P> Chain<G, P> {
+    pub fn new(generator: G, processor: P) -> Chain<G, P> {
+        Chain { generator, processor }
+    }
+}
+
+impl<S: Sample, G: MonoGenerator<S>, P: MonoProcessor<S>> MonoGenerator<S> for Chain<G, P> {
+    fn tick(&mut self) -> S {
+        self.processor.tick(self.generator.tick())
+    }
+
+    fn fill(&mut self, buffer: &mut [S]) {
+        self.generator.fill(buffer);
+        self.processor.process(buffer);
+    }
+}
+
+impl<S: Sample, G: StereoGenerator<S>, P: StereoProcessor<S>> StereoGenerator<S> for Chain<G, P> {
+    fn tick(&mut self) -> (S, S) {
+        self.processor.tick(self.generator.tick())
+    }
+
+    fn fill(&mut self, left: &mut [S], right: &mut [S]) {
+        self.generator.fill(left, right);
+        self.processor.process(left, right);
+    }
+}
+
+/// Two processors in series, which together act as a processor.
+pub struct Series<A, B> {
+    pub first: A,
+    pub second: B,
+}
+
+impl<A, B> Series<A, B> {
+    pub fn new(first: A, second: B) -> Series<A, B> {
+        Series { first, second }
+    }
+}
+
+impl<S: Sample, A: MonoProcessor<S>, B: MonoProcessor<S>> MonoProcessor<S> for Series<A, B> {
+    fn tick(&mut self, input: S) -> S {
+        self.second.tick(self.first.tick(input))
+    }
+
+    fn process(&mut self, buffer: &mut [S]) {
+        self.first.process(buffer);
+        self.second.process(buffer);
+    }
+}
+
+impl<S: Sample, A: StereoProcessor<S>, B: StereoProcessor<S>> StereoProcessor<S> for Series<A, B> {
+    fn tick(&mut self, input: (S, S)) -> (S, S) {
+        self.second.tick(self.first.tick(input))
+    }
+
+    fn process(&mut self, left: &mut [S], right: &mut [S]) {
+        self.first.process(left, right);
+        self.second.process(left, right);
+    }
+}
+
+/// A pair of mono processors used as one stereo processor, one per channel.
+pub struct DualMono<P> {
+    pub left: P,
+    pub right: P,
+}
+
+impl<P> DualMono<P> {
+    pub fn new(left: P, right: P) -> DualMono<P> {
+        DualMono { left, right }
+    }
+}
+
+impl<S: Sample, P: MonoProcessor<S>> StereoProcessor<S> for DualMono<P> {
+    fn tick(&mut self, input: (S, S)) -> (S, S) {
+        (self.left.tick(input.0), self.right.tick(input.1))
+    }
+
+    fn process(&mut self, left: &mut [S], right: &mut [S]) {
+        self.left.process(left);
+        self.right.process(right);
+    }
+}
+
+/// Spreads a mono generator to both channels, so it can drive stereo processors.
+pub struct MonoToStereo<G> {
+    pub generator: G,
+}
+
+impl<G> MonoToStereo<G> {
+    pub fn new(generator: G) -> MonoToStereo<G> {
+        MonoToStereo { generator }
+    }
+}
+
+impl<S: Sample, G: MonoGenerator<S>> StereoGenerator<S> for MonoToStereo<G> {
+    fn tick(&mut self) -> (S, S) {
+        let x = self.generator.tick();
+        (x, x)
+    }
+
+    fn fill(&mut self, left: &mut [S], right: &mut [S]) {
+        let len = left.len().min(right.len());
+        self.generator.fill(&mut left[..len]);
+        right[..len].copy_from_slice(&left[..len]);
+    }
+
+    fn fill_frames(&mut self, frames: &mut [(S, S)]) {
+        self.generator.fill_frames(frames);
+    }
+}
+
+impl<S: Sample, P: MonoProcessor<S> + ?Sized> MonoProcessor<S> for &mut P {
+    fn tick(&mut self, input: S) -> S {
+        (**self).tick(input)
+    }
+
+    fn process(&mut self, buffer: &mut [S]) {
+        (**self).process(buffer)
+    }
+}
+
+impl<S: Sample, P: StereoProcessor<S> + ?Sized> StereoProcessor<S> for &mut P {
+    fn tick(&mut self, input: (S, S)) -> (S, S) {
+        (**self).tick(input)
+    }
+
+    fn process(&mut self, left: &mut [S], right: &mut [S]) {
+        (**self).process(left, right)
+    }
+}
+
+impl<S: Sample, G: MonoGenerator<S> + ?Sized> MonoGenerator<S> for &mut G {
+    fn tick(&mut self) -> S {
+        (**self).tick()
+    }
+
+    fn fill(&mut self, buffer: &mut [S]) {
+        (**self).fill(buffer)
+    }
+
+    fn fill_frames(&mut self, frames: &mut [(S, S)]) {
+        (**self).fill_frames(frames)
+    }
+}
+
+impl<S: Sample, G: StereoGenerator<S> + ?Sized> StereoGenerator<S> for &mut G {
+    fn tick(&mut self) -> (S, S) {
+        (**self).tick()
+    }
+
+    fn fill(&mut self, left: &mut [S], right: &mut [S]) {
+        (**self).fill(left, right)
+    }
+
+    fn fill_frames(&mut self, frames: &mut [(S, S)]) {
+        (**self).fill_frames(frames)
+    }
+}
@@ -0,0 +1,294 @@
+//! Delay lines over caller-supplied memory, and a stereo delay effect built on them.
+//!
+//! Delay memory is borrowed rather than owned so that long buffers can live wherever the platform
+//! has room, such as the Daisy's external SDRAM.
+
+use super::biquad::{Biquad, BiquadKind};
+use super::sample::Sample;
+use super::traits::{MonoProcessor, StereoProcessor};
+
+/// How a `DelayLine` reads between samples.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Interpolation {
+    /// Straight-line between the two nearest samples. Cheap, but dulls the highs at fractional
+    /// delays.
+    Linear,
+    /// Four-point Hermite, keeping more of the highs. Suits modulated delays.
+    Cubic,
+    /// A first-order allpass, which keeps the magnitude flat. Its state makes it click when the
+    /// delay jumps, so it suits fixed or slowly moving delays, read once per sample.
+    Allpass,
+}
+
+/// A circular buffer read at fractional delays.
+///
+/// Delays are in samples, counted from the next write: a delay of 1.0 reads the sample written
+/// last. They're clamped to between 1.0 and two less than the buffer's length.
+pub struct DelayLine<'a, S: Sample = f32> {
+    buffer: &'a mut [S],
+    /// Where the next sample is written.
+    write_index: usize,
+    delay: S,
+    interpolation: Interpolation,
+    allpass_previous: S,
+}
+
+impl<'a, S: Sample> DelayLine<'a, S> {
+    /// Takes over `buffer` as the line's memory, clearing it. The buffer needs at least 4 samples.
+    pub fn new(buffer: &'a mut [S]) -> DelayLine<'a, S> {
+        assert!(buffer.len() >= 4, "delay buffer too short");
+        buffer.iter_mut().for_each(|x| *x = S::ZERO);
+
+        DelayLine {
+            buffer,
+            write_index: 0,
+            delay: S::ONE,
+            interpolation: Interpolation::Linear,
+            allpass_previous: S::ZERO,
+        }
+    }
+
+    /// The longest delay the buffer allows, in samples.
+    pub fn max_delay(&self) -> S {
+        S::from_f64((self.buffer.len() - 2) as f64)
+    }
+
+    /// Sets the delay `tick` reads at, in samples.
+    pub fn set_delay(&mut self, delay: S) {
+        self.delay = delay.clamp(S::ONE, self.max_delay());
+    }
+
+    pub fn delay(&self) -> S {
+        self.delay
+    }
+
+    pub fn set_interpolation(&mut self, interpolation: Interpolation) {
+        self.interpolation = interpolation;
+        self.allpass_previous = S::ZERO;
+    }
+
+    pub fn reset(&mut self) {
+        self.buffer.iter_mut().for_each(|x| *x = S::ZERO);
+        self.allpass_previous = S::ZERO;
+    }
+
+    /// The sample written `delay` writes ago, for whole delays from 1 up to the buffer's length.
+    #[inline(always)]
+    fn sample(&self, delay: usize) -> S {
+        // Cheaper than a modulo, which matters with a dozen lines read every sample.
+        let index = self.write_index + self.buffer.len() - delay;
+        if index >= self.buffer.len() {
+            self.buffer[index - self.buffer.len()]
+        } else {
+            self.buffer[index]
+        }
+    }
+
+    /// Reads at a fractional delay in samples, with the line's interpolation.
+    pub fn read(&mut self, delay: S) -> S {
+        let delay = delay.clamp(S::ONE, self.max_delay());
+        let whole = delay.floor();
+        let mut index = whole.to_f64() as usize;
+        let mut fraction = delay - whole;
+
+        match self.interpolation {
+            Interpolation::Linear => {
+                let (a, b) = (self.sample(index), self.sample(index + 1));
+                a + (b - a) * fraction
+            }
+            Interpolation::Cubic => {
+                // The sample after the nearest isn't written yet at the shortest delays.
+                let newer = self.sample(index.max(2) - 1);
+                let (x0, x1, x2) = (self.sample(index), self.sample(index + 1), self.sample(index + 2));
+
+                let c1 = S::HALF * (x1 - newer);
+                let c2 = newer - S::from_f64(2.5) * x0 + S::TWO * x1 - S::HALF * x2;
+                let c3 = S::HALF * (x2 - newer) + S::from_f64(1.5) * (x0 - x1);
+                ((c3 * fraction + c2) * fraction + c1) * fraction + x0
+            }
+            Interpolation::Allpass => {
+                // The allpass is poorly behaved for fractions near zero, so those borrow a whole
+                // sample from the integer part.
+                if fraction < S::from_f64(0.1) && index > 1 {
+                    index -= 1;
+                    fraction += S::ONE;
+                }
+                let coefficient = (S::ONE - fraction) / (S::ONE + fraction);
+                let output = coefficient * (self.sample(index) - self.allpass_previous) + self.sample(index + 1);
+                self.allpass_previous = output;
+                output
+            }
+        }
+    }
+
+    /// Writes the next sample, overwriting the oldest.
+    pub fn write(&mut self, input: S) {
+        self.buffer[self.write_index] = input;
+        self.write_index += 1;
+        if self.write_index == self.buffer.len() {
+            self.write_index = 0;
+        }
+    }
+}
+
+impl<'a, S: Sample> MonoProcessor<S> for DelayLine<'a, S> {
+    /// Reads at the set delay, then writes the input.
+    fn tick(&mut self, input: S) -> S {
+        let output = self.read(self.delay);
+        self.write(input);
+
+        output
+    }
+}
+
+/// How a `StereoDelay`'s channels feed back.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum DelayMode {
+    /// Each channel repeats on its own side.
+    Stereo,
+    /// The input, summed to mono, repeats alternately left and right.
+    PingPong,
+}
+
+/// Seconds the delay time takes to glide most of the way to a new setting, which bends the pitch
+/// of the repeats like a tape delay rather than clicking.
+const TIME_SMOOTHING: f64 = 0.05;
+
+/// Highest feedback allowed, which stays just short of repeating forever.
+const MAX_FEEDBACK: f64 = 0.99;
+
+/// A stereo echo with filtered feedback, in stereo or ping-pong mode.
+///
+/// The feedback path runs through a low pass and a high pass, so each repeat comes back darker and
+/// thinner, as on an analogue delay.
+pub struct StereoDelay<'a, S: Sample = f32> {
+    left: DelayLine<'a, S>,
+    right: DelayLine<'a, S>,
+    mode: DelayMode,
+    sample_rate: S,
+    /// Target delay of each channel, in samples.
+    target: (S, S),
+    time_coefficient: S,
+    feedback: S,
+    mix: S,
+    damping: (Biquad<S>, Biquad<S>),
+    low_cut: (Biquad<S>, Biquad<S>),
+}
+
+impl<'a, S: Sample> StereoDelay<'a, S> {
+    /// Uses `left` and `right` as the channels' delay memory, which bounds the longest delay.
+    pub fn new(left: &'a mut [S], right: &'a mut [S], sample_rate: S) -> StereoDelay<'a, S> {
+        let q = S::from_f64(core::f64::consts::FRAC_1_SQRT_2);
+        let damping = || Biquad::new(BiquadKind::Lowpass, S::from_f64(6000.0), q, sample_rate);
+        let low_cut = || Biquad::new(BiquadKind::Highpass, S::from_f64(80.0), q, sample_rate);
+
+        let mut delay = StereoDelay {
+            left: DelayLine::new(left),
+            right: DelayLine::new(right),
+            mode: DelayMode::Stereo,
+            sample_rate,
+            target: (S::ONE, S::ONE),
+            time_coefficient: S::ZERO,
+            feedback: S::from_f64(0.4),
+            mix: S::HALF,
+            damping: (damping(), damping()),
+            low_cut: (low_cut(), low_cut()),
+        };
+        delay.update_time_coefficient();
+        delay.set_time(S::from_f64(0.25));
+        delay.left.set_delay(delay.target.0);
+        delay.right.set_delay(delay.target.1);
+
+        delay
+    }
+
+    fn update_time_coefficient(&mut self) {
+        self.time_coefficient = S::ONE - (-S::ONE / (S::from_f64(TIME_SMOOTHING) * self.sample_rate)).exp();
+    }
+
+    pub fn set_mode(&mut self, mode: DelayMode) {
+        self.mode = mode;
+    }
+
+    /// Sets both channels' delay in seconds.
+    pub fn set_time(&mut self, seconds: S) {
+        self.set_times(seconds, seconds);
+    }
+
+    /// Sets each channel's delay in seconds. In ping-pong mode the left time is the first repeat
+    /// and the right time the gap to the second.
+    pub fn set_times(&mut self, left: S, right: S) {
+        self.target = (
+            (left * self.sample_rate).clamp(S::ONE, self.left.max_delay()),
+            (right * self.sample_rate).clamp(S::ONE, self.right.max_delay()),
+        );
+    }
+
+    /// Sets how much of each repeat comes back, from 0.0 up to 0.99.
+    pub fn set_feedback(&mut self, feedback: S) {
+        self.feedback = feedback.clamp(S::ZERO, S::from_f64(MAX_FEEDBACK));
+    }
+
+    /// Sets the balance from dry (0.0) to only the repeats (1.0).
+    pub fn set_mix(&mut self, mix: S) {
+        self.mix = mix.clamp(S::ZERO, S::ONE);
+    }
+
+    /// Sets the cutoff in Hz of the low pass in the feedback path.
+    pub fn set_damping(&mut self, cutoff: S) {
+        self.damping.0.set_frequency(cutoff);
+        self.damping.1.set_frequency(cutoff);
+    }
+
+    /// Sets the cutoff in Hz of the high pass in the feedback path.
+    pub fn set_low_cut(&mut self, cutoff: S) {
+        self.low_cut.0.set_frequency(cutoff);
+        self.low_cut.1.set_frequency(cutoff);
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: S) {
+        let seconds = (self.target.0 / self.sample_rate, self.target.1 / self.sample_rate);
+        self.sample_rate = sample_rate;
+        self.update_time_coefficient();
+        self.set_times(seconds.0, seconds.1);
+        for filter in [&mut self.damping.0, &mut self.damping.1, &mut self.low_cut.0, &mut self.low_cut.1] {
+            filter.set_sample_rate(sample_rate);
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.left.reset();
+        self.right.reset();
+        for filter in [&mut self.damping.0, &mut self.damping.1, &mut self.low_cut.0, &mut self.low_cut.1] {
+            filter.reset();
+        }
+    }
+}
+
+impl<'a, S: Sample> StereoProcessor<S> for StereoDelay<'a, S> {
+    fn tick(&mut self, input: (S, S)) -> (S, S) {
+        let (left, right) = (&mut self.left, &mut self.right);
+        left.set_delay(left.delay() + (self.target.0 - left.delay()) * self.time_coefficient);
+        right.set_delay(right.delay() + (self.target.1 - right.delay()) * self.time_coefficient);
+
+        let wet = (left.read(left.delay()), right.read(right.delay()));
+        let returned = (
+            self.feedback * self.low_cut.0.tick(self.damping.0.tick(wet.0)),
+            self.feedback * self.low_cut.1.tick(self.damping.1.tick(wet.1)),
+        );
+
+        match self.mode {
+            DelayMode::Stereo => {
+                left.write(input.0 + returned.0);
+                right.write(input.1 + returned.1);
+            }
+            DelayMode::PingPong => {
+                left.write(S::HALF * (input.0 + input.1) + returned.1);
+                right.write(returned.0);
+            }
+        }
+
+        let dry = S::ONE - self.mix;
+        (dry * input.0 + self.mix * wet.0, dry * input.1 + self.mix * wet.1)
+    }
+}
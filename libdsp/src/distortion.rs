@@ -0,0 +1,230 @@
+//! Waveshaping distortion, with antiderivative antialiasing or oversampling to keep the
+//! harmonics it adds from folding back below Nyquist.
+
+use super::biquad::{BiquadCascade, BiquadCoefficients, BiquadKind};
+use super::sample::Sample;
+use super::traits::MonoProcessor;
+
+/// The transfer curves a `Distortion` can apply.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum DistortionKind {
+    /// Smooth symmetric saturation, levelling out at ±1.
+    Tanh,
+    /// Linear up to ±1 and flat beyond. Harsh, and the worst for aliasing.
+    HardClip,
+    /// Tanh biased off-centre like a triode's operating point, so the positive half clips sooner
+    /// than the negative and the output gains even harmonics.
+    Tube,
+    /// Folding cells in parallel with the input, as in the Buchla 259, which fold the wave back
+    /// each time it passes ±1, five times over before it flattens out.
+    Wavefolder,
+}
+
+/// How a `Distortion` keeps its harmonics from aliasing.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Antialiasing {
+    /// The curve applied sample by sample.
+    Off,
+    /// First-order antiderivative antialiasing: the curve averaged over the straight line between
+    /// each pair of input samples, taken from the difference of its antiderivative. It costs little
+    /// more than the curve itself, and delays the signal by half a sample.
+    Adaa,
+    /// The curve run at four times the sample rate between eighth-order Butterworth low passes,
+    /// for several times the cost of ADAA. Cleaner than ADAA at high frequencies for the tanh,
+    /// clip and tube curves, but not for the wavefolder, whose harmonics reach far past four
+    /// times the sample rate.
+    Oversampled,
+}
+
+/// How much faster than the sample rate the oversampled curve runs.
+const OVERSAMPLING: usize = 4;
+
+/// Below this difference between successive inputs, relative to their size, ADAA uses the curve at
+/// their midpoint rather than dividing by a difference that's mostly rounding error. The
+/// antiderivatives' rounding error grows with the input, so the threshold does too.
+const ADAA_THRESHOLD: f64 = 1e-3;
+
+/// The tube curve's bias into the tanh.
+const TUBE_BIAS: f64 = 0.5;
+
+/// Thresholds of the wavefolder's cells.
+const FOLD_THRESHOLDS: [f64; 6] = [1.0, 3.0, 5.0, 7.0, 9.0, 11.0];
+
+/// Gains of the wavefolder's cells. Each turns the slope from 1 to -1 or back, so the output
+/// bounces between ±1, until the last levels it out.
+const FOLD_GAINS: [f64; 6] = [-2.0, 2.0, -2.0, 2.0, -2.0, 1.0];
+
+/// Corner in Hz of the high pass that takes out the tube curve's DC offset.
+const DC_BLOCK_FREQUENCY: f64 = 10.0;
+
+/// A waveshaper with selectable curve, drive and antialiasing.
+pub struct Distortion<S: Sample = f32> {
+    kind: DistortionKind,
+    antialiasing: Antialiasing,
+    drive: S,
+    sample_rate: S,
+    /// The last driven input and the curve's antiderivative there, for ADAA.
+    previous: S,
+    previous_antiderivative: S,
+    upsampler: BiquadCascade<4, S>,
+    downsampler: BiquadCascade<4, S>,
+    /// tanh of the tube bias, which is subtracted so silence stays silent.
+    tube_offset: S,
+    dc_coefficient: S,
+    dc_input: S,
+    dc_output: S,
+}
+
+impl<S: Sample> Distortion<S> {
+    pub fn new(kind: DistortionKind, sample_rate: S) -> Distortion<S> {
+        let mut distortion = Distortion {
+            kind,
+            antialiasing: Antialiasing::Adaa,
+            drive: S::ONE,
+            sample_rate,
+            previous: S::ZERO,
+            previous_antiderivative: S::ZERO,
+            upsampler: BiquadCascade::new([BiquadCoefficients::IDENTITY; 4]),
+            downsampler: BiquadCascade::new([BiquadCoefficients::IDENTITY; 4]),
+            tube_offset: S::from_f64(TUBE_BIAS).tanh(),
+            dc_coefficient: S::ZERO,
+            dc_input: S::ZERO,
+            dc_output: S::ZERO,
+        };
+        distortion.set_sample_rate(sample_rate);
+        distortion.reset();
+
+        distortion
+    }
+
+    pub fn set_kind(&mut self, kind: DistortionKind) {
+        self.kind = kind;
+        self.previous_antiderivative = self.antiderivative(self.previous);
+    }
+
+    pub fn kind(&self) -> DistortionKind {
+        self.kind
+    }
+
+    pub fn set_antialiasing(&mut self, antialiasing: Antialiasing) {
+        self.antialiasing = antialiasing;
+        self.reset();
+    }
+
+    /// Sets the gain into the curve. At 1.0 a full-scale input just reaches the curve's knee.
+    pub fn set_drive(&mut self, drive: S) {
+        self.drive = drive.max(S::ZERO);
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: S) {
+        self.sample_rate = sample_rate;
+
+        let rate = sample_rate * S::from_f64(OVERSAMPLING as f64);
+        let cutoff = S::from_f64(0.45) * sample_rate;
+        self.upsampler.set_butterworth(BiquadKind::Lowpass, cutoff, rate);
+        self.downsampler.set_butterworth(BiquadKind::Lowpass, cutoff, rate);
+        self.dc_coefficient = S::ONE - S::TWO_PI * S::from_f64(DC_BLOCK_FREQUENCY) / sample_rate;
+    }
+
+    pub fn reset(&mut self) {
+        self.previous = S::ZERO;
+        self.previous_antiderivative = self.antiderivative(S::ZERO);
+        self.upsampler.reset();
+        self.downsampler.reset();
+        self.dc_input = S::ZERO;
+        self.dc_output = S::ZERO;
+    }
+
+    /// The curve.
+    fn shape(&self, x: S) -> S {
+        match self.kind {
+            DistortionKind::Tanh => x.tanh(),
+            DistortionKind::HardClip => x.clamp(-S::ONE, S::ONE),
+            DistortionKind::Tube => (x + S::from_f64(TUBE_BIAS)).tanh() - self.tube_offset,
+            DistortionKind::Wavefolder => {
+                let mut cells = S::ZERO;
+                for (&threshold, &gain) in FOLD_THRESHOLDS.iter().zip(FOLD_GAINS.iter()) {
+                    cells += S::from_f64(gain) * (x.abs() - S::from_f64(threshold)).max(S::ZERO);
+                }
+                if x < S::ZERO { x - cells } else { x + cells }
+            }
+        }
+    }
+
+    /// An antiderivative of the curve.
+    fn antiderivative(&self, x: S) -> S {
+        match self.kind {
+            DistortionKind::Tanh => log_cosh(x),
+            DistortionKind::HardClip => {
+                if x.abs() <= S::ONE {
+                    S::HALF * x * x
+                } else {
+                    x.abs() - S::HALF
+                }
+            }
+            DistortionKind::Tube => log_cosh(x + S::from_f64(TUBE_BIAS)) - self.tube_offset * x,
+            DistortionKind::Wavefolder => {
+                let mut cells = S::ZERO;
+                for (&threshold, &gain) in FOLD_THRESHOLDS.iter().zip(FOLD_GAINS.iter()) {
+                    let over = (x.abs() - S::from_f64(threshold)).max(S::ZERO);
+                    cells += S::from_f64(gain) * over * over;
+                }
+                S::HALF * (x * x + cells)
+            }
+        }
+    }
+
+    fn shape_adaa(&mut self, x: S) -> S {
+        let antiderivative = self.antiderivative(x);
+        let difference = x - self.previous;
+        let threshold = S::from_f64(ADAA_THRESHOLD) * (S::ONE + x.abs().max(self.previous.abs()));
+        let y = if difference.abs() < threshold {
+            self.shape(S::HALF * (x + self.previous))
+        } else {
+            (antiderivative - self.previous_antiderivative) / difference
+        };
+        self.previous = x;
+        self.previous_antiderivative = antiderivative;
+
+        y
+    }
+
+    fn shape_oversampled(&mut self, x: S) -> S {
+        // Zero-stuffed up, with the gain made up, and decimated by keeping the last sample.
+        let mut y = S::ZERO;
+        for i in 0..OVERSAMPLING {
+            let stuffed = if i == 0 { S::from_f64(OVERSAMPLING as f64) * x } else { S::ZERO };
+            let upsampled = self.upsampler.tick(stuffed);
+            y = self.downsampler.tick(self.shape(upsampled));
+        }
+
+        y
+    }
+}
+
+impl<S: Sample> MonoProcessor<S> for Distortion<S> {
+    fn tick(&mut self, input: S) -> S {
+        let x = input * self.drive;
+        let y = match self.antialiasing {
+            Antialiasing::Off => self.shape(x),
+            Antialiasing::Adaa => self.shape_adaa(x),
+            Antialiasing::Oversampled => self.shape_oversampled(x),
+        };
+
+        if self.kind == DistortionKind::Tube {
+            // One-pole DC blocker, as the coupling capacitor after a tube stage.
+            self.dc_output = y - self.dc_input + self.dc_coefficient * self.dc_output;
+            self.dc_input = y;
+            self.dc_output
+        } else {
+            y
+        }
+    }
+}
+
+/// ln(cosh(x)), the antiderivative of tanh, written so it doesn't overflow for large inputs.
+#[inline(always)]
+fn log_cosh<S: Sample>(x: S) -> S {
+    let x = x.abs();
+    x + (-S::TWO * x).exp().log1p() - S::from_f64(core::f64::consts::LN_2)
+}
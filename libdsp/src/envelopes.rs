@@ -0,0 +1,254 @@
+use super::SampleType;
+use super::traits::MonoGenerator;
+
+// How far past its target each exponential segment aims, as a fraction of full scale. A segment
+// heads towards `target + ratio` like an RC circuit and is cut off when it crosses the target, so
+// smaller ratios give more strongly curved segments.
+const ATTACK_TARGET_RATIO: f64 = 0.3;
+const DECAY_RELEASE_TARGET_RATIO: f64 = 0.0001;
+
+// ln((1 + ratio) / ratio) for each ratio above: the number of time constants a segment takes.
+const ATTACK_TIME_CONSTANTS: f64 = 1.466_337_068_793_427_5;
+const DECAY_RELEASE_TIME_CONSTANTS: f64 = 9.210_440_366_976_517;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum EnvelopeCurve {
+    Linear,
+    /// RC-style segments: a convex attack and concave decay and release.
+    Exponential,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum TriggerMode {
+    /// Every gate on restarts the attack from the current level.
+    Retrigger,
+    /// A gate on while the gate is already held (overlapping notes) doesn't restart the envelope.
+    Legato,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum EnvelopeStage {
+    Idle,
+    Attack,
+    Decay,
+    Sustain,
+    Release,
+}
+
+/// Per-sample update for one segment: `level = base + level * coefficient` for exponential
+/// curves, or `level += base` for linear ones. Kept in f64: a long segment's steps are too small
+/// against the level for f32 to add them up accurately.
+#[derive(Clone, Copy)]
+struct Segment {
+    coefficient: f64,
+    base: f64,
+}
+
+pub struct Adsr {
+    sample_rate: SampleType,
+    attack: SampleType,
+    decay: SampleType,
+    sustain: SampleType,
+    release: SampleType,
+    curve: EnvelopeCurve,
+    trigger_mode: TriggerMode,
+    stage: EnvelopeStage,
+    gate: bool,
+    level: f64,
+    attack_segment: Segment,
+    decay_segment: Segment,
+    release_segment: Segment,
+}
+
+impl Adsr {
+    /// Creates an envelope with times in seconds and a sustain level from 0.0 to 1.0.
+    pub fn new(
+        attack: SampleType,
+        decay: SampleType,
+        sustain: SampleType,
+        release: SampleType,
+        sample_rate: SampleType,
+    ) -> Adsr {
+        let mut env = Adsr {
+            sample_rate,
+            attack,
+            decay,
+            sustain: sustain.clamp(0.0, 1.0),
+            release,
+            curve: EnvelopeCurve::Exponential,
+            trigger_mode: TriggerMode::Retrigger,
+            stage: EnvelopeStage::Idle,
+            gate: false,
+            level: 0.0,
+            attack_segment: Segment { coefficient: 0.0, base: 0.0 },
+            decay_segment: Segment { coefficient: 0.0, base: 0.0 },
+            release_segment: Segment { coefficient: 0.0, base: 0.0 },
+        };
+        env.update_segments();
+
+        env
+    }
+
+    fn samples(&self, seconds: SampleType) -> f64 {
+        (seconds as f64 * self.sample_rate as f64).max(1.0)
+    }
+
+    fn update_segments(&mut self) {
+        let attack = self.samples(self.attack);
+        let decay = self.samples(self.decay);
+        let release = self.samples(self.release);
+
+        match self.curve {
+            EnvelopeCurve::Linear => {
+                self.attack_segment = Segment { coefficient: 1.0, base: 1.0 / attack };
+                self.decay_segment = Segment { coefficient: 1.0, base: -(1.0 - self.sustain as f64) / decay };
+                // The release slope depends on the level the release starts from, so that it
+                // always takes the release time. It's set when the gate goes off.
+                self.release_segment = Segment { coefficient: 1.0, base: -self.level / release };
+            }
+            EnvelopeCurve::Exponential => {
+                let coefficient = libm::exp(-ATTACK_TIME_CONSTANTS / attack);
+                self.attack_segment = Segment {
+                    coefficient,
+                    base: (1.0 + ATTACK_TARGET_RATIO) * (1.0 - coefficient),
+                };
+
+                let coefficient = libm::exp(-DECAY_RELEASE_TIME_CONSTANTS / decay);
+                self.decay_segment = Segment {
+                    coefficient,
+                    base: (self.sustain as f64 - DECAY_RELEASE_TARGET_RATIO) * (1.0 - coefficient),
+                };
+
+                let coefficient = libm::exp(-DECAY_RELEASE_TIME_CONSTANTS / release);
+                self.release_segment = Segment {
+                    coefficient,
+                    base: -DECAY_RELEASE_TARGET_RATIO * (1.0 - coefficient),
+                };
+            }
+        }
+    }
+
+    pub fn set_attack(&mut self, seconds: SampleType) {
+        self.attack = seconds;
+        self.update_segments();
+    }
+
+    pub fn set_decay(&mut self, seconds: SampleType) {
+        self.decay = seconds;
+        self.update_segments();
+    }
+
+    pub fn set_sustain(&mut self, level: SampleType) {
+        self.sustain = level.clamp(0.0, 1.0);
+        self.update_segments();
+    }
+
+    pub fn set_release(&mut self, seconds: SampleType) {
+        self.release = seconds;
+        self.update_segments();
+    }
+
+    pub fn set_curve(&mut self, curve: EnvelopeCurve) {
+        self.curve = curve;
+        self.update_segments();
+    }
+
+    pub fn set_trigger_mode(&mut self, trigger_mode: TriggerMode) {
+        self.trigger_mode = trigger_mode;
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: SampleType) {
+        self.sample_rate = sample_rate;
+        self.update_segments();
+    }
+
+    pub fn gate_on(&mut self) {
+        if !(self.gate && self.trigger_mode == TriggerMode::Legato) {
+            self.stage = EnvelopeStage::Attack;
+        }
+        self.gate = true;
+    }
+
+    pub fn gate_off(&mut self) {
+        self.gate = false;
+        if self.stage != EnvelopeStage::Idle {
+            self.stage = EnvelopeStage::Release;
+            if self.curve == EnvelopeCurve::Linear {
+                self.release_segment.base = -self.level / self.samples(self.release);
+            }
+        }
+    }
+
+    pub fn set_gate(&mut self, gate: bool) {
+        if gate {
+            self.gate_on();
+        } else {
+            self.gate_off();
+        }
+    }
+
+    /// Silences the envelope immediately.
+    pub fn reset(&mut self) {
+        self.gate = false;
+        self.stage = EnvelopeStage::Idle;
+        self.level = 0.0;
+    }
+
+    pub fn stage(&self) -> EnvelopeStage {
+        self.stage
+    }
+
+    /// Whether the envelope is producing output, i.e. hasn't finished its release.
+    pub fn is_active(&self) -> bool {
+        self.stage != EnvelopeStage::Idle
+    }
+
+    pub fn level(&self) -> SampleType {
+        self.level as SampleType
+    }
+
+    fn step(&self, segment: Segment) -> f64 {
+        segment.base + self.level * segment.coefficient
+    }
+}
+
+impl MonoGenerator for Adsr {
+    fn tick(&mut self) -> SampleType {
+        match self.stage {
+            EnvelopeStage::Idle => {}
+            EnvelopeStage::Attack => {
+                self.level = self.step(self.attack_segment);
+                if self.level >= 1.0 {
+                    self.level = 1.0;
+                    self.stage = EnvelopeStage::Decay;
+                }
+            }
+            EnvelopeStage::Decay => {
+                // Close to the sustain level an exponential step can round to nothing, and the
+                // level would stall just above it for good, so a step that doesn't move counts as
+                // arriving.
+                let level = self.step(self.decay_segment);
+                let stalled = level >= self.level;
+                self.level = level;
+                if self.level <= self.sustain as f64 || stalled {
+                    self.level = self.sustain as f64;
+                    self.stage = EnvelopeStage::Sustain;
+                }
+            }
+            EnvelopeStage::Sustain => {
+                self.level = self.sustain as f64;
+            }
+            EnvelopeStage::Release => {
+                let level = self.step(self.release_segment);
+                let stalled = level >= self.level;
+                self.level = level;
+                if self.level <= 0.0 || stalled {
+                    self.level = 0.0;
+                    self.stage = EnvelopeStage::Idle;
+                }
+            }
+        }
+
+        self.level as SampleType
+    }
+}
@@ -0,0 +1,304 @@
+use super::biquad::{BiquadCascade, BiquadCoefficients, BiquadKind};
+use super::math;
+use super::sample::Sample;
+use super::traits::MonoProcessor;
+
+/// All four responses of a `StateVariableFilter` for one input sample.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct SvfOutput<S: Sample = f32> {
+    pub lowpass: S,
+    pub highpass: S,
+    pub bandpass: S,
+    pub notch: S,
+}
+
+/// Which response a `StateVariableFilter` produces when used as a `MonoProcessor`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum SvfMode {
+    Lowpass,
+    Highpass,
+    Bandpass,
+    Notch,
+}
+
+/// Zero-delay-feedback state-variable filter, discretised with the trapezoidal (TPT) integrator.
+///
+/// The integrator states are stored in a form that stays stable when the cutoff is changed every
+/// sample, so the cutoff can be modulated at audio rate.
+pub struct StateVariableFilter<S: Sample = f32> {
+    mode: SvfMode,
+    sample_rate: S,
+    cutoff: S,
+    resonance: S,
+    k: S,
+    a1: S,
+    a2: S,
+    a3: S,
+    ic1eq: S,
+    ic2eq: S,
+}
+
+impl<S: Sample> StateVariableFilter<S> {
+    pub fn new(cutoff: S, resonance: S, sample_rate: S) -> StateVariableFilter<S> {
+        let mut filter = StateVariableFilter {
+            mode: SvfMode::Lowpass,
+            sample_rate,
+            cutoff,
+            resonance: resonance.clamp(S::ZERO, S::ONE),
+            k: S::ZERO,
+            a1: S::ZERO,
+            a2: S::ZERO,
+            a3: S::ZERO,
+            ic1eq: S::ZERO,
+            ic2eq: S::ZERO,
+        };
+        filter.update_coefficients();
+
+        filter
+    }
+
+    fn update_coefficients(&mut self) {
+        // Keep the cutoff clear of Nyquist, where the prewarped gain goes to infinity.
+        let cutoff = self.cutoff.clamp(S::ZERO, self.sample_rate * S::from_f64(0.49));
+        let g = math::tan(S::PI * cutoff / self.sample_rate);
+
+        self.k = S::TWO - S::TWO * self.resonance;
+        self.a1 = S::ONE / (S::ONE + g * (g + self.k));
+        self.a2 = g * self.a1;
+        self.a3 = g * self.a2;
+    }
+
+    pub fn set_mode(&mut self, mode: SvfMode) {
+        self.mode = mode;
+    }
+
+    pub fn set_cutoff(&mut self, cutoff: S) {
+        self.cutoff = cutoff;
+        self.update_coefficients();
+    }
+
+    /// Sets the resonance from 0.0 (Q of 0.5) up to 1.0 (undamped).
+    pub fn set_resonance(&mut self, resonance: S) {
+        self.resonance = resonance.clamp(S::ZERO, S::ONE);
+        self.update_coefficients();
+    }
+
+    /// Sets the resonance as a quality factor instead.
+    pub fn set_q(&mut self, q: S) {
+        self.set_resonance(S::ONE - S::HALF / q);
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: S) {
+        self.sample_rate = sample_rate;
+        self.update_coefficients();
+    }
+
+    pub fn reset(&mut self) {
+        self.ic1eq = S::ZERO;
+        self.ic2eq = S::ZERO;
+    }
+
+    /// Processes one sample, returning every response at once.
+    pub fn tick_all(&mut self, input: S) -> SvfOutput<S> {
+        let v3 = input - self.ic2eq;
+        let v1 = self.a1 * self.ic1eq + self.a2 * v3;
+        let v2 = self.ic2eq + self.a2 * self.ic1eq + self.a3 * v3;
+        self.ic1eq = S::TWO * v1 - self.ic1eq;
+        self.ic2eq = S::TWO * v2 - self.ic2eq;
+
+        let highpass = input - self.k * v1 - v2;
+        SvfOutput {
+            lowpass: v2,
+            highpass,
+            bandpass: v1,
+            notch: v2 + highpass,
+        }
+    }
+}
+
+impl<S: Sample> MonoProcessor<S> for StateVariableFilter<S> {
+    fn tick(&mut self, input: S) -> S {
+        let output = self.tick_all(input);
+        match self.mode {
+            SvfMode::Lowpass => output.lowpass,
+            SvfMode::Highpass => output.highpass,
+            SvfMode::Bandpass => output.bandpass,
+            SvfMode::Notch => output.notch,
+        }
+    }
+}
+
+/// Feedback around the four poles at which the loop gain at the cutoff reaches 1, so the ladder
+/// starts to self-oscillate. A resonance of 1.0 maps to this.
+const LADDER_SELF_OSCILLATION: f64 = 4.0;
+
+/// Corner of the oversampled ladder's anti-imaging and anti-aliasing filters, as a fraction of the
+/// sample rate.
+const LADDER_OVERSAMPLING_CUTOFF: f64 = 0.45;
+
+/// Moog-style four-pole transistor ladder lowpass, with a tanh-style saturation in every stage.
+///
+/// Solved without a unit delay in the feedback path using Teemu Voipio's linearisation: each
+/// stage's saturation is replaced by its gain at the current state, which leaves a linear system
+/// with a closed-form solution every sample. The cutoff is prewarped, so at low levels the filter
+/// matches the analogue response at the cutoff and self-oscillates at the cutoff frequency.
+pub struct LadderFilter<S: Sample = f32> {
+    sample_rate: S,
+    cutoff: S,
+    resonance: S,
+    drive: S,
+    oversample: bool,
+    /// The prewarped integrator gain.
+    g: S,
+    feedback: S,
+    state: [S; 4],
+    last_input: S,
+    upsampler: BiquadCascade<4, S>,
+    downsampler: BiquadCascade<4, S>,
+}
+
+impl<S: Sample> LadderFilter<S> {
+    pub fn new(cutoff: S, resonance: S, sample_rate: S) -> LadderFilter<S> {
+        let mut filter = LadderFilter {
+            sample_rate,
+            cutoff,
+            resonance: S::ZERO,
+            drive: S::ONE,
+            oversample: false,
+            g: S::ZERO,
+            feedback: S::ZERO,
+            state: [S::ZERO; 4],
+            last_input: S::ZERO,
+            upsampler: BiquadCascade::new([BiquadCoefficients::IDENTITY; 4]),
+            downsampler: BiquadCascade::new([BiquadCoefficients::IDENTITY; 4]),
+        };
+        filter.set_resonance(resonance);
+        filter.set_sample_rate(sample_rate);
+
+        filter
+    }
+
+    fn update_coefficients(&mut self) {
+        let rate = if self.oversample { self.sample_rate * S::TWO } else { self.sample_rate };
+        let cutoff = self.cutoff.clamp(S::ZERO, rate * S::from_f64(0.49));
+        self.g = math::tan(S::PI * cutoff / rate);
+    }
+
+    pub fn set_cutoff(&mut self, cutoff: S) {
+        self.cutoff = cutoff;
+        self.update_coefficients();
+    }
+
+    /// Sets the resonance from 0.0 up to 1.0, where the filter starts to self-oscillate, and on to
+    /// 1.2 for a stronger oscillation. The saturation limits how loud it gets, and pulls the pitch
+    /// down a few percent at the top of the range, as in the analogue circuit.
+    pub fn set_resonance(&mut self, resonance: S) {
+        self.resonance = resonance.clamp(S::ZERO, S::from_f64(1.2));
+        self.feedback = S::from_f64(LADDER_SELF_OSCILLATION) * self.resonance;
+    }
+
+    /// Sets the gain into the ladder. Higher drive saturates the stages harder.
+    pub fn set_drive(&mut self, drive: S) {
+        self.drive = drive.max(S::ZERO);
+    }
+
+    /// Runs the ladder at twice the sample rate, which reduces the aliasing the saturation adds and
+    /// keeps high cutoffs further from Nyquist. The input is zero-stuffed up and the output
+    /// decimated through eighth-order Butterworth lowpasses just below the original Nyquist.
+    pub fn set_oversampling(&mut self, oversample: bool) {
+        self.oversample = oversample;
+        self.update_coefficients();
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: S) {
+        self.sample_rate = sample_rate;
+        self.update_coefficients();
+
+        let cutoff = S::from_f64(LADDER_OVERSAMPLING_CUTOFF) * sample_rate;
+        self.upsampler.set_butterworth(BiquadKind::Lowpass, cutoff, S::TWO * sample_rate);
+        self.downsampler.set_butterworth(BiquadKind::Lowpass, cutoff, S::TWO * sample_rate);
+    }
+
+    pub fn reset(&mut self) {
+        self.state = [S::ZERO; 4];
+        self.last_input = S::ZERO;
+        self.upsampler.reset();
+        self.downsampler.reset();
+    }
+
+    /// One step of the ladder at its internal rate.
+    fn step(&mut self, input: S) -> S {
+        let (g, k, s) = (self.g, self.feedback, &mut self.state);
+        let one = S::ONE;
+
+        // The input delayed by half a sample lines up with the states for estimating the gains.
+        let half_delayed = S::HALF * (input + self.last_input);
+        self.last_input = input;
+
+        // Each saturating stage's gain at the current state.
+        let t0 = tanh_over_x(half_delayed - k * s[3]);
+        let t1 = tanh_over_x(s[0]);
+        let t2 = tanh_over_x(s[1]);
+        let t3 = tanh_over_x(s[2]);
+        let t4 = tanh_over_x(s[3]);
+
+        let g0 = one / (one + g * t1);
+        let g1 = one / (one + g * t2);
+        let g2 = one / (one + g * t3);
+        let g3 = one / (one + g * t4);
+
+        // Gains from each stage's input through to the output, for solving the feedback.
+        let f3 = g * t3 * g3;
+        let f2 = g * t2 * g2 * f3;
+        let f1 = g * t1 * g1 * f2;
+        let f0 = g * t0 * g0 * f1;
+
+        let y3 = (g3 * s[3] + f3 * g2 * s[2] + f2 * g1 * s[1] + f1 * g0 * s[0] + f0 * input) / (one + k * f0);
+
+        let x = t0 * (input - k * y3);
+        let y0 = t1 * g0 * (s[0] + g * x);
+        let y1 = t2 * g1 * (s[1] + g * y0);
+        let y2 = t3 * g2 * (s[2] + g * y1);
+
+        let two_g = S::TWO * g;
+        s[0] += two_g * (x - t1 * y0);
+        s[1] += two_g * (t1 * y0 - t2 * y1);
+        s[2] += two_g * (t2 * y1 - t3 * y2);
+        s[3] += two_g * (t3 * y2 - t4 * y3);
+
+        y3
+    }
+}
+
+impl<S: Sample> MonoProcessor<S> for LadderFilter<S> {
+    fn tick(&mut self, input: S) -> S {
+        let input = input * self.drive;
+
+        if self.oversample {
+            // Zero-stuffed up, with the gain made up, and decimated by keeping the second output.
+            let mut y = S::ZERO;
+            for stuffed in [S::TWO * input, S::ZERO].iter() {
+                let upsampled = self.upsampler.tick(*stuffed);
+                let stepped = self.step(upsampled);
+                y = self.downsampler.tick(stepped);
+            }
+
+            y
+        } else {
+            self.step(input)
+        }
+    }
+}
+
+/// tanh(x) / x, using a rational approximation of tanh that's exact at 0 and reaches ±1 at ±3.
+#[inline(always)]
+fn tanh_over_x<S: Sample>(x: S) -> S {
+    let x2 = x * x;
+    let nine = S::from_f64(9.0);
+    if x2 < nine {
+        let twenty_seven = S::from_f64(27.0);
+        (twenty_seven + x2) / (twenty_seven + nine * x2)
+    } else {
+        S::ONE / x.abs()
+    }
+}
@@ -0,0 +1,318 @@
+//! Fixed-point samples and an integer-only oscillator, for voices on targets without an FPU or
+//! that work natively in the codec's integer format.
+//!
+//! Values are signed fractions in [-1.0, 1.0). Arithmetic saturates rather than wrapping, so an
+//! overload clips the way an analogue stage would instead of flipping sign.
+
+use core::ops::{Add, AddAssign, Mul, MulAssign, Neg, Sub, SubAssign};
+
+use super::oscillators::OscillatorMode;
+
+macro_rules! fixed_point {
+    ($name:ident, $raw:ty, $wide:ty, $frac_bits:expr, $doc:expr) => {
+        #[doc = $doc]
+        #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+        pub struct $name(pub $raw);
+
+        impl $name {
+            pub const ZERO: $name = $name(0);
+            /// The largest value, one step below 1.0.
+            pub const MAX: $name = $name(<$raw>::MAX);
+            /// -1.0.
+            pub const MIN: $name = $name(<$raw>::MIN);
+
+            const SCALE: f64 = (1u64 << $frac_bits) as f64;
+
+            /// Converts from a float, rounding to the nearest step and saturating out of range values.
+            pub fn from_f32(x: f32) -> $name {
+                // Float to integer casts saturate.
+                $name(libm::round(x as f64 * Self::SCALE) as $raw)
+            }
+
+            pub fn to_f32(self) -> f32 {
+                (self.0 as f64 / Self::SCALE) as f32
+            }
+
+            fn saturate(x: $wide) -> $name {
+                $name(x.clamp(<$raw>::MIN as $wide, <$raw>::MAX as $wide) as $raw)
+            }
+        }
+
+        impl Add for $name {
+            type Output = $name;
+
+            fn add(self, other: $name) -> $name {
+                $name(self.0.saturating_add(other.0))
+            }
+        }
+
+        impl Sub for $name {
+            type Output = $name;
+
+            fn sub(self, other: $name) -> $name {
+                $name(self.0.saturating_sub(other.0))
+            }
+        }
+
+        impl Neg for $name {
+            type Output = $name;
+
+            fn neg(self) -> $name {
+                $name(self.0.saturating_neg())
+            }
+        }
+
+        impl Mul for $name {
+            type Output = $name;
+
+            /// Rounded product. Only -1.0 * -1.0 is out of range, and saturates to `MAX`.
+            fn mul(self, other: $name) -> $name {
+                let product = self.0 as $wide * other.0 as $wide;
+                $name::saturate((product + (1 << ($frac_bits - 1))) >> $frac_bits)
+            }
+        }
+
+        impl AddAssign for $name {
+            fn add_assign(&mut self, other: $name) {
+                *self = *self + other;
+            }
+        }
+
+        impl SubAssign for $name {
+            fn sub_assign(&mut self, other: $name) {
+                *self = *self - other;
+            }
+        }
+
+        impl MulAssign for $name {
+            fn mul_assign(&mut self, other: $name) {
+                *self = *self * other;
+            }
+        }
+    };
+}
+
+fixed_point!(Q15, i16, i32, 15, "A 16-bit fraction with 15 fractional bits.");
+fixed_point!(Q31, i32, i64, 31, "A 32-bit fraction with 31 fractional bits.");
+
+impl From<Q15> for Q31 {
+    fn from(x: Q15) -> Q31 {
+        Q31((x.0 as i32) << 16)
+    }
+}
+
+impl Q31 {
+    /// Rounds to the nearest `Q15`, saturating at the top of the range.
+    pub fn to_q15(self) -> Q15 {
+        Q15::saturate(((self.0 as i64 + (1 << 15)) >> 16) as i32)
+    }
+}
+
+/// 1.0 in Q31, held in an i64 for intermediate results that may exceed the Q31 range.
+const ONE: i64 = 1 << 31;
+
+/// π in Q29, for turning a phase increment into the triangle's leak coefficient.
+const PI_Q29: u64 = 1_686_629_713;
+
+const SINE_TABLE_BITS: u32 = 10;
+const SINE_TABLE_SIZE: usize = 1 << SINE_TABLE_BITS;
+/// Phase bits below the table index, used for interpolation.
+const SINE_FRACTION_BITS: u32 = 32 - SINE_TABLE_BITS;
+
+/// One cycle of a sine in Q31, with a guard point so interpolation never has to wrap.
+static SINE_TABLE: [i32; SINE_TABLE_SIZE + 1] = sine_table();
+
+const fn sine_table() -> [i32; SINE_TABLE_SIZE + 1] {
+    let mut table = [0; SINE_TABLE_SIZE + 1];
+
+    let mut i = 0;
+    while i <= SINE_TABLE_SIZE {
+        let mut x = 2.0 * core::f64::consts::PI * i as f64 / SINE_TABLE_SIZE as f64;
+        if x > core::f64::consts::PI {
+            x -= 2.0 * core::f64::consts::PI;
+        }
+
+        // Taylor series, which has converged to f64 precision by the 20th term for |x| <= π.
+        let mut term = x;
+        let mut sum = x;
+        let mut n = 1;
+        while n < 20 {
+            term *= -x * x / ((2 * n) * (2 * n + 1)) as f64;
+            sum += term;
+            n += 1;
+        }
+
+        let scaled = sum * ONE as f64;
+        table[i] = if scaled >= 0.0 { scaled + 0.5 } else { scaled - 0.5 } as i32;
+        i += 1;
+    }
+
+    table
+}
+
+/// A band-limited oscillator using only integer arithmetic per sample.
+///
+/// The phase is a wrapping 32-bit accumulator, so it never drifts or needs a wrap loop. The sine
+/// is interpolated from a 1024-point table, and the saw, square and triangle use PolyBLEP as
+/// `Oscillator` does.
+///
+/// Given the same phase increment, the output stays within 5e-6 of the f64 reference
+/// `Oscillator64` for the sine, from the table interpolation, and within 1e-6 for the other modes,
+/// for frequencies up to an eighth of the sample rate. Both limit the triangle's leak coefficient
+/// to below 1.0, but not at the same point. `set_frequency` rounds the increment to the nearest
+/// 2^-32 of a cycle, as the float oscillators do.
+pub struct FixedOscillator {
+    mode: OscillatorMode,
+    sample_rate: f32,
+    phase: u32,
+    phase_increment: u32,
+    /// The size of the increment, ignoring which way the phase runs, for PolyBLEP.
+    blep_width: u32,
+    /// 2^63 / `blep_width`, so PolyBLEP can divide by the width with a multiply.
+    inv_blep_width: u64,
+    /// The triangle's leak coefficient in Q31, matching `Oscillator`'s 2π times the width.
+    triangle_coefficient: i64,
+    backwards: bool,
+    last_output: i64,
+}
+
+impl FixedOscillator {
+    pub fn new(mode: OscillatorMode, frequency: f32, sample_rate: f32) -> FixedOscillator {
+        let mut osc = FixedOscillator {
+            mode,
+            sample_rate,
+            phase: 0,
+            phase_increment: 0,
+            blep_width: 0,
+            inv_blep_width: 0,
+            triangle_coefficient: 0,
+            backwards: false,
+            last_output: 0,
+        };
+        osc.set_frequency(frequency);
+
+        osc
+    }
+
+    /// Sets the frequency in Hz. This is the only float calculation, and only happens here.
+    pub fn set_frequency(&mut self, frequency: f32) {
+        let cycles = frequency as f64 / self.sample_rate as f64;
+        // Negative frequencies wrap to a large increment, which runs the phase backwards.
+        self.set_phase_increment(libm::round(cycles * 4_294_967_296.0) as i64 as u32);
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: f32) {
+        let frequency = self.frequency();
+        self.sample_rate = sample_rate;
+        self.set_frequency(frequency);
+    }
+
+    pub fn frequency(&self) -> f32 {
+        // Read back signed, as `set_frequency` stores negative frequencies.
+        (self.phase_increment as i32 as f64 / 4_294_967_296.0 * self.sample_rate as f64) as f32
+    }
+
+    /// Sets the phase increment directly, as a fraction of a cycle per sample in units of 2^-32,
+    /// for float-free pitch control.
+    pub fn set_phase_increment(&mut self, increment: u32) {
+        self.phase_increment = increment;
+
+        // Increments over half a cycle are the phase running backwards.
+        let signed = increment as i32;
+        let width = signed.unsigned_abs();
+        self.blep_width = width;
+        self.inv_blep_width = if width == 0 { 0 } else { (1u64 << 63) / width as u64 };
+        self.triangle_coefficient = ((width as u64 * PI_Q29) >> 29).min(ONE as u64 - 1) as i64;
+        self.backwards = signed < 0;
+    }
+
+    pub fn phase_increment(&self) -> u32 {
+        self.phase_increment
+    }
+
+    pub fn set_mode(&mut self, mode: OscillatorMode) {
+        self.mode = mode;
+    }
+
+    fn sine(&self) -> i64 {
+        let index = (self.phase >> SINE_FRACTION_BITS) as usize;
+        let fraction = (self.phase & ((1 << SINE_FRACTION_BITS) - 1)) as i64;
+        let a = SINE_TABLE[index] as i64;
+        let b = SINE_TABLE[index + 1] as i64;
+
+        a + (((b - a) * fraction) >> SINE_FRACTION_BITS)
+    }
+
+    fn square(&self) -> i64 {
+        if self.phase < 1 << 31 { ONE } else { -ONE }
+    }
+
+    fn naive_waveform(&self) -> i64 {
+        match self.mode {
+            OscillatorMode::Sine => self.sine(),
+            OscillatorMode::Saw => self.phase as i64 - ONE,
+            OscillatorMode::Square => self.square(),
+            OscillatorMode::Triangle => 2 * ((self.phase as i64 - ONE).abs() - ONE / 2),
+        }
+    }
+
+    pub fn tick_naive(&mut self) -> Q31 {
+        let x = self.naive_waveform();
+        self.phase = self.phase.wrapping_add(self.phase_increment);
+
+        Q31::saturate(x)
+    }
+
+    pub fn tick_poly_blep(&mut self) -> Q31 {
+        let (width, inv_width) = (self.blep_width, self.inv_blep_width);
+
+        let x = match self.mode {
+            OscillatorMode::Sine => self.sine(),
+            OscillatorMode::Saw => self.phase as i64 - ONE - poly_blep(self.phase, width, inv_width),
+            OscillatorMode::Square | OscillatorMode::Triangle => {
+                let mut x = self.square() + poly_blep(self.phase, width, inv_width)
+                    - poly_blep(self.phase.wrapping_add(1 << 31), width, inv_width);
+
+                if self.mode == OscillatorMode::Triangle {
+                    // Keeping both terms within [-1, 1] and the coefficient below 1 keeps the
+                    // product within an i64.
+                    x = x.clamp(-ONE, ONE);
+                    if self.backwards {
+                        x = -x;
+                    }
+                    self.last_output += (self.triangle_coefficient * (x - self.last_output)) >> 31;
+                    x = self.last_output;
+                }
+
+                x
+            }
+        };
+        self.phase = self.phase.wrapping_add(self.phase_increment);
+
+        Q31::saturate(x)
+    }
+
+    /// Fills a block using `tick_poly_blep`.
+    pub fn fill(&mut self, buffer: &mut [Q31]) {
+        for x in buffer.iter_mut() {
+            *x = self.tick_poly_blep();
+        }
+    }
+}
+
+/// The PolyBLEP residual in Q31 for a phase in units of 2^-32 of a cycle.
+#[inline(always)]
+fn poly_blep(phase: u32, width: u32, inv_width: u64) -> i64 {
+    if phase < width {
+        // Just after the discontinuity: -(1 - t/dt)^2.
+        let x = ((phase as u64 * inv_width) >> 32) as i64;
+        let y = ONE - x;
+        -((y * y) >> 31)
+    } else if phase > width.wrapping_neg() {
+        // Just before it: ((t - (1 - dt)) / dt)^2.
+        let x = ((phase.wrapping_add(width) as u64 * inv_width) >> 32) as i64;
+        (x * x) >> 31
+    } else {
+        0
+    }
+}
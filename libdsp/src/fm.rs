@@ -0,0 +1,279 @@
+//! DX-style FM synthesis: sine operators that phase-modulate each other, routed by an algorithm.
+//!
+//! Modulation is in cycles of phase, so a modulator at level 1.0 swings its target's phase by a
+//! whole cycle either way, a modulation index of 2π.
+
+use super::oscillators::{GenericOscillator, OscillatorMode};
+use super::sample::Sample;
+use super::traits::MonoGenerator;
+
+/// How an operator's frequency is set.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum OperatorFrequency<S: Sample> {
+    /// A multiple of the note's frequency.
+    Ratio(S),
+    /// A frequency in Hz, whatever note is played.
+    Fixed(S),
+}
+
+/// A sine oscillator with a phase modulation input, self-feedback and an output level.
+pub struct FmOperator<S: Sample> {
+    oscillator: GenericOscillator<S>,
+    frequency: OperatorFrequency<S>,
+    note_frequency: S,
+    level: S,
+    feedback: S,
+    /// The last two outputs, averaged for feedback as the DX7 does to keep it from oscillating at
+    /// Nyquist.
+    history: [S; 2],
+}
+
+impl<S: Sample> FmOperator<S> {
+    pub fn new(frequency: OperatorFrequency<S>, sample_rate: S) -> FmOperator<S> {
+        let mut operator = FmOperator {
+            oscillator: GenericOscillator::new(OscillatorMode::Sine, S::ZERO, sample_rate),
+            frequency,
+            note_frequency: S::from_f64(440.0),
+            level: S::ONE,
+            feedback: S::ZERO,
+            history: [S::ZERO; 2],
+        };
+        operator.update_frequency();
+
+        operator
+    }
+
+    fn update_frequency(&mut self) {
+        let frequency = match self.frequency {
+            OperatorFrequency::Ratio(ratio) => ratio * self.note_frequency,
+            OperatorFrequency::Fixed(frequency) => frequency,
+        };
+        self.oscillator.set_frequency(frequency);
+    }
+
+    pub fn set_frequency(&mut self, frequency: OperatorFrequency<S>) {
+        self.frequency = frequency;
+        self.update_frequency();
+    }
+
+    pub fn frequency(&self) -> OperatorFrequency<S> {
+        self.frequency
+    }
+
+    /// Sets the frequency of the note being played, which ratio frequencies are multiples of.
+    pub fn set_note_frequency(&mut self, note_frequency: S) {
+        self.note_frequency = note_frequency;
+        self.update_frequency();
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: S) {
+        self.oscillator.set_sample_rate(sample_rate);
+    }
+
+    /// Sets the output level, which is also how deeply the operator modulates others. Cheap
+    /// enough to set every sample from an envelope.
+    pub fn set_level(&mut self, level: S) {
+        self.level = level;
+    }
+
+    pub fn level(&self) -> S {
+        self.level
+    }
+
+    /// Sets how much of its own output the operator feeds back into its phase, in the same units
+    /// as its level. Around 0.2 turns the sine into something close to a saw.
+    pub fn set_feedback(&mut self, feedback: S) {
+        self.feedback = feedback;
+    }
+
+    /// Restarts the cycle and clears the feedback, for consistent note onsets.
+    pub fn reset(&mut self) {
+        self.oscillator.reset_phase();
+        self.history = [S::ZERO; 2];
+    }
+
+    /// Produces a sample with its phase offset by `modulation` cycles.
+    pub fn tick(&mut self, modulation: S) -> S {
+        let feedback = self.feedback * S::HALF * (self.history[0] + self.history[1]);
+        let x = self.level * self.oscillator.tick_modulated(S::ZERO, modulation + feedback);
+        self.history = [x, self.history[0]];
+
+        x
+    }
+}
+
+/// Which operators modulate which, and which are heard, for an `FmVoice` of `N` operators.
+///
+/// Operators run from the highest index down, so each can only be modulated by operators with a
+/// higher index than its own. Connections the other way are ignored; use an operator's feedback
+/// to have it modulate itself.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Algorithm<const N: usize> {
+    /// For each operator, a bit for each operator modulating it.
+    modulators: [u8; N],
+    /// A bit for each operator heard in the output.
+    carriers: u8,
+}
+
+impl<const N: usize> Algorithm<N> {
+    /// Fails the build for more operators than the connections have a bit each for.
+    const FITS_IN_U8: () = assert!(N <= 8, "an Algorithm has at most eight operators");
+
+    /// No connections and nothing heard.
+    pub const fn empty() -> Algorithm<N> {
+        let () = Self::FITS_IN_U8;
+
+        Algorithm {
+            modulators: [0; N],
+            carriers: 0,
+        }
+    }
+
+    /// Each operator modulating the one below it, with operator 0 the only one heard.
+    pub const fn stack() -> Algorithm<N> {
+        let mut algorithm = Algorithm::empty().with_carrier(0);
+        let mut i = 1;
+        while i < N {
+            algorithm = algorithm.with_modulation(i, i - 1);
+            i += 1;
+        }
+
+        algorithm
+    }
+
+    /// Every operator heard and none modulated, for additive sounds.
+    pub const fn parallel() -> Algorithm<N> {
+        let mut algorithm = Algorithm::empty();
+        let mut i = 0;
+        while i < N {
+            algorithm = algorithm.with_carrier(i);
+            i += 1;
+        }
+
+        algorithm
+    }
+
+    /// Pairs of a carrier and the modulator above it: 1 modulates 0, 3 modulates 2, and so on.
+    pub const fn pairs() -> Algorithm<N> {
+        let mut algorithm = Algorithm::empty();
+        let mut i = 0;
+        while i < N {
+            algorithm = algorithm.with_carrier(i);
+            if i + 1 < N {
+                algorithm = algorithm.with_modulation(i + 1, i);
+            }
+            i += 2;
+        }
+
+        algorithm
+    }
+
+    /// Adds `modulator`'s output to `target`'s phase modulation, if `modulator` has the higher index.
+    pub const fn with_modulation(mut self, modulator: usize, target: usize) -> Algorithm<N> {
+        if modulator < N && modulator > target {
+            self.modulators[target] |= 1 << modulator;
+        }
+
+        self
+    }
+
+    /// Adds `operator` to the output.
+    pub const fn with_carrier(mut self, operator: usize) -> Algorithm<N> {
+        if operator < N {
+            self.carriers |= 1 << operator;
+        }
+
+        self
+    }
+
+    pub fn modulates(&self, modulator: usize, target: usize) -> bool {
+        modulator < N && target < N && self.modulators[target] & (1 << modulator) != 0
+    }
+
+    pub fn is_carrier(&self, operator: usize) -> bool {
+        operator < N && self.carriers & (1 << operator) != 0
+    }
+}
+
+/// `N` operators combined by an algorithm. The carriers are mixed at equal levels scaled to keep
+/// the output within [-1, 1], and the operators' levels are left to the caller, typically driven
+/// by an envelope each.
+pub struct FmVoice<S: Sample, const N: usize> {
+    operators: [FmOperator<S>; N],
+    algorithm: Algorithm<N>,
+    output_gain: S,
+}
+
+impl<S: Sample, const N: usize> FmVoice<S, N> {
+    pub fn new(operators: [FmOperator<S>; N], algorithm: Algorithm<N>) -> FmVoice<S, N> {
+        let mut voice = FmVoice {
+            operators,
+            algorithm,
+            output_gain: S::ZERO,
+        };
+        voice.set_algorithm(algorithm);
+
+        voice
+    }
+
+    pub fn set_algorithm(&mut self, algorithm: Algorithm<N>) {
+        self.algorithm = algorithm;
+        let carriers = algorithm.carriers.count_ones();
+        self.output_gain = if carriers == 0 { S::ZERO } else { S::ONE / S::from_f64(carriers as f64) };
+    }
+
+    pub fn algorithm(&self) -> Algorithm<N> {
+        self.algorithm
+    }
+
+    pub fn operators(&self) -> &[FmOperator<S>; N] {
+        &self.operators
+    }
+
+    pub fn operators_mut(&mut self) -> &mut [FmOperator<S>; N] {
+        &mut self.operators
+    }
+
+    /// Sets the note frequency of every operator.
+    pub fn set_note_frequency(&mut self, note_frequency: S) {
+        for operator in self.operators.iter_mut() {
+            operator.set_note_frequency(note_frequency);
+        }
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: S) {
+        for operator in self.operators.iter_mut() {
+            operator.set_sample_rate(sample_rate);
+        }
+    }
+
+    /// Resets every operator, for retriggering on a note on.
+    pub fn reset(&mut self) {
+        for operator in self.operators.iter_mut() {
+            operator.reset();
+        }
+    }
+}
+
+impl<S: Sample, const N: usize> MonoGenerator<S> for FmVoice<S, N> {
+    fn tick(&mut self) -> S {
+        let mut outputs = [S::ZERO; N];
+        let mut mix = S::ZERO;
+
+        for i in (0..N).rev() {
+            let mut modulation = S::ZERO;
+            for (j, &output) in outputs.iter().enumerate().skip(i + 1) {
+                if self.algorithm.modulates(j, i) {
+                    modulation += output;
+                }
+            }
+
+            outputs[i] = self.operators[i].tick(modulation);
+            if self.algorithm.is_carrier(i) {
+                mix += outputs[i];
+            }
+        }
+
+        mix * self.output_gain
+    }
+}
@@ -0,0 +1,343 @@
+//! Low-frequency oscillators for modulation, free-running or synced to a tempo.
+
+use super::noise::XorShift;
+use super::sample::Sample;
+use super::traits::MonoGenerator;
+
+/// 2^32, the number of phase steps in a cycle.
+const PHASE_SCALE: f64 = 4_294_967_296.0;
+
+/// Clock pulses per quarter note, as MIDI clock sends them.
+pub const PULSES_PER_QUARTER_NOTE: u32 = 24;
+
+/// How much each new clock pulse interval moves the measured tempo, to ride out jitter from
+/// pulses only being seen once per audio block.
+const PULSE_SMOOTHING: f64 = 0.125;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum LfoShape {
+    Sine,
+    Triangle,
+    /// A rising ramp.
+    Saw,
+    Square,
+    /// A new random value each cycle, held for the cycle.
+    SampleAndHold,
+    /// Glides smoothly from one random value to the next over each cycle.
+    SmoothRandom,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum LfoPolarity {
+    /// From -1.0 to 1.0.
+    Bipolar,
+    /// From 0.0 to 1.0.
+    Unipolar,
+}
+
+/// Note lengths a synced LFO's cycle can take, in 4/4.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum NoteDivision {
+    FourBars,
+    TwoBars,
+    Whole,
+    Half,
+    Quarter,
+    Eighth,
+    Sixteenth,
+    ThirtySecond,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum NoteModifier {
+    Straight,
+    /// One and a half times as long.
+    Dotted,
+    /// Two thirds as long.
+    Triplet,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum LfoRate<S: Sample> {
+    /// A frequency in Hz.
+    Free(S),
+    /// One cycle per note of the given length, at the tempo.
+    Synced(NoteDivision, NoteModifier),
+}
+
+impl NoteDivision {
+    /// The note's length in half clock pulses, which every division and modifier is a whole
+    /// number of.
+    fn half_pulses(self, modifier: NoteModifier) -> u32 {
+        let quarter = 2 * PULSES_PER_QUARTER_NOTE;
+        let straight = match self {
+            NoteDivision::FourBars => quarter * 16,
+            NoteDivision::TwoBars => quarter * 8,
+            NoteDivision::Whole => quarter * 4,
+            NoteDivision::Half => quarter * 2,
+            NoteDivision::Quarter => quarter,
+            NoteDivision::Eighth => quarter / 2,
+            NoteDivision::Sixteenth => quarter / 4,
+            NoteDivision::ThirtySecond => quarter / 8,
+        };
+
+        match modifier {
+            NoteModifier::Straight => straight,
+            NoteModifier::Dotted => straight * 3 / 2,
+            NoteModifier::Triplet => straight * 2 / 3,
+        }
+    }
+}
+
+/// A modulation source with a choice of shapes, free-running or synced to a tempo.
+///
+/// Synced rates follow `set_tempo`, or the tempo measured from `clock_pulse` when driven by an
+/// external clock, in which case each cycle also restarts on the pulse it's due on so the LFO
+/// stays locked to the beat. `note_on` starts the fade-in, and with key retrigger on restarts the
+/// cycle as well.
+pub struct Lfo<S: Sample> {
+    shape: LfoShape,
+    polarity: LfoPolarity,
+    rate: LfoRate<S>,
+    tempo: S,
+    sample_rate: S,
+    phase: u32,
+    phase_increment: u32,
+    phase_offset: u32,
+    retrigger: bool,
+    one_shot: bool,
+    /// Whether a one-shot cycle has finished.
+    finished: bool,
+    fade_in: S,
+    fade_step: S,
+    fade_level: S,
+    rng: XorShift,
+    random: S,
+    previous_random: S,
+    samples_since_pulse: u32,
+    /// Smoothed samples between clock pulses, once two have been seen.
+    pulse_interval: Option<f64>,
+    pulse_count: u32,
+}
+
+impl<S: Sample> Lfo<S> {
+    pub fn new(shape: LfoShape, rate: LfoRate<S>, sample_rate: S) -> Lfo<S> {
+        let mut rng = XorShift::new(1);
+        let random = rng.next_bipolar();
+
+        let mut lfo = Lfo {
+            shape,
+            polarity: LfoPolarity::Bipolar,
+            rate,
+            tempo: S::from_f64(120.0),
+            sample_rate,
+            phase: 0,
+            phase_increment: 0,
+            phase_offset: 0,
+            retrigger: true,
+            one_shot: false,
+            finished: false,
+            fade_in: S::ZERO,
+            fade_step: S::ONE,
+            fade_level: S::ONE,
+            rng,
+            random,
+            previous_random: random,
+            samples_since_pulse: 0,
+            pulse_interval: None,
+            pulse_count: 0,
+        };
+        lfo.update_phase_increment();
+
+        lfo
+    }
+
+    fn update_phase_increment(&mut self) {
+        let cycles = match self.rate {
+            LfoRate::Free(frequency) => frequency.to_f64() / self.sample_rate.to_f64(),
+            LfoRate::Synced(division, modifier) => {
+                let quarter_notes = division.half_pulses(modifier) as f64 / (2 * PULSES_PER_QUARTER_NOTE) as f64;
+                self.tempo.to_f64() / 60.0 / quarter_notes / self.sample_rate.to_f64()
+            }
+        };
+        // Anything faster than Nyquist isn't a modulation source any more.
+        self.phase_increment = libm::round(cycles.clamp(0.0, 0.5) * PHASE_SCALE) as u32;
+    }
+
+    pub fn set_shape(&mut self, shape: LfoShape) {
+        self.shape = shape;
+    }
+
+    pub fn set_polarity(&mut self, polarity: LfoPolarity) {
+        self.polarity = polarity;
+    }
+
+    pub fn set_rate(&mut self, rate: LfoRate<S>) {
+        self.rate = rate;
+        self.update_phase_increment();
+    }
+
+    /// Sets the tempo in beats per minute that synced rates follow. `clock_pulse` sets it too.
+    pub fn set_tempo(&mut self, bpm: S) {
+        self.tempo = bpm;
+        self.update_phase_increment();
+    }
+
+    pub fn tempo(&self) -> S {
+        self.tempo
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: S) {
+        self.sample_rate = sample_rate;
+        self.update_phase_increment();
+        self.set_fade_in(self.fade_in);
+    }
+
+    /// Sets where in the cycle the LFO starts, as a fraction of a cycle.
+    pub fn set_phase_offset(&mut self, offset: S) {
+        let offset = libm::round(offset.fract().to_f64() * PHASE_SCALE) as i64 as u32;
+        self.phase = self.phase.wrapping_add(offset.wrapping_sub(self.phase_offset));
+        self.phase_offset = offset;
+    }
+
+    /// Sets whether `note_on` restarts the cycle.
+    pub fn set_retrigger(&mut self, retrigger: bool) {
+        self.retrigger = retrigger;
+    }
+
+    /// Sets whether the LFO runs a single cycle after each `note_on` and then holds its last value.
+    pub fn set_one_shot(&mut self, one_shot: bool) {
+        self.one_shot = one_shot;
+        if !one_shot {
+            self.finished = false;
+        }
+    }
+
+    /// Sets how long the output takes to fade in after `note_on`, in seconds.
+    pub fn set_fade_in(&mut self, seconds: S) {
+        self.fade_in = seconds.max(S::ZERO);
+        let samples = self.fade_in * self.sample_rate;
+        self.fade_step = if samples > S::ONE { S::ONE / samples } else { S::ONE };
+    }
+
+    /// Starts the fade-in, and restarts the cycle if key retrigger is on or the LFO is one-shot.
+    pub fn note_on(&mut self) {
+        if self.retrigger || self.one_shot {
+            self.restart();
+        }
+        self.finished = false;
+        self.fade_level = if self.fade_step < S::ONE { S::ZERO } else { S::ONE };
+    }
+
+    /// Restarts the cycle from the phase offset.
+    pub fn restart(&mut self) {
+        // If the cycle is only just under way the random shapes already have their new value.
+        if self.phase.wrapping_sub(self.phase_offset) >= 1 << 31 {
+            self.next_random();
+        }
+        self.phase = self.phase_offset;
+    }
+
+    /// Advances the external clock by a pulse, at `PULSES_PER_QUARTER_NOTE`. Call it when a MIDI
+    /// clock message arrives, before ticking the samples that follow it.
+    pub fn clock_pulse(&mut self) {
+        if self.pulse_count > 0 {
+            let measured = self.samples_since_pulse as f64;
+            let interval = match self.pulse_interval {
+                Some(interval) => interval + PULSE_SMOOTHING * (measured - interval),
+                None => measured,
+            };
+            self.pulse_interval = Some(interval);
+
+            if interval > 0.0 {
+                let quarter_note = interval * PULSES_PER_QUARTER_NOTE as f64 / self.sample_rate.to_f64();
+                self.set_tempo(S::from_f64(60.0 / quarter_note));
+            }
+        }
+
+        if let LfoRate::Synced(division, modifier) = self.rate {
+            let half_pulses = self.pulse_count as u64 * 2;
+            if half_pulses.is_multiple_of(division.half_pulses(modifier) as u64) && !self.finished {
+                self.restart();
+            }
+        }
+
+        self.samples_since_pulse = 0;
+        self.pulse_count = self.pulse_count.wrapping_add(1);
+    }
+
+    /// Forgets the clock's position and tempo measurement, as on a MIDI start. The next pulse is
+    /// taken as the downbeat.
+    pub fn reset_clock(&mut self) {
+        self.pulse_count = 0;
+        self.pulse_interval = None;
+    }
+
+    fn next_random(&mut self) {
+        self.previous_random = self.random;
+        self.random = self.rng.next_bipolar();
+    }
+
+    /// The shape at phase `t`, with `elapsed` of the cycle since the phase offset gone, which is
+    /// where the random shapes change value.
+    fn shape_at(&self, t: S, elapsed: S) -> S {
+        match self.shape {
+            LfoShape::Sine => (S::TWO_PI * t).sin(),
+            LfoShape::Triangle => {
+                let quarter = S::from_f64(0.25);
+                let four = S::TWO * S::TWO;
+                if t < quarter {
+                    four * t
+                } else if t < S::ONE - quarter {
+                    S::TWO - four * t
+                } else {
+                    four * t - four
+                }
+            }
+            LfoShape::Saw => S::TWO * t - S::ONE,
+            LfoShape::Square => {
+                if t < S::HALF {
+                    S::ONE
+                } else {
+                    -S::ONE
+                }
+            }
+            LfoShape::SampleAndHold => self.random,
+            LfoShape::SmoothRandom => {
+                // Smoothstep, so each glide starts and ends level.
+                let s = elapsed * elapsed * (S::TWO + S::ONE - S::TWO * elapsed);
+                self.previous_random + (self.random - self.previous_random) * s
+            }
+        }
+    }
+}
+
+impl<S: Sample> MonoGenerator<S> for Lfo<S> {
+    fn tick(&mut self) -> S {
+        let elapsed = S::from_phase(self.phase.wrapping_sub(self.phase_offset));
+        let mut x = self.shape_at(S::from_phase(self.phase), elapsed);
+        if self.polarity == LfoPolarity::Unipolar {
+            x = S::HALF * (x + S::ONE);
+        }
+        x *= self.fade_level;
+        self.fade_level = (self.fade_level + self.fade_step).min(S::ONE);
+
+        self.samples_since_pulse = self.samples_since_pulse.saturating_add(1);
+        if !self.finished {
+            let previous = self.phase.wrapping_sub(self.phase_offset);
+            self.phase = self.phase.wrapping_add(self.phase_increment);
+
+            if self.phase.wrapping_sub(self.phase_offset) < previous {
+                if self.one_shot {
+                    // Hold the very end of the cycle.
+                    self.finished = true;
+                    self.phase = self.phase_offset.wrapping_sub(1);
+                } else {
+                    self.next_random();
+                }
+            }
+        }
+
+        x
+    }
+}
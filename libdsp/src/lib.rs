@@ -1,9 +1,33 @@
 #![no_std]
-#[cfg(feature = "sample_f32")] type SampleType = f32;
-#[cfg(not(feature = "sample_f32"))] type SampleType = f64;
+// Test and analysis builds link std, whose inherent float methods shadow the micromath extension
+// traits.
+#![cfg_attr(any(test, feature = "analysis"), allow(unused_imports))]
 
-const PI: SampleType = 3.141592653589793;
-const TWO_PI: SampleType = PI * 2.0;
+#[cfg(feature = "analysis")]
+#[macro_use]
+extern crate std;
 
+// The sample type of modules that aren't generic over `sample::Sample`.
+type SampleType = f32;
+
+mod math;
+
+pub mod sample;
 pub mod traits;
-pub mod oscillators;
\ No newline at end of file
+pub mod chain;
+pub mod oscillators;
+pub mod fm;
+pub mod noise;
+pub mod lfo;
+pub mod fixed;
+pub mod wavetable;
+pub mod filters;
+pub mod biquad;
+pub mod distortion;
+pub mod delay;
+pub mod reverb;
+pub mod modulation;
+pub mod envelopes;
+pub mod voices;
+#[cfg(feature = "analysis")]
+pub mod analysis;
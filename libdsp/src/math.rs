@@ -0,0 +1,32 @@
+//! Trigonometry for coefficient calculations.
+//!
+//! micromath's `sin`/`tan` are off by around 1% for small angles, which is fine for generating
+//! waveforms but audibly detunes filters, so coefficient code uses these instead.
+
+use super::sample::Sample;
+
+/// Sine and cosine of `x`, using a quadrant reduction and Taylor series accurate to f32 precision,
+/// and to around 1e-9 in f64.
+pub(crate) fn sin_cos<S: Sample>(x: S) -> (S, S) {
+    let half_pi = S::PI * S::HALF;
+    let quadrant = (x / half_pi + S::HALF).floor();
+    let r = x - quadrant * half_pi;
+    let r2 = r * r;
+    let one = S::ONE;
+    let term = |n: f64| r2 / S::from_f64(n);
+
+    let s = r * (one - term(6.0) * (one - term(20.0) * (one - term(42.0) * (one - term(72.0)))));
+    let c = one - term(2.0) * (one - term(12.0) * (one - term(30.0) * (one - term(56.0) * (one - term(90.0)))));
+
+    match (quadrant.to_f64() as i32) & 3 {
+        0 => (s, c),
+        1 => (c, -s),
+        2 => (-s, -c),
+        _ => (-c, s),
+    }
+}
+
+pub(crate) fn tan<S: Sample>(x: S) -> S {
+    let (s, c) = sin_cos(x);
+    s / c
+}
@@ -0,0 +1,499 @@
+//! Modulated effects: chorus, flanger and phaser.
+//!
+//! The chorus and flanger take their delay memory from the caller, like the other delay-based
+//! effects, so it can live in external memory.
+
+use core::mem;
+
+use super::delay::{DelayLine, Interpolation};
+use super::lfo::{Lfo, LfoPolarity, LfoRate, LfoShape};
+use super::math;
+use super::sample::Sample;
+use super::traits::{MonoGenerator, StereoProcessor};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ModulationError {
+    /// The memory is smaller than the effect's `memory_len` for the sample rate.
+    MemoryTooSmall,
+}
+
+/// Splits `len` samples off the front of `memory` for a delay line with cubic reads.
+fn take_line<'a, S: Sample>(memory: &mut &'a mut [S], len: usize) -> DelayLine<'a, S> {
+    let (line, rest) = mem::take(memory).split_at_mut(len);
+    *memory = rest;
+
+    let mut line = DelayLine::new(line);
+    line.set_interpolation(Interpolation::Cubic);
+    line
+}
+
+/// A sine LFO at `rate` Hz, starting `offset` cycles in.
+fn sine_lfo<S: Sample>(rate: f64, offset: f64, polarity: LfoPolarity, sample_rate: S) -> Lfo<S> {
+    let mut lfo = Lfo::new(LfoShape::Sine, LfoRate::Free(S::from_f64(rate)), sample_rate);
+    lfo.set_polarity(polarity);
+    lfo.set_phase_offset(S::from_f64(offset));
+    lfo
+}
+
+/// Most voices a `Chorus` can run.
+pub const MAX_CHORUS_VOICES: usize = 4;
+
+/// Longest delay a `Chorus` voice can reach, base delay and depth together, in seconds.
+pub const MAX_CHORUS_DELAY: f64 = 0.05;
+
+/// A multi-voice chorus, each voice a copy of the input delayed by its own swing of the LFO.
+///
+/// The input is summed to mono for the voices, which are spread evenly round the LFO's cycle and
+/// panned across the stereo field by the spread, while the dry signal keeps its stereo image.
+pub struct Chorus<'a, S: Sample = f32> {
+    sample_rate: S,
+    /// Samples of memory the chorus was given.
+    capacity: usize,
+    line: DelayLine<'a, S>,
+    lfos: [Lfo<S>; MAX_CHORUS_VOICES],
+    voices: usize,
+    /// Base delay and swing, in samples.
+    delay: S,
+    depth: S,
+    spread: S,
+    /// Each voice's gain into the left and right outputs.
+    pans: [(S, S); MAX_CHORUS_VOICES],
+    mix: S,
+}
+
+impl<'a, S: Sample> Chorus<'a, S> {
+    /// Samples of memory a chorus needs at `sample_rate`.
+    pub fn memory_len(sample_rate: S) -> usize {
+        (MAX_CHORUS_DELAY * sample_rate.to_f64()) as usize + 4
+    }
+
+    /// Lays the chorus's delay out in `memory`, which must hold at least `memory_len` samples. All
+    /// of it is used, so enough for a higher rate lets `set_sample_rate` move up to it later.
+    pub fn new(memory: &'a mut [S], sample_rate: S) -> Result<Chorus<'a, S>, ModulationError> {
+        if memory.len() < Self::memory_len(sample_rate) {
+            return Err(ModulationError::MemoryTooSmall);
+        }
+
+        let capacity = memory.len();
+        let mut memory = memory;
+        let line = take_line(&mut memory, capacity);
+        let mut chorus = Chorus {
+            sample_rate,
+            capacity,
+            line,
+            lfos: [(); MAX_CHORUS_VOICES].map(|_| sine_lfo(0.8, 0.0, LfoPolarity::Unipolar, sample_rate)),
+            voices: 0,
+            delay: S::ZERO,
+            depth: S::ZERO,
+            spread: S::ONE,
+            pans: [(S::ZERO, S::ZERO); MAX_CHORUS_VOICES],
+            mix: S::HALF,
+        };
+        chorus.set_delay(S::from_f64(0.015));
+        chorus.set_depth(S::from_f64(0.004));
+        chorus.set_voices(3);
+
+        Ok(chorus)
+    }
+
+    /// Sets how many voices run, from 1 to `MAX_CHORUS_VOICES`, and restarts their LFOs evenly
+    /// spread round the cycle.
+    pub fn set_voices(&mut self, voices: usize) {
+        self.voices = voices.clamp(1, MAX_CHORUS_VOICES);
+        for (i, lfo) in self.lfos.iter_mut().enumerate() {
+            lfo.set_phase_offset(S::from_f64(i as f64 / self.voices as f64));
+            lfo.restart();
+        }
+        self.update_pans();
+    }
+
+    fn update_pans(&mut self) {
+        // Equal-power pans spread evenly from one side to the other, scaled so the voices together
+        // sit at the level of one.
+        let gain = S::ONE / S::from_f64(self.voices as f64).sqrt();
+        for (i, pan) in self.pans.iter_mut().enumerate().take(self.voices) {
+            let position = if self.voices > 1 {
+                self.spread * S::from_f64(2.0 * i as f64 / (self.voices - 1) as f64 - 1.0)
+            } else {
+                S::ZERO
+            };
+            let (sin, cos) = math::sin_cos(S::from_f64(0.25) * S::PI * (position + S::ONE));
+            *pan = (gain * cos, gain * sin);
+        }
+    }
+
+    /// Sets the LFO rate in Hz.
+    pub fn set_rate(&mut self, rate: S) {
+        let rate = LfoRate::Free(rate.max(S::ZERO));
+        self.lfos.iter_mut().for_each(|lfo| lfo.set_rate(rate));
+    }
+
+    /// `MAX_CHORUS_DELAY` in samples.
+    fn max_delay(&self) -> S {
+        S::from_f64(MAX_CHORUS_DELAY) * self.sample_rate
+    }
+
+    /// Sets the shortest delay in seconds, which the voices swing up from.
+    pub fn set_delay(&mut self, seconds: S) {
+        self.delay = (seconds * self.sample_rate).clamp(S::ONE, self.max_delay());
+        self.depth = self.depth.min(self.max_delay() - self.delay);
+    }
+
+    /// Sets how far in seconds the voices swing above the delay.
+    pub fn set_depth(&mut self, seconds: S) {
+        self.depth = (seconds * self.sample_rate).clamp(S::ZERO, self.max_delay() - self.delay);
+    }
+
+    /// Sets how widely the voices are panned, from 0.0 (all in the centre) to 1.0 (from hard left
+    /// to hard right).
+    pub fn set_spread(&mut self, spread: S) {
+        self.spread = spread.clamp(S::ZERO, S::ONE);
+        self.update_pans();
+    }
+
+    /// Sets the balance from dry (0.0) to only the voices (1.0).
+    pub fn set_mix(&mut self, mix: S) {
+        self.mix = mix.clamp(S::ZERO, S::ONE);
+    }
+
+    /// Moves to a new sample rate, keeping the delays and the LFO rate in seconds. Fails, leaving
+    /// the chorus as it was, if the memory is smaller than `memory_len` for the new rate.
+    pub fn set_sample_rate(&mut self, sample_rate: S) -> Result<(), ModulationError> {
+        if Self::memory_len(sample_rate) > self.capacity {
+            return Err(ModulationError::MemoryTooSmall);
+        }
+
+        let (delay, depth) = (self.delay / self.sample_rate, self.depth / self.sample_rate);
+        self.sample_rate = sample_rate;
+        self.lfos.iter_mut().for_each(|lfo| lfo.set_sample_rate(sample_rate));
+        self.set_delay(delay);
+        self.set_depth(depth);
+
+        Ok(())
+    }
+
+    pub fn reset(&mut self) {
+        self.line.reset();
+        self.set_voices(self.voices);
+    }
+}
+
+impl<'a, S: Sample> StereoProcessor<S> for Chorus<'a, S> {
+    fn tick(&mut self, input: (S, S)) -> (S, S) {
+        let (mut left, mut right) = (S::ZERO, S::ZERO);
+        for (lfo, &(pan_left, pan_right)) in self.lfos.iter_mut().zip(self.pans.iter()).take(self.voices) {
+            let delay = self.delay + self.depth * lfo.tick();
+            let voice = self.line.read(delay);
+            left += pan_left * voice;
+            right += pan_right * voice;
+        }
+        self.line.write(S::HALF * (input.0 + input.1));
+
+        let dry = S::ONE - self.mix;
+        (dry * input.0 + self.mix * left, dry * input.1 + self.mix * right)
+    }
+}
+
+/// Longest delay a `Flanger` can reach, base delay and depth together, in seconds.
+pub const MAX_FLANGER_DELAY: f64 = 0.02;
+
+/// Highest feedback magnitude a `Flanger` allows, short of ringing forever.
+const MAX_FLANGER_FEEDBACK: f64 = 0.95;
+
+/// A stereo flanger: a short swept delay mixed back with the input, which cuts a comb of notches
+/// that sweep up and down the spectrum.
+///
+/// In through-zero mode the dry path is delayed too, to the middle of the sweep, so the swept
+/// copy passes from behind the dry signal to ahead of it and the notches sweep out to infinity
+/// and back, like a tape flanger's. The dry path has lines of its own, which only ever hold the
+/// input, so the feedback colours just the swept copy, as it does outside through-zero mode.
+pub struct Flanger<'a, S: Sample = f32> {
+    sample_rate: S,
+    /// Samples of memory the flanger was given.
+    capacity: usize,
+    lines: (DelayLine<'a, S>, DelayLine<'a, S>),
+    /// The through-zero dry path's delays.
+    dry_lines: (DelayLine<'a, S>, DelayLine<'a, S>),
+    lfos: (Lfo<S>, Lfo<S>),
+    /// Base delay and swing, in samples.
+    delay: S,
+    depth: S,
+    feedback: S,
+    through_zero: bool,
+    mix: S,
+}
+
+impl<'a, S: Sample> Flanger<'a, S> {
+    /// Samples of memory a flanger needs at `sample_rate`.
+    pub fn memory_len(sample_rate: S) -> usize {
+        4 * Self::line_len(sample_rate)
+    }
+
+    fn line_len(sample_rate: S) -> usize {
+        (MAX_FLANGER_DELAY * sample_rate.to_f64()) as usize + 4
+    }
+
+    /// Lays the flanger's delays out in `memory`, which must hold at least `memory_len` samples.
+    /// All of it is used, so enough for a higher rate lets `set_sample_rate` move up to it later.
+    pub fn new(memory: &'a mut [S], sample_rate: S) -> Result<Flanger<'a, S>, ModulationError> {
+        if memory.len() < Self::memory_len(sample_rate) {
+            return Err(ModulationError::MemoryTooSmall);
+        }
+
+        let capacity = memory.len();
+        let mut memory = memory;
+        let mut line = || take_line(&mut memory, capacity / 4);
+        let lines = (line(), line());
+        let dry_lines = (line(), line());
+        let rate = 0.2;
+        let spread = 0.25;
+
+        let mut flanger = Flanger {
+            sample_rate,
+            capacity,
+            lines,
+            dry_lines,
+            lfos: (
+                sine_lfo(rate, 0.0, LfoPolarity::Unipolar, sample_rate),
+                sine_lfo(rate, spread, LfoPolarity::Unipolar, sample_rate),
+            ),
+            delay: S::ZERO,
+            depth: S::ZERO,
+            feedback: S::ZERO,
+            through_zero: false,
+            mix: S::HALF,
+        };
+        flanger.set_delay(S::from_f64(0.0005));
+        flanger.set_depth(S::from_f64(0.004));
+
+        Ok(flanger)
+    }
+
+    /// Sets the LFO rate in Hz.
+    pub fn set_rate(&mut self, rate: S) {
+        let rate = LfoRate::Free(rate.max(S::ZERO));
+        self.lfos.0.set_rate(rate);
+        self.lfos.1.set_rate(rate);
+    }
+
+    /// Sets how far apart the channels' sweeps are, as a fraction of the LFO's cycle. Half a cycle
+    /// sweeps them in opposite directions.
+    pub fn set_spread(&mut self, spread: S) {
+        self.lfos.1.set_phase_offset(spread.clamp(S::ZERO, S::ONE));
+    }
+
+    /// `MAX_FLANGER_DELAY` in samples.
+    fn max_delay(&self) -> S {
+        S::from_f64(MAX_FLANGER_DELAY) * self.sample_rate
+    }
+
+    /// Sets the shortest delay in seconds, which the sweep rises from.
+    pub fn set_delay(&mut self, seconds: S) {
+        self.delay = (seconds * self.sample_rate).clamp(S::ONE, self.max_delay());
+        self.depth = self.depth.min(self.max_delay() - self.delay);
+    }
+
+    /// Sets how far in seconds the sweep rises above the delay.
+    pub fn set_depth(&mut self, seconds: S) {
+        self.depth = (seconds * self.sample_rate).clamp(S::ZERO, self.max_delay() - self.delay);
+    }
+
+    /// Sets how much of the swept delay feeds back into it, from -0.95 to 0.95. Positive feedback
+    /// sharpens the peaks between the notches, and negative feedback moves the peaks to where the
+    /// notches were.
+    pub fn set_feedback(&mut self, feedback: S) {
+        let max = S::from_f64(MAX_FLANGER_FEEDBACK);
+        self.feedback = feedback.clamp(-max, max);
+    }
+
+    pub fn set_through_zero(&mut self, through_zero: bool) {
+        self.through_zero = through_zero;
+    }
+
+    /// Sets the balance between the dry and swept signals. The notches are deepest at 0.5.
+    pub fn set_mix(&mut self, mix: S) {
+        self.mix = mix.clamp(S::ZERO, S::ONE);
+    }
+
+    /// Moves to a new sample rate, keeping the delays and the LFO rate in seconds. Fails, leaving
+    /// the flanger as it was, if the memory is smaller than `memory_len` for the new rate.
+    pub fn set_sample_rate(&mut self, sample_rate: S) -> Result<(), ModulationError> {
+        if Self::memory_len(sample_rate) > self.capacity {
+            return Err(ModulationError::MemoryTooSmall);
+        }
+
+        let (delay, depth) = (self.delay / self.sample_rate, self.depth / self.sample_rate);
+        self.sample_rate = sample_rate;
+        self.lfos.0.set_sample_rate(sample_rate);
+        self.lfos.1.set_sample_rate(sample_rate);
+        self.set_delay(delay);
+        self.set_depth(depth);
+
+        Ok(())
+    }
+
+    pub fn reset(&mut self) {
+        self.lines.0.reset();
+        self.lines.1.reset();
+        self.dry_lines.0.reset();
+        self.dry_lines.1.reset();
+        self.lfos.0.restart();
+        self.lfos.1.restart();
+    }
+
+    fn channel(&mut self, right: bool, input: S) -> S {
+        let (line, dry_line, lfo) = if right {
+            (&mut self.lines.1, &mut self.dry_lines.1, &mut self.lfos.1)
+        } else {
+            (&mut self.lines.0, &mut self.dry_lines.0, &mut self.lfos.0)
+        };
+
+        let delay = self.delay + self.depth * lfo.tick();
+        let wet = line.read(delay);
+        let dry = if self.through_zero {
+            dry_line.read(self.delay + S::HALF * self.depth)
+        } else {
+            input
+        };
+        line.write(input + self.feedback * wet);
+        dry_line.write(input);
+
+        (S::ONE - self.mix) * dry + self.mix * wet
+    }
+}
+
+impl<'a, S: Sample> StereoProcessor<S> for Flanger<'a, S> {
+    fn tick(&mut self, input: (S, S)) -> (S, S) {
+        (self.channel(false, input.0), self.channel(true, input.1))
+    }
+}
+
+/// Most allpass stages a `Phaser` can run.
+pub const MAX_PHASER_STAGES: usize = 12;
+
+/// Fewest allpass stages a `Phaser` can run.
+pub const MIN_PHASER_STAGES: usize = 4;
+
+/// A stereo phaser: a chain of first-order allpasses with a swept corner, mixed back with the
+/// input to cut a notch for every two stages.
+///
+/// The corner frequency sweeps exponentially either side of the centre, so the sweep sounds even
+/// across its range. Unlike a flanger's, the notches aren't harmonically spaced.
+pub struct Phaser<S: Sample = f32> {
+    sample_rate: S,
+    stages: usize,
+    states: ([S; MAX_PHASER_STAGES], [S; MAX_PHASER_STAGES]),
+    lfos: (Lfo<S>, Lfo<S>),
+    frequency: S,
+    /// Sweep either side of the centre, in octaves.
+    depth: S,
+    feedback: S,
+    mix: S,
+    /// Last output of each channel's chain, for the feedback.
+    previous: (S, S),
+}
+
+impl<S: Sample> Phaser<S> {
+    pub fn new(sample_rate: S) -> Phaser<S> {
+        let rate = 0.3;
+        let spread = 0.25;
+
+        Phaser {
+            sample_rate,
+            stages: 6,
+            states: ([S::ZERO; MAX_PHASER_STAGES], [S::ZERO; MAX_PHASER_STAGES]),
+            lfos: (
+                sine_lfo(rate, 0.0, LfoPolarity::Bipolar, sample_rate),
+                sine_lfo(rate, spread, LfoPolarity::Bipolar, sample_rate),
+            ),
+            frequency: S::from_f64(800.0),
+            depth: S::TWO,
+            feedback: S::ZERO,
+            mix: S::HALF,
+            previous: (S::ZERO, S::ZERO),
+        }
+    }
+
+    /// Sets the number of allpass stages, from `MIN_PHASER_STAGES` to `MAX_PHASER_STAGES`. Each
+    /// two stages add a notch.
+    pub fn set_stages(&mut self, stages: usize) {
+        self.stages = stages.clamp(MIN_PHASER_STAGES, MAX_PHASER_STAGES);
+    }
+
+    /// Sets the LFO rate in Hz.
+    pub fn set_rate(&mut self, rate: S) {
+        let rate = LfoRate::Free(rate.max(S::ZERO));
+        self.lfos.0.set_rate(rate);
+        self.lfos.1.set_rate(rate);
+    }
+
+    /// Sets how far apart the channels' sweeps are, as a fraction of the LFO's cycle.
+    pub fn set_spread(&mut self, spread: S) {
+        self.lfos.1.set_phase_offset(spread.clamp(S::ZERO, S::ONE));
+    }
+
+    /// Sets the centre of the stages' corner frequency sweep, in Hz.
+    pub fn set_frequency(&mut self, frequency: S) {
+        self.frequency = frequency;
+    }
+
+    /// Sets how far the corner frequency sweeps either side of the centre, in octaves.
+    pub fn set_depth(&mut self, octaves: S) {
+        self.depth = octaves.max(S::ZERO);
+    }
+
+    /// Sets how much of the chain's output feeds back into it, from -0.95 to 0.95, which
+    /// sharpens the peaks between the notches.
+    pub fn set_feedback(&mut self, feedback: S) {
+        let max = S::from_f64(0.95);
+        self.feedback = feedback.clamp(-max, max);
+    }
+
+    /// Sets the balance between the dry and phase-shifted signals. The notches are deepest at 0.5.
+    pub fn set_mix(&mut self, mix: S) {
+        self.mix = mix.clamp(S::ZERO, S::ONE);
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: S) {
+        self.sample_rate = sample_rate;
+        self.lfos.0.set_sample_rate(sample_rate);
+        self.lfos.1.set_sample_rate(sample_rate);
+    }
+
+    pub fn reset(&mut self) {
+        self.states = ([S::ZERO; MAX_PHASER_STAGES], [S::ZERO; MAX_PHASER_STAGES]);
+        self.previous = (S::ZERO, S::ZERO);
+        self.lfos.0.restart();
+        self.lfos.1.restart();
+    }
+
+    fn channel(&mut self, right: bool, input: S) -> S {
+        let (states, lfo, previous) = if right {
+            (&mut self.states.1, &mut self.lfos.1, &mut self.previous.1)
+        } else {
+            (&mut self.states.0, &mut self.lfos.0, &mut self.previous.0)
+        };
+
+        let corner = self.frequency * (self.depth * lfo.tick()).exp2();
+        let g = math::tan(S::PI * corner.clamp(S::ONE, S::from_f64(0.49) * self.sample_rate) / self.sample_rate);
+        // Bilinear first-order allpass, turning through -90 degrees at the corner.
+        let a = (g - S::ONE) / (g + S::ONE);
+
+        let mut x = input + self.feedback * *previous;
+        for state in states.iter_mut().take(self.stages) {
+            // Transposed direct form II.
+            let y = a * x + *state;
+            *state = x - a * y;
+            x = y;
+        }
+        *previous = x;
+
+        (S::ONE - self.mix) * input + self.mix * x
+    }
+}
+
+impl<S: Sample> StereoProcessor<S> for Phaser<S> {
+    fn tick(&mut self, input: (S, S)) -> (S, S) {
+        (self.channel(false, input.0), self.channel(true, input.1))
+    }
+}
@@ -0,0 +1,259 @@
+//! Seedable noise generators. The same seed always gives the same noise, so renders are
+//! reproducible and voices can be given decorrelated streams.
+
+use core::marker::PhantomData;
+
+use super::sample::Sample;
+use super::traits::MonoGenerator;
+
+/// Marsaglia's 32-bit xorshift generator. Fast and small, and good enough for audio noise, though
+/// not for anything needing statistical rigour.
+#[derive(Debug, Clone, Copy)]
+pub struct XorShift {
+    state: u32,
+}
+
+impl XorShift {
+    /// Xorshift can't start from zero, so a zero seed is replaced with a fixed one.
+    pub fn new(seed: u32) -> XorShift {
+        XorShift {
+            state: if seed == 0 { 0x9E37_79B9 } else { seed },
+        }
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.state = x;
+
+        x
+    }
+
+    /// A uniformly distributed 24-bit value in [-2^23, 2^23).
+    fn next_i24(&mut self) -> i32 {
+        self.next_u32() as i32 >> 8
+    }
+
+    /// A uniformly distributed value in [-1, 1). Only 24 bits are kept, so it's exact in an f32.
+    pub fn next_bipolar<S: Sample>(&mut self) -> S {
+        S::from_f64(self.next_i24() as f64 * I24_SCALE)
+    }
+}
+
+/// Converts a 24-bit integer to [-1, 1).
+const I24_SCALE: f64 = 1.0 / 8_388_608.0;
+
+/// Uniform white noise in [-1, 1), with a flat spectrum.
+pub struct WhiteNoise<S: Sample> {
+    rng: XorShift,
+    sample: PhantomData<S>,
+}
+
+impl<S: Sample> WhiteNoise<S> {
+    pub fn new(seed: u32) -> WhiteNoise<S> {
+        WhiteNoise {
+            rng: XorShift::new(seed),
+            sample: PhantomData,
+        }
+    }
+}
+
+impl<S: Sample> MonoGenerator<S> for WhiteNoise<S> {
+    fn tick(&mut self) -> S {
+        self.rng.next_bipolar()
+    }
+}
+
+/// Rows of the pink noise generator, each an octave apart, so the spectrum falls at 3 dB/octave
+/// for 16 octaves above the lowest.
+const PINK_ROWS: usize = 16;
+
+/// Pink noise in [-1, 1), falling at 3 dB/octave, by the Voss-McCartney algorithm.
+///
+/// The output is the sum of a white noise source and `PINK_ROWS` held random values, row k being
+/// replaced every 2^(k+1) samples. The rows are integers, so the running sum never drifts.
+pub struct PinkNoise<S: Sample> {
+    rng: XorShift,
+    rows: [i32; PINK_ROWS],
+    sum: i32,
+    counter: u32,
+    sample: PhantomData<S>,
+}
+
+impl<S: Sample> PinkNoise<S> {
+    pub fn new(seed: u32) -> PinkNoise<S> {
+        let mut rng = XorShift::new(seed);
+        let mut rows = [0; PINK_ROWS];
+        for row in rows.iter_mut() {
+            *row = rng.next_i24();
+        }
+
+        PinkNoise {
+            rng,
+            rows,
+            sum: rows.iter().sum(),
+            counter: 0,
+            sample: PhantomData,
+        }
+    }
+}
+
+impl<S: Sample> MonoGenerator<S> for PinkNoise<S> {
+    fn tick(&mut self) -> S {
+        self.counter = self.counter.wrapping_add(1);
+        let row = self.counter.trailing_zeros() as usize;
+        if row < PINK_ROWS {
+            let value = self.rng.next_i24();
+            self.sum += value - self.rows[row];
+            self.rows[row] = value;
+        }
+
+        let white = self.rng.next_i24();
+        S::from_f64((self.sum + white) as f64 * (I24_SCALE / (PINK_ROWS + 1) as f64))
+    }
+}
+
+/// How close to 1.0 the brown noise integrator's coefficient is. Sets the corner below which the
+/// spectrum flattens out, around 15 Hz at 48 kHz.
+const BROWN_LEAK: f64 = 0.998;
+
+/// Brown (red) noise, falling at 6 dB/octave, from leakily integrated white noise.
+///
+/// The leak keeps it from wandering off. The output is scaled to an RMS level of 0.25, and
+/// occasionally peaks beyond ±1.
+pub struct BrownNoise<S: Sample> {
+    rng: XorShift,
+    level: S,
+    leak: S,
+    gain: S,
+}
+
+impl<S: Sample> BrownNoise<S> {
+    pub fn new(seed: u32) -> BrownNoise<S> {
+        // White noise in [-1, 1) has a variance of 1/3, and the integrator multiplies it by
+        // 1 / (1 - leak^2).
+        let gain = 0.25 * libm::sqrt(3.0 * (1.0 - BROWN_LEAK * BROWN_LEAK));
+
+        BrownNoise {
+            rng: XorShift::new(seed),
+            level: S::ZERO,
+            leak: S::from_f64(BROWN_LEAK),
+            gain: S::from_f64(gain),
+        }
+    }
+}
+
+impl<S: Sample> MonoGenerator<S> for BrownNoise<S> {
+    fn tick(&mut self) -> S {
+        let white: S = self.rng.next_bipolar();
+        self.level = self.leak * self.level + self.gain * white;
+        self.level
+    }
+}
+
+/// What a `SampleAndHold` does on each clock.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum HoldMode<S: Sample> {
+    /// Jumps to a new random value, unrelated to the last.
+    Random,
+    /// Moves by a random amount of up to the given step either way, reflecting off ±1.
+    Walk(S),
+}
+
+/// Random values in [-1, 1], held between clocks. Clocked at a rate in Hz, or by `trigger`.
+pub struct SampleAndHold<S: Sample> {
+    rng: XorShift,
+    mode: HoldMode<S>,
+    rate: S,
+    sample_rate: S,
+    phase: u32,
+    phase_increment: u32,
+    value: S,
+}
+
+impl<S: Sample> SampleAndHold<S> {
+    pub fn new(rate: S, sample_rate: S, seed: u32) -> SampleAndHold<S> {
+        let mut rng = XorShift::new(seed);
+        let value = rng.next_bipolar();
+
+        let mut sh = SampleAndHold {
+            rng,
+            mode: HoldMode::Random,
+            rate,
+            sample_rate,
+            phase: 0,
+            phase_increment: 0,
+            value,
+        };
+        sh.update_phase_increment();
+
+        sh
+    }
+
+    fn update_phase_increment(&mut self) {
+        // Clocking faster than the sample rate just takes a new value every sample.
+        let cycles = (self.rate.to_f64() / self.sample_rate.to_f64()).clamp(0.0, 1.0);
+        self.phase_increment = libm::round(cycles * 4_294_967_296.0).min(u32::MAX as f64) as u32;
+    }
+
+    /// Sets the clock rate in Hz. Zero stops the clock, leaving only `trigger`.
+    pub fn set_rate(&mut self, rate: S) {
+        self.rate = rate;
+        self.update_phase_increment();
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: S) {
+        self.sample_rate = sample_rate;
+        self.update_phase_increment();
+    }
+
+    pub fn set_mode(&mut self, mode: HoldMode<S>) {
+        self.mode = match mode {
+            HoldMode::Walk(step) => HoldMode::Walk(step.max(S::ZERO).min(S::ONE)),
+            HoldMode::Random => HoldMode::Random,
+        };
+    }
+
+    /// Takes a new value now, as a clock would, and restarts the clock's cycle.
+    pub fn trigger(&mut self) {
+        self.phase = 0;
+        self.next_value();
+    }
+
+    pub fn value(&self) -> S {
+        self.value
+    }
+
+    fn next_value(&mut self) {
+        let random: S = self.rng.next_bipolar();
+        self.value = match self.mode {
+            HoldMode::Random => random,
+            HoldMode::Walk(step) => {
+                let value = self.value + step * random;
+                if value > S::ONE {
+                    S::TWO - value
+                } else if value < -S::ONE {
+                    -S::TWO - value
+                } else {
+                    value
+                }
+            }
+        };
+    }
+}
+
+impl<S: Sample> MonoGenerator<S> for SampleAndHold<S> {
+    fn tick(&mut self) -> S {
+        let x = self.value;
+
+        let previous = self.phase;
+        self.phase = previous.wrapping_add(self.phase_increment);
+        if self.phase < previous || self.phase_increment == u32::MAX {
+            self.next_value();
+        }
+
+        x
+    }
+}
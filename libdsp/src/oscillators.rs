@@ -1,9 +1,6 @@
-use super::{SampleType, PI, TWO_PI};
+use super::sample::Sample;
 use super::traits::{MonoGenerator};
 
-use micromath::F32Ext;
-
-
 #[derive(PartialEq, Clone, Copy)]
 pub enum OscillatorMode {
     Sine,
@@ -12,24 +9,78 @@ pub enum OscillatorMode {
     Triangle,
 }
 
-pub struct Oscillator {
-    sample_rate: SampleType,
+/// How `tick_modulated` treats frequency modulation that would take the frequency past zero.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum FmMode {
+    /// The frequency stops at zero, stalling the oscillator.
+    Linear,
+    /// The frequency carries on through zero and the phase runs backwards.
+    ThroughZero,
+}
+
+/// The f32 oscillator the firmware runs.
+pub type Oscillator = GenericOscillator<f32>;
+
+/// A double-precision oscillator, for reference renders on the host.
+pub type Oscillator64 = GenericOscillator<f64>;
+
+/// 2^32, the number of phase steps in a cycle.
+const PHASE_SCALE: f64 = 4_294_967_296.0;
+
+/// The most the triangle's leaky integrator may take from each new square sample. From 1.0 it
+/// rings, and from 2.0 it diverges.
+const MAX_TRIANGLE_LEAK: f64 = 0.999_999;
+
+/// The phase is a wrapping 32-bit accumulator, in units of 2^-32 of a cycle. Integer addition is
+/// exact, so the phase can't drift over long notes, and wrapping needs no loop however large the
+/// increment.
+pub struct GenericOscillator<S: Sample> {
+    sample_rate: S,
     mode: OscillatorMode,
-    frequency: SampleType,
-    phase: SampleType,
-    phase_increment: SampleType,
-    last_output: SampleType,
+    frequency: S,
+    phase: u32,
+    phase_increment: u32,
+    /// The frequency in phase steps per sample, unrounded and unwrapped, for frequency modulation.
+    frequency_steps: f64,
+    /// Phase steps per sample for each Hz, to turn frequency modulation into steps.
+    steps_per_hz: S,
+    // Size of the phase increment in cycles per sample, and its reciprocal, for the PolyBLEP
+    // residuals.
+    dt: S,
+    inv_dt: S,
+    /// 1.0, or -1.0 when the phase runs backwards.
+    direction: S,
+    last_output: S,
+    /// Where the phase wrapped during the last sample, for syncing other oscillators.
+    sync_out: Option<S>,
+    /// PolyBLEP correction owed to the next sample by `tick_synced`.
+    next_blep: S,
+    /// Fraction of the cycle the square spends high.
+    pulse_width: S,
+    fm_mode: FmMode,
+    /// The phase modulation offset of the last `tick_modulated`.
+    pm_offset: u32,
 }
 
-impl Oscillator {
-    pub fn new(mode: OscillatorMode, frequency: SampleType, sample_rate: SampleType) -> Oscillator {
-        let mut osc = Oscillator {
+impl<S: Sample> GenericOscillator<S> {
+    pub fn new(mode: OscillatorMode, frequency: S, sample_rate: S) -> GenericOscillator<S> {
+        let mut osc = GenericOscillator {
             sample_rate,
             mode,
             frequency,
-            phase: 0.0,
-            phase_increment: 0.0,
-            last_output: 0.0,
+            phase: 0,
+            phase_increment: 0,
+            frequency_steps: 0.0,
+            steps_per_hz: S::ZERO,
+            dt: S::ZERO,
+            inv_dt: S::ZERO,
+            direction: S::ONE,
+            last_output: S::ZERO,
+            sync_out: None,
+            next_blep: S::ZERO,
+            pulse_width: S::HALF,
+            fm_mode: FmMode::Linear,
+            pm_offset: 0,
         };
         osc.update_phase_increment();
 
@@ -37,92 +88,444 @@ impl Oscillator {
     }
 
     fn update_phase_increment(&mut self) {
-        self.phase_increment = self.frequency * 2.0 * PI / self.sample_rate;
+        let sample_rate = self.sample_rate.to_f64();
+        self.frequency_steps = self.frequency.to_f64() / sample_rate * PHASE_SCALE;
+        self.steps_per_hz = S::from_f64(PHASE_SCALE / sample_rate);
+
+        self.phase_increment = steps_to_increment(self.frequency_steps);
+        let (dt, inv_dt, direction) = step_size(self.phase_increment);
+        self.dt = dt;
+        self.inv_dt = inv_dt;
+        self.direction = direction;
     }
 
-    pub fn set_frequency(&mut self, frequency: SampleType) {
+    pub fn set_frequency(&mut self, frequency: S) {
         self.frequency = frequency;
         self.update_phase_increment();
     }
 
-    pub fn set_sample_rate(&mut self, sample_rate: SampleType) {
+    pub fn set_sample_rate(&mut self, sample_rate: S) {
         self.sample_rate = sample_rate;
         self.update_phase_increment();
     }
 
-    fn poly_blep(&self, t: SampleType) -> SampleType {
-        let dt = self.phase_increment / TWO_PI;
-        if t < dt {
-            let x = t / dt;
-            x + x - x * x - 1.0
-        } else if t > 1.0 - dt {
-            let x = (t - 1.0) / dt;
-            x * x + x + x + 1.0
-        } else {
-            0.0
-        }
+    /// Sets the fraction of each cycle the square spends high, from 0.0 to 1.0. The output is
+    /// offset so its average stays at zero whatever the width. Nothing is recalculated, so this
+    /// can be called every sample for audio-rate PWM. The triangle always uses an even square.
+    pub fn set_pulse_width(&mut self, pulse_width: S) {
+        self.pulse_width = pulse_width.max(S::ZERO).min(S::ONE);
+    }
+
+    pub fn pulse_width(&self) -> S {
+        self.pulse_width
+    }
+
+    pub fn set_fm_mode(&mut self, fm_mode: FmMode) {
+        self.fm_mode = fm_mode;
     }
 
-    fn naive_waveform(&self, mode: OscillatorMode) -> SampleType {
+    pub fn fm_mode(&self) -> FmMode {
+        self.fm_mode
+    }
+
+    /// Restarts the cycle from the beginning at the next sample. The jump isn't band-limited, so
+    /// this is for retriggering on a note on rather than for sync.
+    pub fn reset_phase(&mut self) {
+        self.phase = 0;
+        self.next_blep = S::ZERO;
+    }
+
+    /// If the phase wrapped during the last sample, how long before the end of the sample it
+    /// wrapped, as a fraction of a sample period. Feeding this to another oscillator's
+    /// `tick_synced` hard-syncs it to this one.
+    pub fn sync_out(&self) -> Option<S> {
+        self.sync_out
+    }
+
+    /// Advances the phase by a sample, noting where it wrapped for `sync_out`.
+    #[inline(always)]
+    fn advance(&mut self) {
+        let previous = self.phase;
+        self.phase = previous.wrapping_add(self.phase_increment);
+        self.sync_out = wrap_position(previous, self.phase, self.direction, self.inv_dt);
+    }
+
+    /// The waveform at phase `t`, with the square high for the first `width` of the cycle.
+    fn naive_waveform(mode: OscillatorMode, t: S, width: S) -> S {
         match mode {
-            OscillatorMode::Sine => self.phase.sin(),
-            OscillatorMode::Saw => (2.0 * self.phase / TWO_PI) - 1.0,
-            OscillatorMode::Square => {
-                if self.phase < PI {
-                    1.0
-                } else {
-                    -1.0
-                }
-            }
+            OscillatorMode::Sine => (S::TWO_PI * t).sin(),
+            OscillatorMode::Saw => S::TWO * t - S::ONE,
+            OscillatorMode::Square => pulse(t, width),
             OscillatorMode::Triangle => {
-                let x = -1.0 + (2.0 * self.phase / TWO_PI);
-                2.0 * (x.abs() - 0.5)
+                let x = S::TWO * t - S::ONE;
+                S::TWO * (x.abs() - S::HALF)
             }
         }
     }
 
-    pub fn tick_naive(&mut self) -> SampleType {
-        let x = self.naive_waveform(self.mode);
-        
-        self.phase += self.phase_increment;
-        while self.phase >= TWO_PI {
-            self.phase -= TWO_PI
+    pub fn tick_naive(&mut self) -> S {
+        let x = Self::naive_waveform(self.mode, S::from_phase(self.phase), self.pulse_width);
+        self.advance();
+
+        x
+    }
+
+    pub fn tick_poly_blep(&mut self) -> S {
+        let x = self.poly_blep_sample(S::from_phase(self.phase), self.dt, self.inv_dt, self.direction);
+        self.advance();
+
+        x
+    }
+
+    /// A PolyBLEP tick with audio-rate modulation. `fm` is added to the frequency in Hz, as set by
+    /// the FM mode, and `pm` to the phase in cycles. With both at zero this matches
+    /// `tick_poly_blep`.
+    ///
+    /// The frequency modulation is scaled to phase steps by a factor kept by `set_frequency` and
+    /// `set_sample_rate`, and added to the increment. Only while the phase is being modulated
+    /// does the step size, and with it a divide, have to be worked out each sample.
+    ///
+    /// The PolyBLEP residuals follow the instantaneous rate the phase moves at, so edges stay
+    /// band-limited under modulation, though fast changes of direction aren't fully corrected.
+    pub fn tick_modulated(&mut self, fm: S, pm: S) -> S {
+        let mut steps = self.frequency_steps + (fm * self.steps_per_hz).to_f64();
+        if self.fm_mode == FmMode::Linear {
+            steps = if self.frequency_steps < 0.0 { steps.min(0.0) } else { steps.max(0.0) };
         }
+        let increment = steps_to_increment(steps);
+
+        // The waveform is read at the modulated phase, which moves by the increment plus however
+        // much the phase modulation changed since the last sample.
+        let pm_offset = cycles_to_phase(pm.to_f64());
+        let step = increment.wrapping_add(pm_offset.wrapping_sub(self.pm_offset));
+        self.pm_offset = pm_offset;
+
+        let (dt, inv_dt, direction) = self.step_size(step);
+        let x = self.poly_blep_sample(S::from_phase(self.phase.wrapping_add(pm_offset)), dt, inv_dt, direction);
+
+        // Sync follows the unmodulated phase, which only frequency modulation moves.
+        let (_, inv_dt, direction) = if step == increment { (dt, inv_dt, direction) } else { self.step_size(increment) };
+        let previous = self.phase;
+        self.phase = previous.wrapping_add(increment);
+        self.sync_out = wrap_position(previous, self.phase, direction, inv_dt);
 
         x
     }
 
-    pub fn tick_poly_blep(&mut self) -> SampleType {
-        let t = self.phase / TWO_PI;
+    /// Block version of `tick_modulated`, taking the modulation for each sample from `fm` and `pm`.
+    /// Only the common length of the three slices is filled.
+    pub fn fill_modulated(&mut self, buffer: &mut [S], fm: &[S], pm: &[S]) {
+        for ((x, &fm), &pm) in buffer.iter_mut().zip(fm).zip(pm) {
+            *x = self.tick_modulated(fm, pm);
+        }
+    }
 
-        let samp: SampleType = match self.mode {
-            OscillatorMode::Sine => self.naive_waveform(OscillatorMode::Sine),
-            OscillatorMode::Saw => self.naive_waveform(OscillatorMode::Saw) - self.poly_blep(t),
-            _ => {
-                let mut x = self.naive_waveform(OscillatorMode::Square);
-                x += self.poly_blep(t);
-                x -= self.poly_blep((t + 0.5).fract());
+    /// `step_size`, reusing the values kept for the unmodulated increment when `step` matches it.
+    #[inline(always)]
+    fn step_size(&self, step: u32) -> (S, S, S) {
+        if step == self.phase_increment {
+            (self.dt, self.inv_dt, self.direction)
+        } else {
+            step_size(step)
+        }
+    }
 
-                if self.mode == OscillatorMode::Triangle {
-                    x = self.phase_increment * x + (1.0 - self.phase_increment) * self.last_output;
-                    self.last_output = x;
-                }
+    /// The PolyBLEP waveform at phase `t`, for a phase moving `dt` cycles a sample in `direction`.
+    #[inline(always)]
+    fn poly_blep_sample(&mut self, t: S, dt: S, inv_dt: S, direction: S) -> S {
+        match self.mode {
+            OscillatorMode::Sine => Self::naive_waveform(OscillatorMode::Sine, t, S::HALF),
+            OscillatorMode::Saw => Self::naive_waveform(OscillatorMode::Saw, t, S::HALF) - poly_blep(t, dt, inv_dt),
+            OscillatorMode::Square => square_poly_blep(t, self.pulse_width, dt, inv_dt),
+            OscillatorMode::Triangle => {
+                let x = square_poly_blep(t, S::HALF, dt, inv_dt);
+                self.last_output = triangle_step(x * direction, triangle_leak(dt), self.last_output);
+                self.last_output
+            }
+        }
+    }
 
-                x
+    /// A PolyBLEP tick that, given a master oscillator's `sync_out`, restarts the cycle at that
+    /// point with the jump band-limited. Without a sync it matches `tick_poly_blep`.
+    ///
+    /// The correction for a jump is spread over the samples either side of it, so a synced
+    /// oscillator should go through this for every sample rather than mixing in other ticks.
+    /// Sync needs the phase to run forwards; with a negative frequency the reset is a hard one.
+    pub fn tick_synced(&mut self, sync: Option<S>) -> S {
+        if self.direction < S::ZERO {
+            if sync.is_some() {
+                self.reset_phase();
             }
+            return self.tick_poly_blep();
+        }
+
+        // The triangle is integrated from a square, so it's the square that gets corrected.
+        let (shape, width) = if self.mode == OscillatorMode::Triangle {
+            (OscillatorMode::Square, S::HALF)
+        } else {
+            (self.mode, self.pulse_width)
         };
+        let t = S::from_phase(self.phase);
+        let dt = self.dt;
+
+        let mut this_sample = Self::naive_waveform(shape, t, width) + self.next_blep;
+        let mut next_sample = S::ZERO;
+
+        match sync {
+            None => {
+                blep_edges(shape, width, t, t + dt, dt, S::ZERO, &mut this_sample, &mut next_sample);
+                self.advance();
+            }
+            Some(position) => {
+                let position = position.max(S::ZERO).min(S::ONE);
 
-        self.phase += self.phase_increment;
-        while self.phase >= TWO_PI {
-            self.phase -= TWO_PI
+                // The waveform's own edges up to the reset, then the reset itself, then any edges
+                // between the restarted phase and the next sample.
+                let reset_at = t + dt * (S::ONE - position);
+                blep_edges(shape, width, t, reset_at, dt, position, &mut this_sample, &mut next_sample);
+
+                let before_reset = if reset_at >= S::ONE { reset_at - S::ONE } else { reset_at };
+                let jump = Self::naive_waveform(shape, S::ZERO, width) - Self::naive_waveform(shape, before_reset, width);
+                add_blep(jump, position, &mut this_sample, &mut next_sample);
+
+                blep_edges(shape, width, S::ZERO, dt * position, dt, S::ZERO, &mut this_sample, &mut next_sample);
+
+                self.phase = libm::round(self.phase_increment as f64 * position.to_f64()) as u32;
+                self.sync_out = Some(position);
+            }
         }
+        self.next_blep = next_sample;
 
-        samp
+        if self.mode == OscillatorMode::Triangle {
+            this_sample = triangle_step(this_sample, triangle_leak(dt), self.last_output);
+            self.last_output = this_sample;
+        }
+
+        this_sample
     }
+
+    /// Block version of `tick_naive`, producing identical samples.
+    pub fn fill_naive(&mut self, buffer: &mut [S]) {
+        let (mode, width) = (self.mode, self.pulse_width);
+        fill_phase(&mut self.phase, self.phase_increment, buffer, |t| Self::naive_waveform(mode, t, width));
+        self.update_sync_out(buffer.len());
+    }
+
+    /// Block version of `tick_poly_blep`, producing identical samples.
+    pub fn fill_poly_blep(&mut self, buffer: &mut [S]) {
+        let increment = self.phase_increment;
+        let (dt, inv_dt) = (self.dt, self.inv_dt);
+        match self.mode {
+            OscillatorMode::Sine => fill_phase(&mut self.phase, increment, buffer, |t| {
+                Self::naive_waveform(OscillatorMode::Sine, t, S::HALF)
+            }),
+            OscillatorMode::Saw => fill_phase(&mut self.phase, increment, buffer, |t| {
+                Self::naive_waveform(OscillatorMode::Saw, t, S::HALF) - poly_blep(t, dt, inv_dt)
+            }),
+            OscillatorMode::Square => {
+                let width = self.pulse_width;
+                fill_phase(&mut self.phase, increment, buffer, |t| square_poly_blep(t, width, dt, inv_dt))
+            }
+            OscillatorMode::Triangle => {
+                let (direction, leak) = (self.direction, triangle_leak(dt));
+                let mut last_output = self.last_output;
+                fill_phase(&mut self.phase, increment, buffer, |t| {
+                    let x = square_poly_blep(t, S::HALF, dt, inv_dt);
+                    last_output = triangle_step(x * direction, leak, last_output);
+                    last_output
+                });
+                self.last_output = last_output;
+            }
+        }
+        self.update_sync_out(buffer.len());
+    }
+
+    /// `fill_poly_blep` with the pulse width set from `pulse_width` before each sample, for PWM at
+    /// audio rate. Fills as much of `buffer` as `pulse_width` covers.
+    pub fn fill_poly_blep_pwm(&mut self, buffer: &mut [S], pulse_width: &[S]) {
+        for (x, &width) in buffer.iter_mut().zip(pulse_width) {
+            self.set_pulse_width(width);
+            *x = self.tick_poly_blep();
+        }
+    }
+
+    /// Sets `sync_out` for the last sample of a block, as the per-sample ticks would have.
+    fn update_sync_out(&mut self, block_len: usize) {
+        if block_len > 0 {
+            let previous = self.phase.wrapping_sub(self.phase_increment);
+            self.sync_out = wrap_position(previous, self.phase, self.direction, self.inv_dt);
+        }
+    }
+}
+
+/// Converts a number of cycles to a phase step. Only the fraction of a cycle matters, so negative
+/// numbers become steps that run the phase backwards.
+fn cycles_to_phase(cycles: f64) -> u32 {
+    let cycles = cycles - libm::floor(cycles);
+    libm::round(cycles * PHASE_SCALE) as u64 as u32
+}
+
+/// Rounds a number of phase steps per sample to an increment. Frequencies above Nyquist fold to
+/// their aliases and negative ones to an increment that runs the phase backwards, as sampling
+/// would.
+#[inline(always)]
+fn steps_to_increment(steps: f64) -> u32 {
+    libm::round(steps) as i64 as u32
+}
+
+/// The size of a phase step in cycles, its reciprocal, and 1.0 or -1.0 for the way it runs.
+#[inline(always)]
+fn step_size<S: Sample>(step: u32) -> (S, S, S) {
+    let signed = step as i32;
+    let dt = S::from_f64(signed.unsigned_abs() as f64 / PHASE_SCALE);
+    let direction = if signed < 0 { -S::ONE } else { S::ONE };
+
+    (dt, S::ONE / dt, direction)
+}
+
+/// If the phase wrapped going from `previous` to `phase`, how long before `phase` it wrapped, as
+/// a fraction of the step.
+fn wrap_position<S: Sample>(previous: u32, phase: u32, direction: S, inv_dt: S) -> Option<S> {
+    let since_wrap = if direction > S::ZERO && phase < previous {
+        S::from_phase(phase)
+    } else if direction < S::ZERO && phase > previous {
+        S::ONE - S::from_phase(phase)
+    } else {
+        return None;
+    };
+
+    Some((since_wrap * inv_dt).min(S::ONE))
+}
+
+#[inline(always)]
+fn poly_blep<S: Sample>(t: S, dt: S, inv_dt: S) -> S {
+    if t < dt {
+        let x = t * inv_dt;
+        x + x - x * x - S::ONE
+    } else if t > S::ONE - dt {
+        let x = (t - S::ONE) * inv_dt;
+        x * x + x + x + S::ONE
+    } else {
+        S::ZERO
+    }
+}
+
+/// A pulse high for the first `width` of the cycle, offset by 1 - 2 * `width` so it averages zero.
+/// At a width of 0.5 the offset is exactly zero.
+#[inline(always)]
+fn pulse<S: Sample>(t: S, width: S) -> S {
+    let x = if t < width { S::ONE } else { -S::ONE };
+    x + (S::ONE - S::TWO * width)
 }
 
-impl MonoGenerator for Oscillator {
-    fn tick(&mut self) -> SampleType {
+#[inline(always)]
+fn square_poly_blep<S: Sample>(t: S, width: S, dt: S, inv_dt: S) -> S {
+    let mut x = pulse(t, width);
+    x += poly_blep(t, dt, inv_dt);
+    x -= poly_blep(if t < width { t + (S::ONE - width) } else { t - width }, dt, inv_dt);
+    x
+}
+
+/// The waveform's own discontinuities between phases `from` (exclusive) and `to` (inclusive), in
+/// cycles, with `to` lying `offset` samples before the next sample. `to` may pass the end of the
+/// cycle. The square falls at `width`.
+#[inline(always)]
+#[allow(clippy::too_many_arguments)]
+fn blep_edges<S: Sample>(
+    shape: OscillatorMode,
+    width: S,
+    from: S,
+    to: S,
+    dt: S,
+    offset: S,
+    this_sample: &mut S,
+    next_sample: &mut S,
+) {
+    let mut edge = |at: S, jump: S| {
+        if from < at && at <= to {
+            add_blep(jump, (to - at) / dt + offset, this_sample, next_sample);
+        }
+    };
+
+    match shape {
+        OscillatorMode::Sine => {}
+        OscillatorMode::Saw => edge(S::ONE, -S::TWO),
+        OscillatorMode::Square | OscillatorMode::Triangle => {
+            // A narrow pulse can fall again in the next cycle before `to`.
+            edge(width, -S::TWO);
+            edge(S::ONE, S::TWO);
+            edge(S::ONE + width, -S::TWO);
+        }
+    }
+}
+
+/// Spreads a jump of `jump`, which happened `elapsed` samples before this sample's successor,
+/// over this sample and the next.
+#[inline(always)]
+fn add_blep<S: Sample>(jump: S, elapsed: S, this_sample: &mut S, next_sample: &mut S) {
+    let remaining = S::ONE - elapsed;
+    *this_sample += jump * S::HALF * elapsed * elapsed;
+    *next_sample -= jump * S::HALF * remaining * remaining;
+}
+
+/// The triangle integrator's leak for a step of `dt` cycles: 2π·`dt`, which gives the triangle the
+/// same amplitude as the square, limited below 1.0 for steps over 1/π of a cycle. Up there the
+/// triangle becomes the square.
+#[inline(always)]
+fn triangle_leak<S: Sample>(dt: S) -> S {
+    (S::TWO_PI * dt).min(S::from_f64(MAX_TRIANGLE_LEAK))
+}
+
+/// One step of the leaky integrator that turns the band-limited square into a triangle. The
+/// square is inverted when the phase runs backwards, so the triangle keeps its shape.
+#[inline(always)]
+fn triangle_step<S: Sample>(square: S, leak: S, last_output: S) -> S {
+    leak * square + (S::ONE - leak) * last_output
+}
+
+/// Writes `waveform(t)` for each sample of a block, `t` being the phase as a fraction of a cycle,
+/// while advancing `phase` exactly as the per-sample paths do.
+#[inline(always)]
+fn fill_phase<S, F>(phase: &mut u32, increment: u32, buffer: &mut [S], mut waveform: F)
+where
+    S: Sample,
+    F: FnMut(S) -> S,
+{
+    let mut p = *phase;
+    for x in buffer.iter_mut() {
+        *x = waveform(S::from_phase(p));
+        p = p.wrapping_add(increment);
+    }
+    *phase = p;
+}
+
+/// Two oscillators in hard sync: the slave restarts its cycle whenever the master's wraps, so the
+/// output has the master's pitch and a timbre set by the slave's frequency.
+pub struct HardSync<S: Sample> {
+    pub master: GenericOscillator<S>,
+    pub slave: GenericOscillator<S>,
+}
+
+impl<S: Sample> HardSync<S> {
+    pub fn new(master: GenericOscillator<S>, slave: GenericOscillator<S>) -> HardSync<S> {
+        HardSync { master, slave }
+    }
+}
+
+impl<S: Sample> MonoGenerator<S> for HardSync<S> {
+    fn tick(&mut self) -> S {
+        // Only the master's phase is needed, not its output.
+        self.master.advance();
+        self.slave.tick_synced(self.master.sync_out())
+    }
+}
+
+impl<S: Sample> MonoGenerator<S> for GenericOscillator<S> {
+    fn tick(&mut self) -> S {
         self.tick_poly_blep()
     }
-}
\ No newline at end of file
+
+    fn fill(&mut self, buffer: &mut [S]) {
+        self.fill_poly_blep(buffer);
+    }
+}
@@ -0,0 +1,315 @@
+//! A stereo feedback delay network reverb, with its delay memory supplied by the caller.
+
+use core::mem;
+
+use super::delay::{DelayLine, Interpolation};
+use super::sample::Sample;
+use super::traits::{MonoProcessor, StereoProcessor};
+
+/// Delay lines in the feedback network.
+const LINES: usize = 8;
+
+/// Lengths of the network's delay lines at full size and 48 kHz, in samples. They're mutually
+/// prime, so their echoes rarely line up, and spread over 30 to 57 ms.
+const LINE_LENGTHS: [usize; LINES] = [1447, 1601, 1811, 1949, 2153, 2351, 2503, 2713];
+
+/// Input diffusers, which smear the input into a dense cloud before it reaches the network.
+const DIFFUSERS: usize = 4;
+
+/// Lengths of the input diffusers at 48 kHz, in samples.
+const DIFFUSER_LENGTHS: [usize; DIFFUSERS] = [167, 239, 347, 443];
+
+const DIFFUSION: f64 = 0.6;
+
+/// Size setting 0.0 scales the lines to this fraction of their full length.
+const MIN_SIZE: f64 = 0.2;
+
+/// Longest pre-delay, in seconds.
+pub const MAX_PRE_DELAY: f64 = 0.25;
+
+/// Cycles per sample the reverb may take on the Daisy's Cortex-M7, a tenth of the core at 48 kHz.
+pub const CYCLE_BUDGET: u32 = 1000;
+
+/// How far the rotations swing either way at full depth, as the tangent of half the angle. A
+/// quarter turn, which swaps a pair of lines at the extremes.
+const MODULATION_DEPTH: f64 = 1.0;
+
+/// Rates of the rotations between pairs of lines, in Hz. Unrelated, so the swings never line up.
+const MODULATION_RATES: [f64; LINES / 2] = [0.53, 0.71, 0.89, 1.13];
+
+/// Output level, so the tail sits near the level of the dry signal at moderate decays.
+const OUTPUT_GAIN: f64 = 0.5;
+
+/// 2^32, the number of phase steps in a modulation cycle.
+const PHASE_SCALE: f64 = 4_294_967_296.0;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ReverbError {
+    /// The memory is smaller than `Reverb::memory_len` for the sample rate.
+    MemoryTooSmall,
+}
+
+/// A stereo reverb built from an eight-line feedback delay network with Householder mixing.
+///
+/// The stereo input is summed to mono, pre-delayed, and diffused by a chain of allpasses before
+/// entering the network. Each line has a one-pole low pass for damping and a gain that sets the
+/// decay time. Pairs of lines are rotated into each other by slowly swinging angles before the
+/// Householder mix, which breaks up metallic ringing while keeping the network lossless, as in
+/// Schlecht and Habets' time-varying feedback matrices. The left and right outputs tap alternate
+/// lines, which keeps them decorrelated.
+///
+/// All the delay memory comes from one caller-supplied buffer, so it can live in the Daisy's
+/// SDRAM. The work per sample is fixed: 13 delay reads and writes, 8 one-poles, 4 rotations, the
+/// Householder mix and 4 cheap modulation oscillators. Every line reads and writes sequentially,
+/// which lets the cache hide most of the SDRAM's latency. On the Daisy it must fit in
+/// `CYCLE_BUDGET` cycles per sample, which the firmware checks with the DWT cycle counter and
+/// logs once a second. The `effects` bench tracks its cost on the host.
+pub struct Reverb<'a, S: Sample = f32> {
+    sample_rate: S,
+    pre_delay: DelayLine<'a, S>,
+    diffusers: [DelayLine<'a, S>; DIFFUSERS],
+    lines: [DelayLine<'a, S>; LINES],
+    /// Each line's current length in samples, before modulation.
+    lengths: [S; LINES],
+    gains: [S; LINES],
+    damping_states: [S; LINES],
+    damping_coefficient: S,
+    modulation_phases: [u32; LINES / 2],
+    modulation_increments: [u32; LINES / 2],
+    /// Tangent of half the rotations' widest angle.
+    modulation_depth: S,
+    size: S,
+    decay: S,
+    mix: S,
+}
+
+impl<'a, S: Sample> Reverb<'a, S> {
+    /// Samples of memory a reverb needs at `sample_rate`.
+    pub fn memory_len(sample_rate: S) -> usize {
+        let sample_rate = sample_rate.to_f64();
+        let scale = sample_rate / 48000.0;
+        let pre_delay = Self::pre_delay_len(sample_rate);
+        let diffusers: usize = DIFFUSER_LENGTHS.iter().map(|&l| Self::diffuser_len(l, scale)).sum();
+        let lines: usize = LINE_LENGTHS.iter().map(|&l| Self::line_len(l, sample_rate)).sum();
+
+        pre_delay + diffusers + lines
+    }
+
+    /// Samples a delay line needs to reach `delay`, and never fewer than the 4 `DelayLine` needs,
+    /// which the diffusers' delays drop below at low sample rates.
+    fn buffer_len(delay: f64) -> usize {
+        (delay as usize + 3).max(4)
+    }
+
+    fn pre_delay_len(sample_rate: f64) -> usize {
+        Self::buffer_len(MAX_PRE_DELAY * sample_rate)
+    }
+
+    fn diffuser_len(length: usize, scale: f64) -> usize {
+        Self::buffer_len(length as f64 * scale)
+    }
+
+    fn line_len(length: usize, sample_rate: f64) -> usize {
+        Self::buffer_len(length as f64 * sample_rate / 48000.0)
+    }
+
+    /// Lays the reverb's delays out in `memory`, which must hold at least `memory_len` samples.
+    pub fn new(memory: &'a mut [S], sample_rate: S) -> Result<Reverb<'a, S>, ReverbError> {
+        if memory.len() < Self::memory_len(sample_rate) {
+            return Err(ReverbError::MemoryTooSmall);
+        }
+
+        let mut rest = memory;
+        let mut take = |len: usize| {
+            let (line, remaining) = mem::take(&mut rest).split_at_mut(len);
+            rest = remaining;
+            DelayLine::new(line)
+        };
+
+        let rate = sample_rate.to_f64();
+        let scale = rate / 48000.0;
+        let mut pre_delay = take(Self::pre_delay_len(rate));
+        let mut diffusers = DIFFUSER_LENGTHS.map(|l| take(Self::diffuser_len(l, scale)));
+        let mut lines = LINE_LENGTHS.map(|l| take(Self::line_len(l, rate)));
+
+        // The lines only move when the size changes, so they can use the allpass, which unlike
+        // the others doesn't dull the tail a little more on every pass.
+        for line in lines.iter_mut() {
+            line.set_interpolation(Interpolation::Allpass);
+        }
+
+        pre_delay.set_delay(S::ONE);
+        for (diffuser, &length) in diffusers.iter_mut().zip(DIFFUSER_LENGTHS.iter()) {
+            diffuser.set_delay(S::from_f64(length as f64 * scale));
+        }
+
+        let mut increments = [0; LINES / 2];
+        for (increment, &modulation_rate) in increments.iter_mut().zip(MODULATION_RATES.iter()) {
+            *increment = (modulation_rate / rate * PHASE_SCALE) as u32;
+        }
+
+        let mut reverb = Reverb {
+            sample_rate,
+            pre_delay,
+            diffusers,
+            lines,
+            lengths: [S::ZERO; LINES],
+            gains: [S::ZERO; LINES],
+            damping_states: [S::ZERO; LINES],
+            damping_coefficient: S::ZERO,
+            // Spread the oscillators' starting points round the cycle.
+            modulation_phases: [0, 1 << 30, 2 << 30, 3 << 30],
+            modulation_increments: increments,
+            modulation_depth: S::ZERO,
+            size: S::from_f64(0.7),
+            decay: S::TWO,
+            mix: S::from_f64(0.3),
+        };
+        reverb.set_damping(S::from_f64(6000.0));
+        reverb.set_modulation(S::HALF);
+        reverb.update_lengths();
+
+        Ok(reverb)
+    }
+
+    fn update_lengths(&mut self) {
+        let min_size = S::from_f64(MIN_SIZE);
+        let scale = (min_size + (S::ONE - min_size) * self.size) * self.sample_rate / S::from_f64(48000.0);
+        for (length, &full) in self.lengths.iter_mut().zip(LINE_LENGTHS.iter()) {
+            *length = S::from_f64(full as f64) * scale;
+        }
+        self.update_gains();
+    }
+
+    fn update_gains(&mut self) {
+        // Each pass round a line loses its share of 60 dB over the decay time.
+        for (gain, &length) in self.gains.iter_mut().zip(self.lengths.iter()) {
+            *gain = S::from_f64(10.0).powf(S::from_f64(-3.0) * length / (self.decay * self.sample_rate));
+        }
+    }
+
+    /// Sets the room size from 0.0 to 1.0, which scales the network's delays. Large jumps while
+    /// the tail is sounding can click.
+    pub fn set_size(&mut self, size: S) {
+        self.size = size.clamp(S::ZERO, S::ONE);
+        self.update_lengths();
+    }
+
+    /// Sets the time in seconds the tail takes to fall by 60 dB.
+    pub fn set_decay(&mut self, seconds: S) {
+        self.decay = seconds.max(S::from_f64(0.01));
+        self.update_gains();
+    }
+
+    /// Sets the cutoff in Hz of the low pass in each line, above which the tail dies away faster.
+    /// At half the sample rate or above there's no damping at all.
+    pub fn set_damping(&mut self, cutoff: S) {
+        self.damping_coefficient = if cutoff >= S::HALF * self.sample_rate {
+            S::ZERO
+        } else {
+            (-S::TWO_PI * cutoff.max(S::ZERO) / self.sample_rate).exp()
+        };
+    }
+
+    /// Sets the delay before the reverb starts, in seconds, up to `MAX_PRE_DELAY`.
+    pub fn set_pre_delay(&mut self, seconds: S) {
+        self.pre_delay.set_delay(seconds * self.sample_rate);
+    }
+
+    /// Sets how far the mixing between the lines swings, from 0.0 to 1.0. A little softens the
+    /// ringing of long tails, and a lot smears it into a gentle shimmer.
+    pub fn set_modulation(&mut self, depth: S) {
+        self.modulation_depth = depth.clamp(S::ZERO, S::ONE) * S::from_f64(MODULATION_DEPTH);
+    }
+
+    /// Sets the balance from dry (0.0) to only the reverb (1.0).
+    pub fn set_mix(&mut self, mix: S) {
+        self.mix = mix.clamp(S::ZERO, S::ONE);
+    }
+
+    pub fn reset(&mut self) {
+        self.pre_delay.reset();
+        self.diffusers.iter_mut().for_each(|d| d.reset());
+        self.lines.iter_mut().for_each(|l| l.reset());
+        self.damping_states = [S::ZERO; LINES];
+    }
+}
+
+/// A sine-like wave from a phase, built from two parabolas. Smooth enough for modulating delays,
+/// for a fraction of the cost of a sine.
+#[inline(always)]
+fn parabolic_sine<S: Sample>(phase: u32) -> S {
+    // -1 to 1 over the cycle.
+    let x = S::from_f64(phase as i32 as f64 * (2.0 / PHASE_SCALE));
+    S::from_f64(4.0) * x * (S::ONE - x.abs())
+}
+
+/// The sign of line `i`'s input and output taps.
+#[inline(always)]
+fn sign<S: Sample>(i: usize) -> S {
+    if i % 4 < 2 {
+        S::ONE
+    } else {
+        -S::ONE
+    }
+}
+
+impl<'a, S: Sample> StereoProcessor<S> for Reverb<'a, S> {
+    fn tick(&mut self, input: (S, S)) -> (S, S) {
+        let diffusion = S::from_f64(DIFFUSION);
+        let mut x = self.pre_delay.tick(S::HALF * (input.0 + input.1));
+        for diffuser in self.diffusers.iter_mut() {
+            let delayed = diffuser.read(diffuser.delay());
+            let w = x + diffusion * delayed;
+            diffuser.write(w);
+            x = delayed - diffusion * w;
+        }
+
+        // Read every line, damped and scaled for the decay.
+        let mut outputs = [S::ZERO; LINES];
+        let (mut left, mut right) = (S::ZERO, S::ZERO);
+        for (i, output) in outputs.iter_mut().enumerate() {
+            let delayed = self.lines[i].read(self.lengths[i]);
+            let state = &mut self.damping_states[i];
+            *state = delayed + self.damping_coefficient * (*state - delayed);
+            *output = self.gains[i] * *state;
+
+            // Alternating signs keep the input and outputs from lining up with the reflection.
+            if i % 2 == 0 {
+                left += sign::<S>(i) * *output;
+            } else {
+                right += sign::<S>(i) * *output;
+            }
+        }
+
+        // Rotating each pair of lines into each other by a slowly swinging angle keeps the mixing
+        // lossless but moves the network's resonances about, without the loss of high frequencies
+        // that reading a moving delay would cost on every pass.
+        // The sine and cosine come from the tangent of half the angle, which keeps the rotation
+        // exact however rough the oscillator.
+        let mut sum = S::ZERO;
+        let lfos = self.modulation_phases.iter_mut().zip(self.modulation_increments.iter());
+        for (pair, (phase, &increment)) in outputs.chunks_exact_mut(2).zip(lfos) {
+            let t = self.modulation_depth * parabolic_sine::<S>(*phase);
+            *phase = phase.wrapping_add(increment);
+
+            let scale = S::ONE / (S::ONE + t * t);
+            let (sine, cosine) = (S::TWO * t * scale, (S::ONE - t * t) * scale);
+            let (a, b) = (pair[0], pair[1]);
+            pair[0] = cosine * a - sine * b;
+            pair[1] = sine * a + cosine * b;
+            sum += pair[0] + pair[1];
+        }
+
+        // The Householder matrix reflects the lines' outputs through the all-ones vector, which
+        // mixes every line into every other losslessly for the cost of one sum.
+        let reflection = sum * S::from_f64(2.0 / LINES as f64);
+        for (i, line) in self.lines.iter_mut().enumerate() {
+            line.write(outputs[i] - reflection + sign::<S>(i) * x);
+        }
+
+        let dry = S::ONE - self.mix;
+        let wet = self.mix * S::from_f64(OUTPUT_GAIN);
+        (dry * input.0 + wet * left, dry * input.1 + wet * right)
+    }
+}
@@ -0,0 +1,251 @@
+//! The floating-point types DSP code can be generic over, so the same code runs in f32 on the
+//! firmware and in f64 for reference renders on the host.
+
+use core::fmt::Debug;
+use core::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign};
+
+use micromath::F32Ext;
+
+/// A floating-point sample, with the constants and maths DSP code needs.
+///
+/// The f32 sine and cosine use micromath's fast approximations, which are what the firmware runs
+/// for waveforms every sample. Everything else, which goes into coefficients and curves where the
+/// approximations' errors would be heard, uses libm's accurate implementations, as f64 does
+/// throughout. Neither depends on std, so results are the same whether or not std is linked.
+pub trait Sample:
+    Copy
+    + Default
+    + Debug
+    + PartialOrd
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Div<Output = Self>
+    + Neg<Output = Self>
+    + AddAssign
+    + SubAssign
+    + MulAssign
+    + DivAssign
+{
+    const ZERO: Self;
+    const HALF: Self;
+    const ONE: Self;
+    const TWO: Self;
+    const PI: Self;
+    const TWO_PI: Self;
+    const INV_TWO_PI: Self;
+
+    fn from_f64(x: f64) -> Self;
+    fn to_f64(self) -> f64;
+
+    /// Converts a wrapping 32-bit phase to a fraction of a cycle in [0, 1). Keeps as many bits as
+    /// the type holds exactly, so the result never rounds up to 1.0.
+    fn from_phase(phase: u32) -> Self;
+
+    fn abs(self) -> Self;
+    fn floor(self) -> Self;
+    fn fract(self) -> Self;
+    fn sqrt(self) -> Self;
+    fn sin(self) -> Self;
+    fn cos(self) -> Self;
+    fn tan(self) -> Self;
+    fn exp(self) -> Self;
+    fn exp2(self) -> Self;
+    fn ln(self) -> Self;
+    /// ln(1 + self), accurate for small values.
+    fn log1p(self) -> Self;
+    fn powf(self, exponent: Self) -> Self;
+    fn tanh(self) -> Self;
+
+    fn min(self, other: Self) -> Self {
+        if other < self { other } else { self }
+    }
+
+    fn max(self, other: Self) -> Self {
+        if other > self { other } else { self }
+    }
+
+    fn clamp(self, min: Self, max: Self) -> Self {
+        self.max(min).min(max)
+    }
+}
+
+impl Sample for f32 {
+    const ZERO: f32 = 0.0;
+    const HALF: f32 = 0.5;
+    const ONE: f32 = 1.0;
+    const TWO: f32 = 2.0;
+    const PI: f32 = core::f32::consts::PI;
+    const TWO_PI: f32 = core::f32::consts::PI * 2.0;
+    const INV_TWO_PI: f32 = 1.0 / (core::f32::consts::PI * 2.0);
+
+    #[inline(always)]
+    fn from_f64(x: f64) -> f32 {
+        x as f32
+    }
+
+    #[inline(always)]
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+
+    #[inline(always)]
+    fn from_phase(phase: u32) -> f32 {
+        (phase >> 8) as f32 * (1.0 / 16_777_216.0)
+    }
+
+    #[inline(always)]
+    fn abs(self) -> f32 {
+        F32Ext::abs(self)
+    }
+
+    #[inline(always)]
+    fn floor(self) -> f32 {
+        F32Ext::floor(self)
+    }
+
+    #[inline(always)]
+    fn fract(self) -> f32 {
+        F32Ext::fract(self)
+    }
+
+    #[inline(always)]
+    fn sqrt(self) -> f32 {
+        libm::sqrtf(self)
+    }
+
+    #[inline(always)]
+    fn sin(self) -> f32 {
+        F32Ext::sin(self)
+    }
+
+    #[inline(always)]
+    fn cos(self) -> f32 {
+        F32Ext::cos(self)
+    }
+
+    #[inline(always)]
+    fn tan(self) -> f32 {
+        libm::tanf(self)
+    }
+
+    #[inline(always)]
+    fn exp(self) -> f32 {
+        libm::expf(self)
+    }
+
+    #[inline(always)]
+    fn exp2(self) -> f32 {
+        libm::exp2f(self)
+    }
+
+    #[inline(always)]
+    fn ln(self) -> f32 {
+        libm::logf(self)
+    }
+
+    #[inline(always)]
+    fn log1p(self) -> f32 {
+        libm::log1pf(self)
+    }
+
+    #[inline(always)]
+    fn powf(self, exponent: f32) -> f32 {
+        libm::powf(self, exponent)
+    }
+
+    #[inline(always)]
+    fn tanh(self) -> f32 {
+        libm::tanhf(self)
+    }
+}
+
+impl Sample for f64 {
+    const ZERO: f64 = 0.0;
+    const HALF: f64 = 0.5;
+    const ONE: f64 = 1.0;
+    const TWO: f64 = 2.0;
+    const PI: f64 = core::f64::consts::PI;
+    const TWO_PI: f64 = core::f64::consts::PI * 2.0;
+    const INV_TWO_PI: f64 = 1.0 / (core::f64::consts::PI * 2.0);
+
+    #[inline(always)]
+    fn from_f64(x: f64) -> f64 {
+        x
+    }
+
+    #[inline(always)]
+    fn to_f64(self) -> f64 {
+        self
+    }
+
+    #[inline(always)]
+    fn from_phase(phase: u32) -> f64 {
+        phase as f64 * (1.0 / 4_294_967_296.0)
+    }
+
+    #[inline(always)]
+    fn abs(self) -> f64 {
+        libm::fabs(self)
+    }
+
+    #[inline(always)]
+    fn floor(self) -> f64 {
+        libm::floor(self)
+    }
+
+    #[inline(always)]
+    fn fract(self) -> f64 {
+        self - libm::trunc(self)
+    }
+
+    #[inline(always)]
+    fn sqrt(self) -> f64 {
+        libm::sqrt(self)
+    }
+
+    #[inline(always)]
+    fn sin(self) -> f64 {
+        libm::sin(self)
+    }
+
+    #[inline(always)]
+    fn cos(self) -> f64 {
+        libm::cos(self)
+    }
+
+    #[inline(always)]
+    fn tan(self) -> f64 {
+        libm::tan(self)
+    }
+
+    #[inline(always)]
+    fn exp(self) -> f64 {
+        libm::exp(self)
+    }
+
+    #[inline(always)]
+    fn exp2(self) -> f64 {
+        libm::exp2(self)
+    }
+
+    #[inline(always)]
+    fn ln(self) -> f64 {
+        libm::log(self)
+    }
+
+    #[inline(always)]
+    fn log1p(self) -> f64 {
+        libm::log1p(self)
+    }
+
+    #[inline(always)]
+    fn powf(self, exponent: f64) -> f64 {
+        libm::pow(self, exponent)
+    }
+
+    #[inline(always)]
+    fn tanh(self) -> f64 {
+        libm::tanh(self)
+    }
+}
@@ -1,9 +1,101 @@
-use super::SampleType;
+//! The generator and processor traits. Each is generic over the sample type, defaulting to the
+//! f32 the firmware runs.
 
-pub trait MonoGenerator {
-    fn tick(&mut self) -> SampleType;
+use super::sample::Sample;
+use super::chain::{Chain, Series};
+
+pub trait MonoGenerator<S: Sample = f32> {
+    fn tick(&mut self) -> S;
+
+    /// Fills a block with consecutive samples.
+    fn fill(&mut self, buffer: &mut [S]) {
+        for x in buffer.iter_mut() {
+            *x = self.tick();
+        }
+    }
+
+    /// Fills both channels of a block of stereo frames with the same samples.
+    fn fill_frames(&mut self, frames: &mut [(S, S)]) {
+        for frame in frames.iter_mut() {
+            let x = self.tick();
+            *frame = (x, x);
+        }
+    }
+
+    /// Feeds this generator's output into `processor`.
+    fn then<P: MonoProcessor<S>>(self, processor: P) -> Chain<Self, P>
+    where
+        Self: Sized,
+    {
+        Chain::new(self, processor)
+    }
+}
+
+pub trait StereoGenerator<S: Sample = f32> {
+    fn tick(&mut self) -> (S, S);
+
+    /// Fills a block of each channel with consecutive samples. Only the common length of both
+    /// channels is filled.
+    fn fill(&mut self, left: &mut [S], right: &mut [S]) {
+        for (l, r) in left.iter_mut().zip(right.iter_mut()) {
+            let (out_l, out_r) = self.tick();
+            *l = out_l;
+            *r = out_r;
+        }
+    }
+
+    /// Fills a block of stereo frames, laid out as libdaisy's audio buffers are.
+    fn fill_frames(&mut self, frames: &mut [(S, S)]) {
+        for frame in frames.iter_mut() {
+            *frame = self.tick();
+        }
+    }
+
+    /// Feeds this generator's output into `processor`.
+    fn then<P: StereoProcessor<S>>(self, processor: P) -> Chain<Self, P>
+    where
+        Self: Sized,
+    {
+        Chain::new(self, processor)
+    }
+}
+
+pub trait MonoProcessor<S: Sample = f32> {
+    fn tick(&mut self, input: S) -> S;
+
+    /// Processes a block of samples in place.
+    fn process(&mut self, buffer: &mut [S]) {
+        for x in buffer.iter_mut() {
+            *x = self.tick(*x);
+        }
+    }
+
+    /// Feeds this processor's output into `next`.
+    fn then<P: MonoProcessor<S>>(self, next: P) -> Series<Self, P>
+    where
+        Self: Sized,
+    {
+        Series::new(self, next)
+    }
 }
 
-pub trait StereoGenerator {
-    fn tick(&mut self) -> (SampleType, SampleType);
-}
\ No newline at end of file
+pub trait StereoProcessor<S: Sample = f32> {
+    fn tick(&mut self, input: (S, S)) -> (S, S);
+
+    /// Processes a block of samples in place. Only the common length of both channels is processed.
+    fn process(&mut self, left: &mut [S], right: &mut [S]) {
+        for (l, r) in left.iter_mut().zip(right.iter_mut()) {
+            let (out_l, out_r) = self.tick((*l, *r));
+            *l = out_l;
+            *r = out_r;
+        }
+    }
+
+    /// Feeds this processor's output into `next`.
+    fn then<P: StereoProcessor<S>>(self, next: P) -> Series<Self, P>
+    where
+        Self: Sized,
+    {
+        Series::new(self, next)
+    }
+}
@@ -0,0 +1,397 @@
+use super::SampleType;
+use super::envelopes::Adsr;
+use super::filters::StateVariableFilter;
+use super::oscillators::{Oscillator, OscillatorMode};
+use super::traits::{MonoGenerator, MonoProcessor};
+
+/// Anything the `VoiceManager` can play notes on.
+pub trait Voice {
+    fn note_on(&mut self, note: u8, velocity: u8);
+    fn note_off(&mut self);
+    /// Whether the voice is still producing sound, including its release.
+    fn is_active(&self) -> bool;
+    /// The voice's current loudness, used to pick a voice when stealing the quietest.
+    fn level(&self) -> SampleType;
+}
+
+/// Which held note a monophonic voice plays.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum NotePriority {
+    Last,
+    Lowest,
+    Highest,
+}
+
+/// Which voice is taken for a new note when every voice is busy.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum StealMode {
+    Oldest,
+    Quietest,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum VoiceMode {
+    Poly(StealMode),
+    Mono(NotePriority),
+}
+
+/// How many held notes the monophonic note stack remembers.
+const MAX_HELD_NOTES: usize = 16;
+
+/// Block size the voices are mixed in by `VoiceManager::fill`.
+const MIX_BLOCK_SIZE: usize = 64;
+
+#[derive(Clone, Copy)]
+struct VoiceSlot {
+    note: Option<u8>,
+    /// Whether the note's key is still down.
+    held: bool,
+    /// Whether the key was released while the sustain pedal was down.
+    sustained: bool,
+    /// When the voice was last given a note, for oldest-first stealing.
+    started: u32,
+}
+
+impl VoiceSlot {
+    const EMPTY: VoiceSlot = VoiceSlot {
+        note: None,
+        held: false,
+        sustained: false,
+        started: 0,
+    };
+}
+
+/// Assigns notes to a fixed set of voices.
+///
+/// In `Poly` mode each note gets its own voice, stealing one when they're all busy. In `Mono` mode
+/// only the first voice is used, and it follows the held note chosen by the note priority.
+pub struct VoiceManager<V: Voice, const N: usize> {
+    voices: [V; N],
+    slots: [VoiceSlot; N],
+    mode: VoiceMode,
+    sustain: bool,
+    clock: u32,
+    held_notes: [(u8, u8); MAX_HELD_NOTES],
+    held_count: usize,
+}
+
+impl<V: Voice, const N: usize> VoiceManager<V, N> {
+    /// Fails the build for a manager with no voices, which would have nothing to steal.
+    const HAS_VOICES: () = assert!(N > 0, "a VoiceManager needs at least one voice");
+
+    pub fn new(voices: [V; N], mode: VoiceMode) -> VoiceManager<V, N> {
+        let () = Self::HAS_VOICES;
+
+        VoiceManager {
+            voices,
+            slots: [VoiceSlot::EMPTY; N],
+            mode,
+            sustain: false,
+            clock: 0,
+            held_notes: [(0, 0); MAX_HELD_NOTES],
+            held_count: 0,
+        }
+    }
+
+    pub fn voices(&self) -> &[V; N] {
+        &self.voices
+    }
+
+    pub fn voices_mut(&mut self) -> &mut [V; N] {
+        &mut self.voices
+    }
+
+    pub fn mode(&self) -> VoiceMode {
+        self.mode
+    }
+
+    /// Changes the voice mode, releasing every sounding note.
+    pub fn set_mode(&mut self, mode: VoiceMode) {
+        self.all_notes_off();
+        self.mode = mode;
+    }
+
+    /// The note each voice is assigned, if any.
+    pub fn note_of(&self, voice: usize) -> Option<u8> {
+        self.slots[voice].note
+    }
+
+    pub fn note_on(&mut self, note: u8, velocity: u8) {
+        self.clock = self.clock.wrapping_add(1);
+
+        match self.mode {
+            VoiceMode::Poly(steal_mode) => self.poly_note_on(note, velocity, steal_mode),
+            VoiceMode::Mono(priority) => {
+                self.remove_held(note);
+                if self.held_count == MAX_HELD_NOTES {
+                    self.held_notes.copy_within(1.., 0);
+                    self.held_count -= 1;
+                }
+                self.held_notes[self.held_count] = (note, velocity);
+                self.held_count += 1;
+
+                self.mono_update(priority);
+            }
+        }
+    }
+
+    pub fn note_off(&mut self, note: u8) {
+        match self.mode {
+            VoiceMode::Poly(_) => {
+                for i in 0..N {
+                    let slot = &mut self.slots[i];
+                    if slot.note == Some(note) && slot.held {
+                        slot.held = false;
+                        if self.sustain {
+                            slot.sustained = true;
+                        } else {
+                            self.voices[i].note_off();
+                        }
+                    }
+                }
+            }
+            VoiceMode::Mono(priority) => {
+                self.remove_held(note);
+                self.mono_update(priority);
+            }
+        }
+    }
+
+    /// Sets the sustain pedal. While it's down, released notes keep sounding until it's lifted.
+    pub fn set_sustain(&mut self, sustain: bool) {
+        self.sustain = sustain;
+        if sustain {
+            return;
+        }
+
+        match self.mode {
+            VoiceMode::Poly(_) => {
+                for i in 0..N {
+                    if self.slots[i].sustained {
+                        self.slots[i].sustained = false;
+                        self.voices[i].note_off();
+                    }
+                }
+            }
+            VoiceMode::Mono(priority) => self.mono_update(priority),
+        }
+    }
+
+    /// Releases every voice and forgets all held notes, ignoring the sustain pedal.
+    pub fn all_notes_off(&mut self) {
+        for (voice, slot) in self.voices.iter_mut().zip(self.slots.iter_mut()) {
+            if slot.held || slot.sustained {
+                voice.note_off();
+            }
+            slot.held = false;
+            slot.sustained = false;
+        }
+        self.held_count = 0;
+    }
+
+    fn poly_note_on(&mut self, note: u8, velocity: u8, steal_mode: StealMode) {
+        // A repeated note reuses its voice rather than stacking a second one.
+        let index = match self.slots.iter().position(|s| s.note == Some(note) && (s.held || s.sustained)) {
+            Some(index) => index,
+            None => self.free_voice().unwrap_or_else(|| self.steal_voice(steal_mode)),
+        };
+
+        self.slots[index] = VoiceSlot {
+            note: Some(note),
+            held: true,
+            sustained: false,
+            started: self.clock,
+        };
+        self.voices[index].note_on(note, velocity);
+    }
+
+    /// The voice that has been silent the longest, if any are silent.
+    fn free_voice(&self) -> Option<usize> {
+        (0..N)
+            .filter(|&i| !self.voices[i].is_active() && !self.slots[i].held && !self.slots[i].sustained)
+            .min_by_key(|&i| self.age_key(i))
+    }
+
+    fn steal_voice(&self, steal_mode: StealMode) -> usize {
+        // Voices whose keys are up are always taken before ones still being played.
+        let any_released = self.slots.iter().any(|s| !s.held);
+        let candidates = (0..N).filter(|&i| !any_released || !self.slots[i].held);
+
+        // There's always a candidate, as N > 0 and the filter only narrows to released voices
+        // when there are some.
+        match steal_mode {
+            StealMode::Oldest => candidates.min_by_key(|&i| self.age_key(i)),
+            StealMode::Quietest => candidates.min_by(|&a, &b| {
+                self.voices[a]
+                    .level()
+                    .partial_cmp(&self.voices[b].level())
+                    .unwrap_or(core::cmp::Ordering::Equal)
+            }),
+        }
+        .unwrap_or(0)
+    }
+
+    /// Orders voices by how long ago they were started, coping with the clock wrapping.
+    fn age_key(&self, voice: usize) -> core::cmp::Reverse<u32> {
+        core::cmp::Reverse(self.clock.wrapping_sub(self.slots[voice].started))
+    }
+
+    fn remove_held(&mut self, note: u8) {
+        if let Some(i) = self.held_notes[..self.held_count].iter().position(|&(n, _)| n == note) {
+            self.held_notes.copy_within(i + 1..self.held_count, i);
+            self.held_count -= 1;
+        }
+    }
+
+    /// Points the mono voice at the highest-priority held note, or releases it when no notes are
+    /// held and the sustain pedal is up.
+    fn mono_update(&mut self, priority: NotePriority) {
+        let held = &self.held_notes[..self.held_count];
+        let target = match priority {
+            NotePriority::Last => held.last(),
+            NotePriority::Lowest => held.iter().min_by_key(|&&(n, _)| n),
+            NotePriority::Highest => held.iter().max_by_key(|&&(n, _)| n),
+        };
+
+        let slot = &mut self.slots[0];
+        match target {
+            Some(&(note, velocity)) => {
+                if slot.note != Some(note) || !slot.held {
+                    *slot = VoiceSlot {
+                        note: Some(note),
+                        held: true,
+                        sustained: false,
+                        started: self.clock,
+                    };
+                    self.voices[0].note_on(note, velocity);
+                }
+                slot.held = true;
+                slot.sustained = false;
+            }
+            None => {
+                if slot.held {
+                    slot.held = false;
+                    slot.sustained = self.sustain;
+                    if !self.sustain {
+                        self.voices[0].note_off();
+                    }
+                } else if slot.sustained && !self.sustain {
+                    slot.sustained = false;
+                    self.voices[0].note_off();
+                }
+            }
+        }
+    }
+}
+
+impl<V: Voice + MonoGenerator, const N: usize> MonoGenerator for VoiceManager<V, N> {
+    fn tick(&mut self) -> SampleType {
+        let mut sum = 0.0;
+        for voice in self.voices.iter_mut() {
+            if voice.is_active() {
+                sum += voice.tick();
+            }
+        }
+
+        sum
+    }
+
+    fn fill(&mut self, buffer: &mut [SampleType]) {
+        let mut voice_block = [0.0; MIX_BLOCK_SIZE];
+
+        for block in buffer.chunks_mut(MIX_BLOCK_SIZE) {
+            block.iter_mut().for_each(|x| *x = 0.0);
+            let voice_block = &mut voice_block[..block.len()];
+
+            for voice in self.voices.iter_mut().filter(|v| v.is_active()) {
+                voice.fill(voice_block);
+                for (x, v) in block.iter_mut().zip(voice_block.iter()) {
+                    *x += *v;
+                }
+            }
+        }
+    }
+}
+
+/// Converts a MIDI note number to a frequency in Hz, with A4 (note 69) at 440 Hz.
+pub fn midi_note_to_frequency(note: u8) -> SampleType {
+    // Equal-tempered semitones from C-1 (note 0), so that only octave shifts are computed.
+    const C_MINUS_1: [SampleType; 12] = [
+        8.175_799, 8.661_957, 9.177_024, 9.722_718, 10.300_861, 10.913_382,
+        11.562_326, 12.249_857, 12.978_272, 13.75, 14.567_618, 15.433_853,
+    ];
+
+    C_MINUS_1[(note % 12) as usize] * (1u32 << (note / 12)) as SampleType
+}
+
+/// A basic subtractive voice: an oscillator through a state-variable filter, shaped by an ADSR.
+pub struct SubtractiveVoice {
+    oscillator: Oscillator,
+    filter: StateVariableFilter,
+    envelope: Adsr,
+    velocity: SampleType,
+}
+
+impl SubtractiveVoice {
+    pub fn new(sample_rate: SampleType) -> SubtractiveVoice {
+        SubtractiveVoice {
+            oscillator: Oscillator::new(OscillatorMode::Saw, 440.0, sample_rate),
+            filter: StateVariableFilter::new(2000.0, 0.3, sample_rate),
+            envelope: Adsr::new(0.005, 0.2, 0.7, 0.3, sample_rate),
+            velocity: 0.0,
+        }
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: SampleType) {
+        self.oscillator.set_sample_rate(sample_rate);
+        self.filter.set_sample_rate(sample_rate);
+        self.envelope.set_sample_rate(sample_rate);
+    }
+
+    pub fn oscillator_mut(&mut self) -> &mut Oscillator {
+        &mut self.oscillator
+    }
+
+    pub fn filter_mut(&mut self) -> &mut StateVariableFilter {
+        &mut self.filter
+    }
+
+    pub fn envelope_mut(&mut self) -> &mut Adsr {
+        &mut self.envelope
+    }
+}
+
+impl Voice for SubtractiveVoice {
+    fn note_on(&mut self, note: u8, velocity: u8) {
+        self.oscillator.set_frequency(midi_note_to_frequency(note));
+        self.velocity = velocity as SampleType / 127.0;
+        self.envelope.gate_on();
+    }
+
+    fn note_off(&mut self) {
+        self.envelope.gate_off();
+    }
+
+    fn is_active(&self) -> bool {
+        self.envelope.is_active()
+    }
+
+    fn level(&self) -> SampleType {
+        self.envelope.level() * self.velocity
+    }
+}
+
+impl MonoGenerator for SubtractiveVoice {
+    fn tick(&mut self) -> SampleType {
+        let x = self.filter.tick(self.oscillator.tick());
+        x * self.envelope.tick() * self.velocity
+    }
+
+    fn fill(&mut self, buffer: &mut [SampleType]) {
+        self.oscillator.fill(buffer);
+        self.filter.process(buffer);
+        for x in buffer.iter_mut() {
+            *x *= self.envelope.tick() * self.velocity;
+        }
+    }
+}
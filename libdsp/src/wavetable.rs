@@ -0,0 +1,327 @@
+use super::sample::Sample;
+use super::traits::MonoGenerator;
+
+/// 2^32, the number of phase steps in a cycle.
+const PHASE_SCALE: f64 = 4_294_967_296.0;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum WavetableError {
+    /// The table size is not a power of two, or is smaller than 4 samples.
+    InvalidTableSize,
+    /// The source is empty or is not a whole number of frames long.
+    InvalidSource,
+    /// The storage buffer is smaller than `Wavetable::storage_len`.
+    StorageTooSmall,
+    /// The scratch buffer is smaller than `Wavetable::scratch_len`.
+    ScratchTooSmall,
+}
+
+/// A set of single-cycle frames, each band-limited into one table per octave.
+///
+/// The tables live in a caller-supplied buffer so they can be placed in external memory.
+/// Storage is laid out frame by frame, and within a frame from the full-bandwidth table (level 0)
+/// up to the pure sine (last level).
+pub struct Wavetable<'a, S: Sample = f32> {
+    data: &'a [S],
+    table_size: usize,
+    frames: usize,
+    levels: usize,
+}
+
+impl<'a, S: Sample> Wavetable<'a, S> {
+    /// Number of mip levels for a table size: level `n` keeps harmonics up to `table_size / 2^(n+1)`.
+    pub fn levels_for(table_size: usize) -> usize {
+        table_size.trailing_zeros() as usize
+    }
+
+    /// Length of the storage buffer needed to build `frames` frames of `table_size` samples.
+    pub fn storage_len(table_size: usize, frames: usize) -> usize {
+        table_size * frames * Self::levels_for(table_size)
+    }
+
+    /// Length of the scratch buffer needed while building tables of `table_size` samples.
+    pub fn scratch_len(table_size: usize) -> usize {
+        table_size * 4
+    }
+
+    /// Builds the mip-mapped tables for `source`, which holds one or more frames of `table_size`
+    /// samples back to back.
+    pub fn build(
+        source: &[S],
+        table_size: usize,
+        storage: &'a mut [S],
+        scratch: &mut [S],
+    ) -> Result<Wavetable<'a, S>, WavetableError> {
+        if table_size < 4 || !table_size.is_power_of_two() {
+            return Err(WavetableError::InvalidTableSize);
+        }
+        if source.is_empty() || !source.len().is_multiple_of(table_size) {
+            return Err(WavetableError::InvalidSource);
+        }
+
+        let frames = source.len() / table_size;
+        let levels = Self::levels_for(table_size);
+        if storage.len() < Self::storage_len(table_size, frames) {
+            return Err(WavetableError::StorageTooSmall);
+        }
+        if scratch.len() < Self::scratch_len(table_size) {
+            return Err(WavetableError::ScratchTooSmall);
+        }
+
+        let (spectrum, work) = scratch.split_at_mut(table_size * 2);
+        let (spec_re, spec_im) = spectrum.split_at_mut(table_size);
+        let (work_re, work_im) = work.split_at_mut(table_size);
+        let work_im = &mut work_im[..table_size];
+
+        for (frame, samples) in source.chunks_exact(table_size).enumerate() {
+            spec_re.copy_from_slice(samples);
+            spec_im.iter_mut().for_each(|x| *x = S::ZERO);
+            fft(spec_re, spec_im, false);
+
+            for level in 0..levels {
+                let max_harmonic = (table_size / 2) >> level;
+
+                work_re.copy_from_slice(spec_re);
+                work_im.copy_from_slice(spec_im);
+                // Keep DC and harmonics 1..=max_harmonic (and their mirror images), but never the
+                // Nyquist bin, which can't be represented unambiguously.
+                for bin in 1..table_size {
+                    let harmonic = if bin <= table_size / 2 { bin } else { table_size - bin };
+                    if harmonic > max_harmonic || harmonic == table_size / 2 {
+                        work_re[bin] = S::ZERO;
+                        work_im[bin] = S::ZERO;
+                    }
+                }
+                fft(work_re, work_im, true);
+
+                let offset = (frame * levels + level) * table_size;
+                storage[offset..offset + table_size].copy_from_slice(work_re);
+            }
+        }
+
+        Ok(Wavetable {
+            data: storage,
+            table_size,
+            frames,
+            levels,
+        })
+    }
+
+    pub fn table_size(&self) -> usize {
+        self.table_size
+    }
+
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    pub fn levels(&self) -> usize {
+        self.levels
+    }
+
+    /// The band-limited table for a frame at a mip level.
+    pub fn table(&self, frame: usize, level: usize) -> &[S] {
+        let offset = (frame * self.levels + level) * self.table_size;
+        &self.data[offset..offset + self.table_size]
+    }
+
+    /// Picks the mip level whose highest harmonic stays below Nyquist for a phase increment given
+    /// in cycles per sample.
+    pub fn level_for_increment(&self, increment: S) -> usize {
+        let harmonics_per_nyquist = self.table_size as f64 * increment.abs().to_f64();
+        let mut level = 0;
+        while level < self.levels - 1 && ((1usize << level) as f64) < harmonics_per_nyquist {
+            level += 1;
+        }
+
+        level
+    }
+
+    /// The mip level for an increment, as `level_for_increment`, and how far to fade from it
+    /// towards the next level up. The fade follows the fractional octave within the level's range,
+    /// so sweeping the frequency changes the bandwidth smoothly instead of in octave steps.
+    pub fn crossfade_for_increment(&self, increment: S) -> (usize, S) {
+        let level = self.level_for_increment(increment);
+        if level + 1 >= self.levels {
+            return (level, S::ZERO);
+        }
+
+        // A level is chosen from one octave above the previous level's limit up to its own.
+        let harmonics_per_nyquist = self.table_size as f64 * increment.abs().to_f64();
+        let octave = libm::log2(harmonics_per_nyquist) - (level as f64 - 1.0);
+        (level, S::from_f64(octave.clamp(0.0, 1.0)))
+    }
+
+    /// Reads a table at a 32-bit phase: the top bits pick the sample and the rest interpolate.
+    fn lookup(&self, frame: usize, level: usize, phase: u32) -> S {
+        let table = self.table(frame, level);
+        let index = (phase >> (32 - self.levels)) as usize;
+        let frac = S::from_phase(phase << self.levels);
+
+        let a = table[index];
+        let b = table[(index + 1) & (self.table_size - 1)];
+        a + (b - a) * frac
+    }
+}
+
+/// Plays a `Wavetable`, morphing between its frames. Like `GenericOscillator`, the phase is a
+/// wrapping 32-bit accumulator, so it can't drift over long notes.
+pub struct WavetableOscillator<'a, S: Sample = f32> {
+    table: Wavetable<'a, S>,
+    sample_rate: S,
+    frequency: S,
+    phase: u32,
+    phase_increment: u32,
+    position: S,
+    level: usize,
+    fade: S,
+}
+
+impl<'a, S: Sample> WavetableOscillator<'a, S> {
+    pub fn new(table: Wavetable<'a, S>, frequency: S, sample_rate: S) -> WavetableOscillator<'a, S> {
+        let mut osc = WavetableOscillator {
+            table,
+            sample_rate,
+            frequency,
+            phase: 0,
+            phase_increment: 0,
+            position: S::ZERO,
+            level: 0,
+            fade: S::ZERO,
+        };
+        osc.update_phase_increment();
+
+        osc
+    }
+
+    fn update_phase_increment(&mut self) {
+        let cycles = self.frequency.to_f64() / self.sample_rate.to_f64();
+        // Negative frequencies wrap to increments that run the phase backwards.
+        self.phase_increment = libm::round(cycles * PHASE_SCALE) as i64 as u32;
+        let (level, fade) = self.table.crossfade_for_increment(S::from_f64(cycles));
+        self.level = level;
+        self.fade = fade;
+    }
+
+    pub fn set_frequency(&mut self, frequency: S) {
+        self.frequency = frequency;
+        self.update_phase_increment();
+    }
+
+    pub fn set_sample_rate(&mut self, sample_rate: S) {
+        self.sample_rate = sample_rate;
+        self.update_phase_increment();
+    }
+
+    /// Sets the morph position across the frames, from 0.0 (first frame) to 1.0 (last frame).
+    pub fn set_position(&mut self, position: S) {
+        self.position = position.max(S::ZERO).min(S::ONE);
+    }
+
+    pub fn reset_phase(&mut self) {
+        self.phase = 0;
+    }
+
+    pub fn table(&self) -> &Wavetable<'a, S> {
+        &self.table
+    }
+
+    fn lookup_level(&self, frame: usize, morph: S, level: usize) -> S {
+        let x = self.table.lookup(frame, level, self.phase);
+        if morph > S::ZERO {
+            let y = self.table.lookup(frame + 1, level, self.phase);
+            x + (y - x) * morph
+        } else {
+            x
+        }
+    }
+}
+
+impl<'a, S: Sample> MonoGenerator<S> for WavetableOscillator<'a, S> {
+    fn tick(&mut self) -> S {
+        let frame_position = self.position * S::from_f64((self.table.frames() - 1) as f64);
+        let frame = frame_position.floor().to_f64() as usize;
+        let morph = frame_position - S::from_f64(frame as f64);
+
+        let mut x = self.lookup_level(frame, morph, self.level);
+        if self.fade > S::ZERO {
+            let y = self.lookup_level(frame, morph, self.level + 1);
+            x += (y - x) * self.fade;
+        }
+
+        self.phase = self.phase.wrapping_add(self.phase_increment);
+
+        x
+    }
+}
+
+/// Computes the sine and cosine of `angle` in double precision, for building twiddle factors
+/// without relying on the approximate single-precision maths used in the audio path.
+fn sin_cos(angle: f64) -> (f64, f64) {
+    let x2 = angle * angle;
+    let mut sin = 0.0;
+    let mut cos = 0.0;
+    let mut sin_term = angle;
+    let mut cos_term = 1.0;
+    for n in 0..20 {
+        sin += sin_term;
+        cos += cos_term;
+        let k = (2 * n + 2) as f64;
+        sin_term *= -x2 / (k * (k + 1.0));
+        cos_term *= -x2 / ((k - 1.0) * k);
+    }
+
+    (sin, cos)
+}
+
+/// In-place iterative radix-2 FFT. The inverse transform is scaled by `1 / len`.
+fn fft<S: Sample>(re: &mut [S], im: &mut [S], inverse: bool) {
+    let len = re.len();
+
+    let mut j = 0;
+    for i in 1..len {
+        let mut bit = len >> 1;
+        while j & bit != 0 {
+            j ^= bit;
+            bit >>= 1;
+        }
+        j |= bit;
+        if i < j {
+            re.swap(i, j);
+            im.swap(i, j);
+        }
+    }
+
+    let sign = if inverse { 1.0 } else { -1.0 };
+    let mut size = 2;
+    while size <= len {
+        let (step_sin, step_cos) = sin_cos(sign * 2.0 * core::f64::consts::PI / size as f64);
+        for start in (0..len).step_by(size) {
+            let (mut w_re, mut w_im) = (1.0f64, 0.0f64);
+            for k in 0..size / 2 {
+                let a = start + k;
+                let b = a + size / 2;
+                let (twiddle_re, twiddle_im) = (S::from_f64(w_re), S::from_f64(w_im));
+                let t_re = re[b] * twiddle_re - im[b] * twiddle_im;
+                let t_im = re[b] * twiddle_im + im[b] * twiddle_re;
+                re[b] = re[a] - t_re;
+                im[b] = im[a] - t_im;
+                re[a] += t_re;
+                im[a] += t_im;
+
+                let next_re = w_re * step_cos - w_im * step_sin;
+                w_im = w_re * step_sin + w_im * step_cos;
+                w_re = next_re;
+            }
+        }
+        size <<= 1;
+    }
+
+    if inverse {
+        let scale = S::ONE / S::from_f64(len as f64);
+        for (r, i) in re.iter_mut().zip(im.iter_mut()) {
+            *r *= scale;
+            *i *= scale;
+        }
+    }
+}
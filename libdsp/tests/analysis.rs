@@ -0,0 +1,276 @@
+//! Checks that the PolyBLEP oscillator paths, antialiased distortion and oversampled ladder alias
+//! measurably less than the naive ones, and that the noise generators have the colours they claim.
+//! Run with `cargo test --features analysis`.
+
+use libdsp::analysis::{analyze, analyze_oscillator, spectral_slope, OscillatorPath, SpectralReport, ANALYSIS_LENGTH};
+use libdsp::distortion::{Antialiasing, Distortion, DistortionKind};
+use libdsp::filters::LadderFilter;
+use libdsp::noise::{BrownNoise, PinkNoise, WhiteNoise};
+use libdsp::oscillators::{HardSync, Oscillator, OscillatorMode};
+use libdsp::traits::{MonoGenerator, MonoProcessor};
+
+const SAMPLE_RATE: f32 = 48000.0;
+
+// Off-bin fundamentals, so aliases don't land on top of harmonics.
+const FREQUENCIES: [f32; 3] = [220.5, 1234.5, 3001.0];
+
+fn compare(name: &str, mode: OscillatorMode, frequency: f32) -> (SpectralReport, SpectralReport) {
+    let naive = analyze_oscillator(mode, OscillatorPath::Naive, frequency, SAMPLE_RATE);
+    let blep = analyze_oscillator(mode, OscillatorPath::PolyBlep, frequency, SAMPLE_RATE);
+    println!("{} {} Hz\n  naive     {:?}\n  polyblep  {:?}", name, frequency, naive, blep);
+
+    (naive, blep)
+}
+
+/// Asserts the PolyBLEP path beats the naive one by at least the given margins, in dB.
+fn assert_improvement(name: &str, mode: OscillatorMode, min_alias_reduction: f64, min_hnr_gain: f64) {
+    for &frequency in FREQUENCIES.iter() {
+        let (naive, blep) = compare(name, mode, frequency);
+
+        let alias_reduction = naive.alias_below_fundamental_db - blep.alias_below_fundamental_db;
+        assert!(
+            alias_reduction >= min_alias_reduction,
+            "{} {} Hz: aliasing below the fundamental only {:.1} dB lower",
+            name,
+            frequency,
+            alias_reduction
+        );
+
+        let hnr_gain = blep.harmonic_to_noise_db - naive.harmonic_to_noise_db;
+        assert!(
+            hnr_gain >= min_hnr_gain,
+            "{} {} Hz: harmonic-to-noise ratio only {:.1} dB higher",
+            name,
+            frequency,
+            hnr_gain
+        );
+
+        assert!(blep.dc_offset.abs() < 1e-3, "{} {} Hz: DC offset {}", name, frequency, blep.dc_offset);
+    }
+}
+
+#[test]
+fn saw_poly_blep_beats_naive() {
+    assert_improvement("saw", OscillatorMode::Saw, 40.0, 10.0);
+}
+
+#[test]
+fn square_poly_blep_beats_naive() {
+    assert_improvement("square", OscillatorMode::Square, 40.0, 10.0);
+}
+
+#[test]
+fn triangle_poly_blep_beats_naive() {
+    // The naive triangle's harmonics already fall at 12 dB/octave, so there's less to gain.
+    assert_improvement("triangle", OscillatorMode::Triangle, 10.0, 2.0);
+}
+
+#[test]
+fn hard_sync_poly_blep_beats_naive() {
+    let (master_frequency, slave_frequency) = (1234.5, 2987.0);
+
+    // Naive sync restarts the slave on the sample after the master wraps.
+    let mut master = Oscillator::new(OscillatorMode::Saw, master_frequency, SAMPLE_RATE);
+    let mut slave = Oscillator::new(OscillatorMode::Saw, slave_frequency, SAMPLE_RATE);
+    let naive: Vec<f32> = (0..ANALYSIS_LENGTH)
+        .map(|_| {
+            master.tick_naive();
+            if master.sync_out().is_some() {
+                slave.reset_phase();
+            }
+            slave.tick_naive()
+        })
+        .collect();
+
+    let mut sync = HardSync::new(
+        Oscillator::new(OscillatorMode::Saw, master_frequency, SAMPLE_RATE),
+        Oscillator::new(OscillatorMode::Saw, slave_frequency, SAMPLE_RATE),
+    );
+    let mut blep = vec![0.0; ANALYSIS_LENGTH];
+    sync.fill(&mut blep);
+
+    let naive = analyze(&naive, master_frequency as f64, SAMPLE_RATE as f64);
+    let blep = analyze(&blep, master_frequency as f64, SAMPLE_RATE as f64);
+    println!("sync\n  naive     {:?}\n  polyblep  {:?}", naive, blep);
+
+    let alias_reduction = naive.alias_below_fundamental_db - blep.alias_below_fundamental_db;
+    assert!(alias_reduction >= 30.0, "aliasing below the fundamental only {:.1} dB lower", alias_reduction);
+    let hnr_gain = blep.harmonic_to_noise_db - naive.harmonic_to_noise_db;
+    assert!(hnr_gain >= 10.0, "harmonic-to-noise ratio only {:.1} dB higher", hnr_gain);
+}
+
+#[test]
+fn narrow_pulse_poly_blep_beats_naive() {
+    for &frequency in FREQUENCIES.iter() {
+        let render = |poly_blep: bool| {
+            let mut osc = Oscillator::new(OscillatorMode::Square, frequency, SAMPLE_RATE);
+            osc.set_pulse_width(0.2);
+            let mut signal = vec![0.0; ANALYSIS_LENGTH];
+            if poly_blep {
+                osc.fill_poly_blep(&mut signal);
+            } else {
+                osc.fill_naive(&mut signal);
+            }
+            analyze(&signal, frequency as f64, SAMPLE_RATE as f64)
+        };
+        let (naive, blep) = (render(false), render(true));
+        println!("pulse {} Hz\n  naive     {:?}\n  polyblep  {:?}", frequency, naive, blep);
+
+        let alias_reduction = naive.alias_below_fundamental_db - blep.alias_below_fundamental_db;
+        assert!(alias_reduction >= 40.0, "{} Hz: aliasing only {:.1} dB lower", frequency, alias_reduction);
+        let hnr_gain = blep.harmonic_to_noise_db - naive.harmonic_to_noise_db;
+        assert!(hnr_gain >= 10.0, "{} Hz: harmonic-to-noise ratio only {:.1} dB higher", frequency, hnr_gain);
+        assert!(blep.dc_offset.abs() < 1e-3, "{} Hz: DC offset {}", frequency, blep.dc_offset);
+    }
+}
+
+/// Asserts a noise generator's spectrum falls at `expected` dB/octave, give or take `tolerance`,
+/// from 50 Hz to 15 kHz.
+fn assert_slope<G: MonoGenerator>(name: &str, mut noise: G, expected: f64, tolerance: f64) {
+    let mut signal = vec![0.0; SAMPLE_RATE as usize * 20];
+    noise.fill(&mut signal);
+
+    let slope = spectral_slope(&signal, SAMPLE_RATE as f64, 50.0, 15000.0);
+    println!("{} noise: {:.2} dB/octave", name, slope);
+    assert!((slope - expected).abs() <= tolerance, "{} noise falls at {:.2} dB/octave", name, slope);
+}
+
+#[test]
+fn white_noise_is_flat() {
+    assert_slope("white", WhiteNoise::new(1), 0.0, 0.1);
+}
+
+#[test]
+fn pink_noise_falls_3_db_per_octave() {
+    assert_slope("pink", PinkNoise::new(1), -3.0, 0.25);
+}
+
+#[test]
+fn brown_noise_falls_6_db_per_octave() {
+    assert_slope("brown", BrownNoise::new(1), -6.0, 0.25);
+}
+
+/// Renders a driven sine through a distortion, after letting it settle, and measures it.
+fn analyze_distortion(kind: DistortionKind, antialiasing: Antialiasing, drive: f32, frequency: f32) -> SpectralReport {
+    let mut distortion = Distortion::new(kind, SAMPLE_RATE);
+    distortion.set_antialiasing(antialiasing);
+    distortion.set_drive(drive);
+
+    let mut osc = Oscillator::new(OscillatorMode::Sine, frequency, SAMPLE_RATE);
+    for _ in 0..SAMPLE_RATE as usize {
+        distortion.tick(osc.tick());
+    }
+    let signal: Vec<f32> = (0..ANALYSIS_LENGTH).map(|_| distortion.tick(osc.tick())).collect();
+
+    analyze(&signal, frequency as f64, SAMPLE_RATE as f64)
+}
+
+/// Asserts an antialiasing mode beats the naive curve by at least the given margins, in dB.
+fn assert_distortion_improvement(antialiasing: Antialiasing, min_alias_reduction: f64, min_hnr_gain: f64) {
+    for &(kind, drive) in &[
+        (DistortionKind::Tanh, 10.0),
+        (DistortionKind::HardClip, 10.0),
+        (DistortionKind::Tube, 10.0),
+        (DistortionKind::Wavefolder, 6.0),
+    ] {
+        // The lowest frequency's aliases are already down at the float noise floor for the smooth
+        // curves, with nothing left to remove.
+        for &frequency in FREQUENCIES[1..].iter() {
+            let naive = analyze_distortion(kind, Antialiasing::Off, drive, frequency);
+            let better = analyze_distortion(kind, antialiasing, drive, frequency);
+            println!("{:?} {} Hz\n  naive  {:?}\n  {:?}  {:?}", kind, frequency, naive, antialiasing, better);
+
+            let alias_reduction = naive.alias_below_fundamental_db - better.alias_below_fundamental_db;
+            assert!(
+                alias_reduction >= min_alias_reduction,
+                "{:?} {:?} {} Hz: aliasing below the fundamental only {:.1} dB lower",
+                kind,
+                antialiasing,
+                frequency,
+                alias_reduction
+            );
+
+            let hnr_gain = better.harmonic_to_noise_db - naive.harmonic_to_noise_db;
+            assert!(
+                hnr_gain >= min_hnr_gain,
+                "{:?} {:?} {} Hz: harmonic-to-noise ratio only {:.1} dB higher",
+                kind,
+                antialiasing,
+                frequency,
+                hnr_gain
+            );
+        }
+    }
+}
+
+#[test]
+fn distortion_adaa_beats_naive() {
+    assert_distortion_improvement(Antialiasing::Adaa, 15.0, 4.0);
+}
+
+#[test]
+fn distortion_oversampling_beats_adaa_on_smooth_curves() {
+    // At the highest frequency the smooth curves' aliasing is mostly from harmonics well under
+    // four times the sample rate, which oversampling removes. The folder's reach far beyond.
+    let frequency = FREQUENCIES[FREQUENCIES.len() - 1];
+    for &(kind, drive) in &[
+        (DistortionKind::Tanh, 10.0),
+        (DistortionKind::HardClip, 10.0),
+        (DistortionKind::Tube, 10.0),
+        (DistortionKind::Wavefolder, 6.0),
+    ] {
+        let adaa = analyze_distortion(kind, Antialiasing::Adaa, drive, frequency).alias_below_fundamental_db;
+        let oversampled = analyze_distortion(kind, Antialiasing::Oversampled, drive, frequency).alias_below_fundamental_db;
+        println!("{:?} {} Hz: ADAA {:.1} dB, oversampled {:.1} dB", kind, frequency, adaa, oversampled);
+
+        if kind == DistortionKind::Wavefolder {
+            assert!(adaa < oversampled, "{:?}: ADAA {:.1} dB, oversampled {:.1} dB", kind, adaa, oversampled);
+        } else {
+            assert!(
+                oversampled < adaa - 10.0,
+                "{:?}: ADAA {:.1} dB, oversampled {:.1} dB",
+                kind,
+                adaa,
+                oversampled
+            );
+        }
+    }
+}
+
+#[test]
+fn distortion_oversampling_beats_naive() {
+    // The folder's harmonics reach far past four times the sample rate, so oversampling only
+    // takes the edge off its aliasing, though it clears the most from the rest of the spectrum.
+    assert_distortion_improvement(Antialiasing::Oversampled, 8.0, 10.0);
+}
+
+/// Renders a sine through a driven, resonant ladder, after letting it settle, and measures it.
+fn analyze_ladder(oversample: bool, cutoff: f32, resonance: f32, drive: f32, frequency: f32) -> SpectralReport {
+    let mut filter = LadderFilter::new(cutoff, resonance, SAMPLE_RATE);
+    filter.set_oversampling(oversample);
+    filter.set_drive(drive);
+
+    let mut osc = Oscillator::new(OscillatorMode::Sine, frequency, SAMPLE_RATE);
+    for _ in 0..SAMPLE_RATE as usize {
+        filter.tick(osc.tick());
+    }
+    let signal: Vec<f32> = (0..ANALYSIS_LENGTH).map(|_| filter.tick(osc.tick())).collect();
+
+    analyze(&signal, frequency as f64, SAMPLE_RATE as f64)
+}
+
+#[test]
+fn ladder_oversampling_beats_single_rate() {
+    // A high cutoff lets the saturation's harmonics through to alias, and resonance near
+    // self-oscillation with heavy drive works the stages hardest.
+    for &frequency in FREQUENCIES.iter() {
+        let single = analyze_ladder(false, 10000.0, 0.9, 8.0, frequency);
+        let oversampled = analyze_ladder(true, 10000.0, 0.9, 8.0, frequency);
+        println!("ladder {} Hz\n  single       {:?}\n  oversampled  {:?}", frequency, single, oversampled);
+
+        let alias_reduction = single.alias_below_fundamental_db - oversampled.alias_below_fundamental_db;
+        assert!(alias_reduction >= 25.0, "{} Hz: aliasing only {:.1} dB lower", frequency, alias_reduction);
+        let hnr_gain = oversampled.harmonic_to_noise_db - single.harmonic_to_noise_db;
+        assert!(hnr_gain >= 20.0, "{} Hz: harmonic-to-noise ratio only {:.1} dB higher", frequency, hnr_gain);
+    }
+}
@@ -0,0 +1,171 @@
+use libdsp::biquad::{Biquad, BiquadCascade, BiquadCoefficients, BiquadKind, DirectFormI, TransposedDirectFormII};
+use libdsp::traits::MonoProcessor;
+use std::f64::consts::PI;
+
+const SAMPLE_RATE: f32 = 48000.0;
+const FREQUENCY: f32 = 1000.0;
+
+const KINDS: [BiquadKind; 8] = [
+    BiquadKind::Lowpass,
+    BiquadKind::Highpass,
+    BiquadKind::Bandpass,
+    BiquadKind::Notch,
+    BiquadKind::Allpass,
+    BiquadKind::Peaking,
+    BiquadKind::LowShelf,
+    BiquadKind::HighShelf,
+];
+
+/// Measures the steady-state response to a sine at `frequency`, as a gain and a phase in radians,
+/// by correlating the output against quadrature sinusoids.
+fn measure<P: MonoProcessor>(filter: &mut P, frequency: f64) -> (f64, f64) {
+    let settle = SAMPLE_RATE as usize / 4;
+    let measure = SAMPLE_RATE as usize / 2;
+    let (mut in_phase, mut quadrature) = (0.0, 0.0);
+    for i in 0..settle + measure {
+        let phase = 2.0 * PI * frequency * i as f64 / SAMPLE_RATE as f64;
+        let y = filter.tick(phase.sin() as f32) as f64;
+        if i >= settle {
+            in_phase += y * phase.sin();
+            quadrature += y * phase.cos();
+        }
+    }
+
+    let gain = 2.0 * (in_phase * in_phase + quadrature * quadrature).sqrt() / measure as f64;
+    (gain, quadrature.atan2(in_phase))
+}
+
+/// Magnitude of a ratio of quadratics in s = jw, each given as (s^2, s, 1) coefficients.
+fn quadratic_ratio(w: f64, numerator: (f64, f64, f64), denominator: (f64, f64, f64)) -> f64 {
+    let magnitude = |(c2, c1, c0): (f64, f64, f64)| (c0 - c2 * w * w).hypot(c1 * w);
+    magnitude(numerator) / magnitude(denominator)
+}
+
+/// The cookbook's analogue prototypes, at `frequency` warped as the bilinear transform warps it.
+fn analytic_gain(kind: BiquadKind, frequency: f64, q: f64, gain_db: f64) -> f64 {
+    let rate = SAMPLE_RATE as f64;
+    let w = (PI * frequency / rate).tan() / (PI * FREQUENCY as f64 / rate).tan();
+    let a = 10f64.powf(gain_db / 40.0);
+    let poles = (1.0, 1.0 / q, 1.0);
+
+    match kind {
+        BiquadKind::Lowpass => quadratic_ratio(w, (0.0, 0.0, 1.0), poles),
+        BiquadKind::Highpass => quadratic_ratio(w, (1.0, 0.0, 0.0), poles),
+        BiquadKind::Bandpass => quadratic_ratio(w, (0.0, 1.0 / q, 0.0), poles),
+        BiquadKind::Notch => quadratic_ratio(w, (1.0, 0.0, 1.0), poles),
+        BiquadKind::Allpass => 1.0,
+        BiquadKind::Peaking => quadratic_ratio(w, (1.0, a / q, 1.0), (1.0, 1.0 / (a * q), 1.0)),
+        BiquadKind::LowShelf => a * quadratic_ratio(w, (1.0, a.sqrt() / q, a), (a, a.sqrt() / q, 1.0)),
+        BiquadKind::HighShelf => a * quadratic_ratio(w, (a, a.sqrt() / q, 1.0), (1.0, a.sqrt() / q, a)),
+    }
+}
+
+fn assert_close_db(measured: f64, expected: f64, tolerance_db: f64, context: &str) {
+    let floor = 1e-4;
+    let difference = 20.0 * (measured.max(floor) / expected.max(floor)).log10();
+    assert!(
+        difference.abs() < tolerance_db,
+        "{}: measured {} expected {} ({} dB off)",
+        context,
+        measured,
+        expected,
+        difference
+    );
+}
+
+#[test]
+fn cookbook_responses_match_analytic_magnitudes() {
+    for &kind in KINDS.iter() {
+        for &q in &[std::f32::consts::FRAC_1_SQRT_2, 3.0] {
+            for &gain_db in &[-12.0, 6.0] {
+                let coefficients = BiquadCoefficients::design(kind, FREQUENCY, q, gain_db, SAMPLE_RATE);
+
+                for &frequency in &[50.0, 300.0, 1000.0, 3000.0, 12000.0] {
+                    if kind == BiquadKind::Notch && frequency == FREQUENCY as f64 {
+                        continue;
+                    }
+                    let expected = analytic_gain(kind, frequency, q as f64, gain_db as f64);
+                    let context = format!("{:?} Q {} gain {} at {} Hz", kind, q, gain_db, frequency);
+
+                    let (df1, _) = measure(&mut DirectFormI::new(coefficients), frequency);
+                    assert_close_db(df1, expected, 0.05, &context);
+                    let (tdf2, _) = measure(&mut TransposedDirectFormII::new(coefficients), frequency);
+                    assert_close_db(tdf2, expected, 0.05, &context);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn centre_frequency_gains() {
+    let gain_at_centre = |kind, gain_db| {
+        let mut filter = Biquad::new(kind, FREQUENCY, 2.0, SAMPLE_RATE);
+        filter.set_gain(gain_db);
+        measure(&mut filter, FREQUENCY as f64)
+    };
+
+    assert!(gain_at_centre(BiquadKind::Notch, 0.0).0 < 1e-3);
+    assert_close_db(gain_at_centre(BiquadKind::Bandpass, 0.0).0, 1.0, 0.01, "band pass");
+    assert_close_db(gain_at_centre(BiquadKind::Peaking, 9.0).0, 10f64.powf(9.0 / 20.0), 0.01, "peaking");
+    assert_close_db(gain_at_centre(BiquadKind::Peaking, -9.0).0, 10f64.powf(-9.0 / 20.0), 0.01, "peaking");
+
+    // The all pass is half way round at its centre.
+    let (gain, phase) = gain_at_centre(BiquadKind::Allpass, 0.0);
+    assert_close_db(gain, 1.0, 0.01, "all pass");
+    assert!((phase.abs() - PI).abs() < 0.01, "all pass phase {}", phase);
+}
+
+#[test]
+fn direct_forms_agree() {
+    let coefficients = BiquadCoefficients::design(BiquadKind::Peaking, 250.0, 4.0, 12.0, SAMPLE_RATE);
+    let mut df1 = DirectFormI::new(coefficients);
+    let mut tdf2 = TransposedDirectFormII::new(coefficients);
+
+    let mut seed = 1u32;
+    for i in 0..48000 {
+        seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+        let x = (seed >> 8) as f32 / 8_388_608.0 - 1.0;
+        let (a, b) = (df1.tick(x), tdf2.tick(x));
+        assert!((a - b).abs() < 1e-4, "sample {}: {} vs {}", i, a, b);
+    }
+}
+
+#[test]
+fn butterworth_cascades_are_maximally_flat() {
+    fn check<const N: usize>(kind: BiquadKind) {
+        let order = 2 * N as i32;
+        let rate = SAMPLE_RATE as f64;
+        for &frequency in &[100.0, 500.0, 1000.0, 1500.0, 4000.0] {
+            let mut w = (PI * frequency / rate).tan() / (PI * FREQUENCY as f64 / rate).tan();
+            if kind == BiquadKind::Highpass {
+                w = 1.0 / w;
+            }
+            let expected = 1.0 / (1.0 + w.powi(2 * order)).sqrt();
+
+            let mut cascade = BiquadCascade::<N>::butterworth(kind, FREQUENCY, SAMPLE_RATE);
+            let (gain, _) = measure(&mut cascade, frequency);
+            let context = format!("{:?} order {} at {} Hz", kind, order, frequency);
+            assert_close_db(gain, expected, 0.05, &context);
+        }
+    }
+
+    for &kind in &[BiquadKind::Lowpass, BiquadKind::Highpass] {
+        check::<1>(kind);
+        check::<2>(kind);
+        check::<4>(kind);
+    }
+}
+
+#[test]
+fn stable_when_swept() {
+    let mut filter = Biquad::new(BiquadKind::Lowpass, FREQUENCY, 10.0, SAMPLE_RATE);
+    for i in 0..SAMPLE_RATE as usize {
+        let t = i as f32 / SAMPLE_RATE;
+        filter.set_frequency(2000.0 + 1900.0 * (2.0 * std::f32::consts::PI * 200.0 * t).sin());
+
+        let x = if (i / 40) % 2 == 0 { 1.0 } else { -1.0 };
+        let y = filter.tick(x);
+        assert!(y.is_finite() && y.abs() < 100.0, "output blew up at sample {}", i);
+    }
+}
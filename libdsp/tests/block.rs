@@ -0,0 +1,56 @@
+use libdsp::oscillators::{Oscillator, OscillatorMode};
+use libdsp::traits::MonoGenerator;
+
+const MODES: [OscillatorMode; 4] = [
+    OscillatorMode::Sine,
+    OscillatorMode::Saw,
+    OscillatorMode::Square,
+    OscillatorMode::Triangle,
+];
+
+#[test]
+fn fill_matches_tick() {
+    for &mode in MODES.iter() {
+        for &frequency in &[55.0, 440.0, 7040.0, 20000.0] {
+            let mut ticked = Oscillator::new(mode, frequency, 48000.0);
+            let mut filled = Oscillator::new(mode, frequency, 48000.0);
+
+            // Odd block sizes so block boundaries land at different phases.
+            let mut block = [0.0; 37];
+            for _ in 0..100 {
+                filled.fill(&mut block);
+                for &x in block.iter() {
+                    assert_eq!(x, ticked.tick_poly_blep());
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn fill_naive_matches_tick_naive() {
+    for &mode in MODES.iter() {
+        let mut ticked = Oscillator::new(mode, 440.0, 48000.0);
+        let mut filled = Oscillator::new(mode, 440.0, 48000.0);
+
+        let mut block = [0.0; 37];
+        for _ in 0..100 {
+            filled.fill_naive(&mut block);
+            for &x in block.iter() {
+                assert_eq!(x, ticked.tick_naive());
+            }
+        }
+    }
+}
+
+#[test]
+fn fill_handles_increments_beyond_one_cycle() {
+    let mut ticked = Oscillator::new(OscillatorMode::Saw, 100000.0, 48000.0);
+    let mut filled = Oscillator::new(OscillatorMode::Saw, 100000.0, 48000.0);
+
+    let mut block = [0.0; 64];
+    filled.fill(&mut block);
+    for &x in block.iter() {
+        assert_eq!(x, ticked.tick_poly_blep());
+    }
+}
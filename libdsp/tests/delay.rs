@@ -0,0 +1,202 @@
+use libdsp::delay::{DelayLine, DelayMode, Interpolation, StereoDelay};
+use libdsp::traits::{MonoProcessor, StereoProcessor};
+use std::f32::consts::PI;
+
+const SAMPLE_RATE: f32 = 48000.0;
+
+const INTERPOLATIONS: [Interpolation; 3] = [Interpolation::Linear, Interpolation::Cubic, Interpolation::Allpass];
+
+fn impulse_response(line: &mut DelayLine, length: usize) -> Vec<f32> {
+    (0..length).map(|n| line.tick(if n == 0 { 1.0 } else { 0.0 })).collect()
+}
+
+#[test]
+fn whole_delays_are_exact() {
+    for &interpolation in INTERPOLATIONS.iter() {
+        for &delay in &[1, 2, 3, 17, 98] {
+            let mut buffer = [0.0; 100];
+            let mut line = DelayLine::new(&mut buffer);
+            line.set_interpolation(interpolation);
+            line.set_delay(delay as f32);
+
+            let output = impulse_response(&mut line, 200);
+            for (n, &x) in output.iter().enumerate().take(100) {
+                let expected = if n == delay { 1.0 } else { 0.0 };
+                assert!((x - expected).abs() < 1e-6, "{:?} delay {} sample {}: {}", interpolation, delay, n, x);
+            }
+        }
+    }
+}
+
+#[test]
+fn delay_is_clamped_to_the_buffer() {
+    let mut buffer = vec![0.5; 100];
+    let mut line = DelayLine::new(&mut buffer);
+    line.set_delay(1000.0);
+    assert_eq!(line.delay(), 98.0);
+    line.set_delay(0.0);
+    assert_eq!(line.delay(), 1.0);
+
+    // The buffer is cleared on the way in.
+    line.set_delay(98.0);
+    assert!(impulse_response(&mut line, 98).iter().all(|&x| x == 0.0));
+}
+
+/// Runs a sine through a line at a fractional delay, returning the largest error against the
+/// ideally delayed sine and the output's peak.
+fn fractional_delay(interpolation: Interpolation, delay: f32, frequency: f32) -> (f32, f32) {
+    let mut buffer = vec![0.0; 1024];
+    let mut line = DelayLine::new(&mut buffer);
+    line.set_interpolation(interpolation);
+    line.set_delay(delay);
+
+    let w = 2.0 * PI * frequency / SAMPLE_RATE;
+    let (mut error, mut peak) = (0.0f32, 0.0f32);
+    for n in 0..4800 {
+        let y = line.tick((w * n as f32).sin());
+        if n > 2400 {
+            error = error.max((y - (w * (n as f32 - delay)).sin()).abs());
+            peak = peak.max(y.abs());
+        }
+    }
+
+    (error, peak)
+}
+
+#[test]
+fn fractional_delays_interpolate() {
+    for &interpolation in INTERPOLATIONS.iter() {
+        for &delay in &[2.5, 10.25, 100.5, 500.9] {
+            let (error, _) = fractional_delay(interpolation, delay, 500.0);
+            assert!(error < 2e-3, "{:?} at {}: error {}", interpolation, delay, error);
+        }
+    }
+
+    // Halfway between samples is where the interpolations differ most in the highs.
+    let (_, linear) = fractional_delay(Interpolation::Linear, 100.5, 8000.0);
+    let (_, cubic) = fractional_delay(Interpolation::Cubic, 100.5, 8000.0);
+    let (_, allpass) = fractional_delay(Interpolation::Allpass, 100.5, 8000.0);
+    assert!(linear < 0.9, "linear {}", linear);
+    assert!(cubic > linear + 0.05, "cubic {} vs linear {}", cubic, linear);
+    assert!((allpass - 1.0).abs() < 1e-3, "allpass {}", allpass);
+}
+
+/// Runs silence through a delay until its time has glided to the setting.
+fn settle(delay: &mut StereoDelay) {
+    for _ in 0..SAMPLE_RATE as usize {
+        delay.tick((0.0, 0.0));
+    }
+}
+
+/// Feeds a 10 ms burst of 1 kHz into the left input and returns the peak level of each channel in
+/// each `period`.
+fn repeat_levels(mode: DelayMode, period: usize, periods: usize) -> Vec<(f32, f32)> {
+    let (mut left, mut right) = (vec![0.0; 48000], vec![0.0; 48000]);
+    let mut delay = StereoDelay::new(&mut left, &mut right, SAMPLE_RATE);
+    delay.set_mode(mode);
+    delay.set_time(period as f32 / SAMPLE_RATE);
+    delay.set_feedback(0.5);
+    delay.set_mix(1.0);
+    delay.set_damping(20000.0);
+    delay.set_low_cut(20.0);
+    settle(&mut delay);
+
+    let mut levels = vec![(0.0f32, 0.0f32); periods];
+    for n in 0..period * periods {
+        let x = if n < 480 { (2.0 * PI * 1000.0 * n as f32 / SAMPLE_RATE).sin() } else { 0.0 };
+        let (l, r) = delay.tick((x, 0.0));
+        // Skip the burst's edges, where the feedback filters ring.
+        if n % period > 100 && n % period < 380 {
+            let level = &mut levels[n / period];
+            level.0 = level.0.max(l.abs());
+            level.1 = level.1.max(r.abs());
+        }
+    }
+
+    levels
+}
+
+/// Checks a repeat's level to within 5%, which leaves room for the low cut's slow settling.
+fn assert_level(measured: f32, expected: f32, context: &str) {
+    assert!((measured - expected).abs() < 0.05 * expected.max(0.05), "{}: {} vs {}", context, measured, expected);
+}
+
+#[test]
+fn stereo_repeats_stay_on_their_side() {
+    let levels = repeat_levels(DelayMode::Stereo, 4800, 5);
+    assert_level(levels[0].0, 0.0, "dry");
+    for (k, &(l, r)) in levels.iter().enumerate().skip(1) {
+        assert_level(l, 0.5f32.powi(k as i32 - 1), &format!("left repeat {}", k));
+        assert!(r < 1e-6, "right repeat {}: {}", k, r);
+    }
+}
+
+#[test]
+fn ping_pong_repeats_alternate() {
+    let levels = repeat_levels(DelayMode::PingPong, 4800, 5);
+    for (k, &(l, r)) in levels.iter().enumerate().skip(1) {
+        // The input is summed to mono on the way in, halving a one-sided input.
+        let expected = 0.5 * 0.5f32.powi(k as i32 - 1);
+        let (on, off) = if k % 2 == 1 { (l, r) } else { (r, l) };
+        assert_level(on, expected, &format!("repeat {}", k));
+        assert!(off < 1e-6, "repeat {} leaked to the other side: {}", k, off);
+    }
+}
+
+/// Feeds a Hann-windowed 10 ms burst of 8 kHz through a delay with strong feedback, and returns the
+/// peak of the repeats still sounding half a second later.
+fn high_tone_tail(damping: f32) -> f32 {
+    let (mut left, mut right) = (vec![0.0; 4800], vec![0.0; 4800]);
+    let mut delay = StereoDelay::new(&mut left, &mut right, SAMPLE_RATE);
+    delay.set_time(0.05);
+    delay.set_feedback(0.9);
+    delay.set_mix(1.0);
+    delay.set_damping(damping);
+    settle(&mut delay);
+
+    let mut tail = 0.0f32;
+    for n in 0..48000 {
+        let x = if n < 480 {
+            let window = 0.5 - 0.5 * (2.0 * PI * n as f32 / 480.0).cos();
+            window * (2.0 * PI * 8000.0 * n as f32 / SAMPLE_RATE).sin()
+        } else {
+            0.0
+        };
+        let (l, _) = delay.tick((x, x));
+        if n > 24000 {
+            tail = tail.max(l.abs());
+        }
+    }
+
+    tail
+}
+
+#[test]
+fn feedback_filters_darken_repeats() {
+    let bright = high_tone_tail(20000.0);
+    let dark = high_tone_tail(2000.0);
+    assert!(bright > 0.05, "bright tail {}", bright);
+    assert!(dark < 1e-3 * bright, "dark tail {} vs bright {}", dark, bright);
+}
+
+#[test]
+fn stable_at_full_feedback_with_moving_time() {
+    let (mut left, mut right) = (vec![0.0; 9600], vec![0.0; 9600]);
+    let mut delay = StereoDelay::new(&mut left, &mut right, SAMPLE_RATE);
+    delay.set_feedback(1.0);
+    delay.set_damping(20000.0);
+
+    for &mode in &[DelayMode::Stereo, DelayMode::PingPong] {
+        delay.set_mode(mode);
+        let mut seed = 1u32;
+        for n in 0..SAMPLE_RATE as usize * 2 {
+            if n % 4800 == 0 {
+                delay.set_time(0.01 + (n % 9600) as f32 / SAMPLE_RATE);
+            }
+            seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            let x = (seed >> 8) as f32 / 8_388_608.0 - 1.0;
+            let (l, r) = delay.tick((x, -x));
+            assert!(l.is_finite() && r.is_finite() && l.abs() < 50.0 && r.abs() < 50.0, "{:?} sample {}", mode, n);
+        }
+    }
+}
@@ -0,0 +1,198 @@
+use libdsp::distortion::{Antialiasing, Distortion, DistortionKind};
+use libdsp::traits::MonoProcessor;
+use std::f64::consts::PI;
+
+const SAMPLE_RATE: f32 = 48000.0;
+
+const KINDS: [DistortionKind; 4] = [
+    DistortionKind::Tanh,
+    DistortionKind::HardClip,
+    DistortionKind::Tube,
+    DistortionKind::Wavefolder,
+];
+
+const ANTIALIASING: [Antialiasing; 3] = [Antialiasing::Off, Antialiasing::Adaa, Antialiasing::Oversampled];
+
+/// The curves, written out independently.
+fn curve(kind: DistortionKind, x: f64) -> f64 {
+    match kind {
+        DistortionKind::Tanh => x.tanh(),
+        DistortionKind::HardClip => x.clamp(-1.0, 1.0),
+        DistortionKind::Tube => (x + 0.5).tanh() - 0.5f64.tanh(),
+        DistortionKind::Wavefolder => {
+            // A triangle through ±1 at odd inputs, level beyond 11.
+            let folded = ((x.abs().min(11.0) + 1.0) % 4.0 - 2.0).abs() - 1.0;
+            -folded * x.signum()
+        }
+    }
+}
+
+/// The amplitude of the `harmonic`th harmonic of a sine at `frequency` through `distortion`, after
+/// letting it settle.
+fn harmonic(distortion: &mut Distortion, frequency: f64, harmonic: usize) -> f64 {
+    let settle = SAMPLE_RATE as usize / 4;
+    let measure = SAMPLE_RATE as usize;
+    let (mut in_phase, mut quadrature) = (0.0, 0.0);
+    for i in 0..settle + measure {
+        let phase = 2.0 * PI * frequency * i as f64 / SAMPLE_RATE as f64;
+        let y = distortion.tick(phase.sin() as f32) as f64;
+        if i >= settle {
+            in_phase += y * (harmonic as f64 * phase).sin();
+            quadrature += y * (harmonic as f64 * phase).cos();
+        }
+    }
+
+    2.0 * in_phase.hypot(quadrature) / measure as f64
+}
+
+#[test]
+fn slow_inputs_follow_the_curve() {
+    for &kind in KINDS.iter() {
+        for &antialiasing in &[Antialiasing::Off, Antialiasing::Adaa] {
+            let mut distortion = Distortion::new(kind, SAMPLE_RATE);
+            distortion.set_antialiasing(antialiasing);
+            distortion.set_drive(14.0);
+
+            // A ramp over the whole curve, fast enough that ADAA takes the antiderivative path.
+            let steps = 2000;
+            let mut previous = 0.0;
+            // The tube's DC blocker, a one-pole high pass at 10 Hz.
+            let coefficient = 1.0 - 2.0 * PI * 10.0 / SAMPLE_RATE as f64;
+            let (mut blocker_input, mut blocker_output) = (0.0, 0.0);
+            for n in 0..=steps {
+                let x = -1.0 + 2.0 * n as f64 / steps as f64;
+                let y = distortion.tick(x as f32) as f64;
+
+                // ADAA gives the curve averaged since the last sample, close to its midpoint. Its
+                // first sample averages from the silence before the ramp, across the knee.
+                let at = if antialiasing == Antialiasing::Adaa { 0.5 * (x + previous) } else { x };
+                previous = x;
+                let mut expected = curve(kind, 14.0 * at);
+                if kind == DistortionKind::Tube {
+                    blocker_output = expected - blocker_input + coefficient * blocker_output;
+                    blocker_input = expected;
+                    expected = blocker_output;
+                }
+                if n == 0 && antialiasing == Antialiasing::Adaa {
+                    continue;
+                }
+
+                let tolerance = 0.02;
+                assert!(
+                    (y - expected).abs() < tolerance,
+                    "{:?} {:?} at {}: {} vs {}",
+                    kind,
+                    antialiasing,
+                    x,
+                    y,
+                    expected
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn quiet_fast_inputs_take_the_antiderivative() {
+    // A quiet sine riding on the hard clip's knee. Its steps are small, but the curve bends
+    // between them, so the average over each step differs from the curve at its midpoint.
+    let mut distortion = Distortion::new(DistortionKind::HardClip, SAMPLE_RATE);
+    let antiderivative = |x: f64| if x.abs() <= 1.0 { 0.5 * x * x } else { x.abs() - 0.5 };
+
+    let mut previous = 0.0;
+    let mut largest_gap: f64 = 0.0;
+    for n in 0..480 {
+        let x = (1.0 + 0.01 * (2.0 * PI * 5003.0 * n as f64 / SAMPLE_RATE as f64).sin()) as f32;
+        let y = distortion.tick(x) as f64;
+
+        let (x, p) = (x as f64, previous);
+        previous = x;
+        if n == 0 {
+            continue;
+        }
+        let average = (antiderivative(x) - antiderivative(p)) / (x - p);
+        let midpoint = (0.5 * (x + p)).clamp(-1.0, 1.0);
+        largest_gap = largest_gap.max((average - midpoint).abs());
+        assert!((y - average).abs() < 2e-4, "sample {}: {} vs {}", n, y, average);
+    }
+
+    // Otherwise the test couldn't tell the two apart.
+    assert!(largest_gap > 5e-4, "{}", largest_gap);
+}
+
+#[test]
+fn wavefolder_folds_at_odd_levels() {
+    let mut distortion = Distortion::new(DistortionKind::Wavefolder, SAMPLE_RATE);
+    distortion.set_antialiasing(Antialiasing::Off);
+
+    for &(x, expected) in &[(0.5, 0.5), (1.0, 1.0), (2.0, 0.0), (3.0, -1.0), (4.0, 0.0), (5.0, 1.0), (9.0, 1.0), (11.0, -1.0), (20.0, -1.0)] {
+        assert!((distortion.tick(x) - expected).abs() < 1e-5, "{}", x);
+        assert!((distortion.tick(-x) + expected).abs() < 1e-5, "{}", -x);
+    }
+}
+
+#[test]
+fn quiet_signals_pass_unchanged() {
+    for &kind in &[DistortionKind::Tanh, DistortionKind::HardClip, DistortionKind::Wavefolder] {
+        for &antialiasing in ANTIALIASING.iter() {
+            let mut distortion = Distortion::new(kind, SAMPLE_RATE);
+            distortion.set_antialiasing(antialiasing);
+            distortion.set_drive(0.01);
+
+            let gain = harmonic(&mut distortion, 1000.0, 1) / 0.01;
+            assert!((gain - 1.0).abs() < 0.01, "{:?} {:?}: {}", kind, antialiasing, gain);
+        }
+    }
+}
+
+#[test]
+fn only_the_tube_adds_even_harmonics() {
+    for &kind in KINDS.iter() {
+        let mut distortion = Distortion::new(kind, SAMPLE_RATE);
+        distortion.set_drive(3.0);
+
+        let fundamental = harmonic(&mut distortion, 500.0, 1);
+        let second = harmonic(&mut distortion, 500.0, 2) / fundamental;
+        let third = harmonic(&mut distortion, 500.0, 3) / fundamental;
+        assert!(third > 0.05, "{:?} third {}", kind, third);
+        if kind == DistortionKind::Tube {
+            assert!(second > 0.05, "{:?} second {}", kind, second);
+        } else {
+            assert!(second < 1e-3, "{:?} second {}", kind, second);
+        }
+    }
+}
+
+#[test]
+fn tube_output_has_no_dc() {
+    let mut distortion = Distortion::new(DistortionKind::Tube, SAMPLE_RATE);
+    distortion.set_drive(5.0);
+
+    let output: Vec<f32> = (0..SAMPLE_RATE as usize * 2)
+        .map(|i| distortion.tick((2.0 * PI * 440.0 * i as f64 / SAMPLE_RATE as f64).sin() as f32))
+        .collect();
+    let last = &output[SAMPLE_RATE as usize..];
+    let mean = last.iter().map(|&x| x as f64).sum::<f64>() / last.len() as f64;
+    assert!(mean.abs() < 1e-3, "{}", mean);
+}
+
+#[test]
+fn bounded_and_finite_for_any_input() {
+    for &kind in KINDS.iter() {
+        for &antialiasing in ANTIALIASING.iter() {
+            let mut distortion = Distortion::new(kind, SAMPLE_RATE);
+            distortion.set_antialiasing(antialiasing);
+            distortion.set_drive(100.0);
+
+            let mut seed = 1u32;
+            for n in 0..20000 {
+                seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                // Noise, with stretches of DC where ADAA's successive inputs are equal.
+                let x = if n % 1000 < 100 { 0.3 } else { (seed >> 8) as f32 / 8_388_608.0 - 1.0 };
+
+                let y = distortion.tick(x);
+                assert!(y.is_finite() && y.abs() < 2.5, "{:?} {:?} sample {}: {}", kind, antialiasing, n, y);
+            }
+        }
+    }
+}
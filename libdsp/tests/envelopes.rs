@@ -0,0 +1,183 @@
+use libdsp::envelopes::{Adsr, EnvelopeCurve, EnvelopeStage, TriggerMode};
+use libdsp::traits::MonoGenerator;
+
+const CURVES: [EnvelopeCurve; 2] = [EnvelopeCurve::Linear, EnvelopeCurve::Exponential];
+
+/// Created at one rate and moved to another, so the times have to follow `set_sample_rate`.
+const SAMPLE_RATES: [f32; 2] = [44100.0, 96000.0];
+
+fn envelope(curve: EnvelopeCurve, attack: f32, decay: f32, sustain: f32, release: f32, sample_rate: f32) -> Adsr {
+    let mut env = Adsr::new(attack, decay, sustain, release, 48000.0);
+    env.set_curve(curve);
+    env.set_sample_rate(sample_rate);
+    env
+}
+
+/// Ticks until the envelope leaves `stage`, returning how many ticks that took.
+fn time_in(env: &mut Adsr, stage: EnvelopeStage, limit: usize) -> usize {
+    let mut ticks = 0;
+    while env.stage() == stage {
+        env.tick();
+        ticks += 1;
+        assert!(ticks <= limit, "still in {:?} after {} ticks at level {}", stage, ticks, env.level());
+    }
+    ticks
+}
+
+/// Asserts a segment took its time, give or take a couple of samples.
+fn assert_time(name: &str, curve: EnvelopeCurve, sample_rate: f32, ticks: usize, seconds: f32) {
+    let expected = seconds * sample_rate;
+    assert!(
+        (ticks as f32 - expected).abs() <= 2.0,
+        "{:?} {} at {} Hz: {} ticks, expected {}",
+        curve,
+        name,
+        sample_rate,
+        ticks,
+        expected
+    );
+}
+
+#[test]
+fn attack_takes_its_time() {
+    for &curve in CURVES.iter() {
+        for &sample_rate in SAMPLE_RATES.iter() {
+            for &attack in &[0.001, 0.05, 2.0] {
+                let mut env = envelope(curve, attack, 0.1, 0.5, 0.1, sample_rate);
+                env.gate_on();
+                let ticks = time_in(&mut env, EnvelopeStage::Attack, 1_000_000);
+                assert_time("attack", curve, sample_rate, ticks, attack);
+                assert_eq!(env.level(), 1.0);
+            }
+        }
+    }
+}
+
+#[test]
+fn decay_to_zero_takes_its_time() {
+    for &curve in CURVES.iter() {
+        for &sample_rate in SAMPLE_RATES.iter() {
+            for &decay in &[0.01, 0.3, 4.0] {
+                let mut env = envelope(curve, 0.001, decay, 0.0, 0.1, sample_rate);
+                env.gate_on();
+                time_in(&mut env, EnvelopeStage::Attack, 1000);
+                let ticks = time_in(&mut env, EnvelopeStage::Decay, 1_000_000);
+                assert_time("decay", curve, sample_rate, ticks, decay);
+                assert_eq!(env.stage(), EnvelopeStage::Sustain);
+            }
+        }
+    }
+}
+
+#[test]
+fn decay_reaches_a_raised_sustain() {
+    for &curve in CURVES.iter() {
+        for &sample_rate in SAMPLE_RATES.iter() {
+            for &decay in &[0.01, 1.0, 10.0] {
+                let mut env = envelope(curve, 0.001, decay, 0.5, 0.5, sample_rate);
+                env.gate_on();
+                time_in(&mut env, EnvelopeStage::Attack, 1000);
+                let ticks = time_in(&mut env, EnvelopeStage::Decay, (2.0 * decay * sample_rate) as usize);
+
+                if curve == EnvelopeCurve::Linear {
+                    assert_time("decay", curve, sample_rate, ticks, decay);
+                } else {
+                    // The exponential decay aims past zero, whatever the sustain level, so it
+                    // reaches a raised sustain early: here at ln(0.5 / 1e-4) / ln(1 / 1e-4) of the
+                    // time.
+                    let fraction = ticks as f32 / (decay * sample_rate);
+                    assert!((fraction - 0.9247).abs() < 0.002, "{} s at {} Hz: {}", decay, sample_rate, fraction);
+                }
+                assert_eq!(env.stage(), EnvelopeStage::Sustain);
+                assert_eq!(env.tick(), 0.5);
+            }
+        }
+    }
+}
+
+#[test]
+fn short_attack_into_long_decay_reaches_sustain() {
+    // The last steps of a long exponential decay are far smaller than the level they're added to,
+    // and once used to round away and leave the level stuck just above the sustain.
+    let mut env = Adsr::new(0.001, 1.0, 0.5, 0.5, 48000.0);
+    env.gate_on();
+    time_in(&mut env, EnvelopeStage::Attack, 1000);
+    time_in(&mut env, EnvelopeStage::Decay, 48000);
+    assert_eq!(env.stage(), EnvelopeStage::Sustain);
+    assert_eq!(env.tick(), 0.5);
+}
+
+#[test]
+fn release_takes_its_time() {
+    for &curve in CURVES.iter() {
+        for &sample_rate in SAMPLE_RATES.iter() {
+            for &release in &[0.01, 0.5, 3.0] {
+                let mut env = envelope(curve, 0.001, 0.001, 1.0, release, sample_rate);
+                env.gate_on();
+                for _ in 0..1000 {
+                    env.tick();
+                }
+                assert_eq!(env.stage(), EnvelopeStage::Sustain);
+
+                env.gate_off();
+                let ticks = time_in(&mut env, EnvelopeStage::Release, 1_000_000);
+                assert_time("release", curve, sample_rate, ticks, release);
+                assert_eq!(env.stage(), EnvelopeStage::Idle);
+                assert!(!env.is_active());
+                assert_eq!(env.level(), 0.0);
+            }
+        }
+    }
+}
+
+#[test]
+fn linear_release_from_mid_decay_keeps_its_time() {
+    let mut env = envelope(EnvelopeCurve::Linear, 0.001, 0.1, 0.2, 0.05, 48000.0);
+    env.gate_on();
+    for _ in 0..2000 {
+        env.tick();
+    }
+    assert_eq!(env.stage(), EnvelopeStage::Decay);
+
+    env.gate_off();
+    let ticks = time_in(&mut env, EnvelopeStage::Release, 10000);
+    assert_time("release", EnvelopeCurve::Linear, 48000.0, ticks, 0.05);
+}
+
+#[test]
+fn retrigger_restarts_and_legato_holds() {
+    for &curve in CURVES.iter() {
+        let mut env = envelope(curve, 0.01, 0.01, 0.5, 0.1, 48000.0);
+        env.gate_on();
+        for _ in 0..4800 {
+            env.tick();
+        }
+        assert_eq!(env.stage(), EnvelopeStage::Sustain);
+
+        // Retrigger climbs again from where it is.
+        env.gate_on();
+        assert_eq!(env.stage(), EnvelopeStage::Attack);
+        let first = env.tick();
+        assert!(first > 0.5 && first < 0.6, "{:?}: {}", curve, first);
+
+        let mut env = envelope(curve, 0.01, 0.01, 0.5, 0.1, 48000.0);
+        env.set_trigger_mode(TriggerMode::Legato);
+        env.gate_on();
+        for _ in 0..4800 {
+            env.tick();
+        }
+
+        // Overlapping notes don't restart a legato envelope...
+        env.gate_on();
+        assert_eq!(env.stage(), EnvelopeStage::Sustain);
+        assert_eq!(env.tick(), 0.5);
+
+        // ...but a note after the gate's been released does.
+        env.gate_off();
+        for _ in 0..100 {
+            env.tick();
+        }
+        env.gate_on();
+        assert_eq!(env.stage(), EnvelopeStage::Attack);
+    }
+}
@@ -0,0 +1,103 @@
+use libdsp::fixed::{FixedOscillator, Q15, Q31};
+use libdsp::oscillators::{Oscillator64, OscillatorMode};
+
+#[test]
+fn arithmetic_saturates() {
+    assert_eq!(Q15::MAX + Q15(1), Q15::MAX);
+    assert_eq!(Q15::MIN - Q15(1), Q15::MIN);
+    assert_eq!(-Q15::MIN, Q15::MAX);
+    assert_eq!(Q15::MIN * Q15::MIN, Q15::MAX);
+
+    assert_eq!(Q31::MAX + Q31::MAX, Q31::MAX);
+    assert_eq!(Q31::MIN + Q31::MIN, Q31::MIN);
+    assert_eq!(-Q31::MIN, Q31::MAX);
+    assert_eq!(Q31::MIN * Q31::MIN, Q31::MAX);
+}
+
+#[test]
+fn multiplication_rounds() {
+    assert_eq!(Q15::from_f32(0.5) * Q15::from_f32(0.5), Q15::from_f32(0.25));
+    assert_eq!(Q15::from_f32(-0.5) * Q15::from_f32(0.5), Q15::from_f32(-0.25));
+    // Three steps times a half is 1.5 steps, which rounds to 2 where truncation would give 1.
+    assert_eq!(Q15(3) * Q15(1 << 14), Q15(2));
+    assert_eq!(Q31::from_f32(0.75) * Q31::MIN, Q31::from_f32(-0.75));
+}
+
+#[test]
+fn conversions() {
+    assert_eq!(Q15::from_f32(2.0), Q15::MAX);
+    assert_eq!(Q15::from_f32(-2.0), Q15::MIN);
+    assert_eq!(Q31::from_f32(1.0), Q31::MAX);
+    assert_eq!(Q31::from_f32(-1.0), Q31::MIN);
+    assert_eq!(Q15::from_f32(0.25).to_f32(), 0.25);
+
+    assert_eq!(Q31::from(Q15::from_f32(-0.5)), Q31::from_f32(-0.5));
+    assert_eq!(Q31::MAX.to_q15(), Q15::MAX);
+    assert_eq!(Q31::from_f32(0.123).to_q15(), Q15::from_f32(0.123));
+}
+
+/// Runs the fixed-point oscillator against the f64 one at the same phase increment and returns
+/// the largest difference.
+fn worst_difference(mode: OscillatorMode, increment: u32) -> f64 {
+    let mut fixed = FixedOscillator::new(mode, 0.0, 48000.0);
+    fixed.set_phase_increment(increment);
+    let frequency = increment as f64 * 48000.0 / 4_294_967_296.0;
+    let mut float = Oscillator64::new(mode, frequency, 48000.0);
+
+    let mut worst: f64 = 0.0;
+    for _ in 0..48000 {
+        let x = fixed.tick_poly_blep().to_f32() as f64;
+        worst = worst.max((x - float.tick_poly_blep()).abs());
+    }
+
+    worst
+}
+
+// Roughly 47, 440, 1500 and 6000 Hz at 48 kHz, chosen to fall between sine table points.
+const INCREMENTS: [u32; 4] = [4_194_305, 39_370_241, 134_217_773, 536_870_911];
+
+#[test]
+fn sine_matches_float_oscillator() {
+    for &increment in INCREMENTS.iter() {
+        let worst = worst_difference(OscillatorMode::Sine, increment);
+        assert!(worst < 5e-6, "increment {}: worst difference {:.3e}", increment, worst);
+    }
+}
+
+#[test]
+fn poly_blep_modes_match_float_oscillator() {
+    for &mode in [OscillatorMode::Saw, OscillatorMode::Square, OscillatorMode::Triangle].iter() {
+        for &increment in INCREMENTS.iter() {
+            let worst = worst_difference(mode, increment);
+            assert!(worst < 1e-6, "increment {}: worst difference {:.3e}", increment, worst);
+        }
+    }
+}
+
+#[test]
+fn negative_frequency_runs_backwards() {
+    let mut forwards = FixedOscillator::new(OscillatorMode::Sine, 1000.0, 48000.0);
+    let mut backwards = FixedOscillator::new(OscillatorMode::Sine, -1000.0, 48000.0);
+
+    // Interpolation rounding, and Q31 stopping a step short of 1.0, let the mirrored halves
+    // differ by a couple of steps.
+    for _ in 0..1000 {
+        let (x, y) = (forwards.tick_naive(), backwards.tick_naive());
+        assert!((x.0 as i64 + y.0 as i64).abs() <= 2, "{:?} {:?}", x, y);
+    }
+}
+
+#[test]
+fn frequency_keeps_its_sign() {
+    let mut osc = FixedOscillator::new(OscillatorMode::Saw, -1000.0, 48000.0);
+    assert!((osc.frequency() + 1000.0).abs() < 1e-3, "{}", osc.frequency());
+
+    // Changing the sample rate keeps the pitch, and the direction.
+    osc.set_sample_rate(96000.0);
+    assert!((osc.frequency() + 1000.0).abs() < 1e-3, "{}", osc.frequency());
+    assert_eq!(osc.phase_increment() as i32, -(1000.0 / 96000.0 * 4_294_967_296.0f64).round() as i32);
+
+    let mut osc = FixedOscillator::new(OscillatorMode::Saw, 1000.0, 48000.0);
+    osc.set_sample_rate(96000.0);
+    assert!((osc.frequency() - 1000.0).abs() < 1e-3, "{}", osc.frequency());
+}
@@ -0,0 +1,184 @@
+use libdsp::fm::{Algorithm, FmOperator, FmVoice, OperatorFrequency};
+use libdsp::oscillators::{FmMode, Oscillator64, OscillatorMode};
+use libdsp::traits::MonoGenerator;
+
+use std::f64::consts::PI;
+
+const SAMPLE_RATE: f64 = 48000.0;
+
+// A whole number of 2^-32 cycle steps per sample at 48 kHz, as are its multiples below, so
+// oscillators at these frequencies can be compared with exact sines.
+const EXACT: f64 = 375.0;
+
+const MODES: [OscillatorMode; 4] = [
+    OscillatorMode::Sine,
+    OscillatorMode::Saw,
+    OscillatorMode::Square,
+    OscillatorMode::Triangle,
+];
+
+#[test]
+fn unmodulated_tick_matches_poly_blep() {
+    for &mode in MODES.iter() {
+        for &frequency in &[440.0, -440.0, 7040.0] {
+            let mut plain = Oscillator64::new(mode, frequency, SAMPLE_RATE);
+            let mut modulated = Oscillator64::new(mode, frequency, SAMPLE_RATE);
+            for n in 0..4800 {
+                assert_eq!(plain.tick_poly_blep(), modulated.tick_modulated(0.0, 0.0), "{} Hz, sample {}", frequency, n);
+                assert_eq!(plain.sync_out(), modulated.sync_out());
+            }
+        }
+    }
+}
+
+#[test]
+fn constant_fm_shifts_the_frequency() {
+    for &fm_mode in &[FmMode::Linear, FmMode::ThroughZero] {
+        for &mode in MODES.iter() {
+            let mut shifted = Oscillator64::new(mode, 660.0, SAMPLE_RATE);
+            let mut modulated = Oscillator64::new(mode, 440.0, SAMPLE_RATE);
+            modulated.set_fm_mode(fm_mode);
+
+            let mut fm = [220.0; 4800];
+            let mut block = [0.0; 4800];
+            modulated.fill_modulated(&mut block, &fm, &[0.0; 4800]);
+            shifted.fill_poly_blep(&mut fm);
+            assert_eq!(block, fm);
+        }
+    }
+}
+
+#[test]
+fn linear_fm_stops_at_zero() {
+    let mut osc = Oscillator64::new(OscillatorMode::Saw, 440.0, SAMPLE_RATE);
+    osc.set_fm_mode(FmMode::Linear);
+    osc.tick_modulated(0.0, 0.0);
+    let stalled = osc.tick_modulated(-880.0, 0.0);
+    for _ in 0..100 {
+        assert_eq!(osc.tick_modulated(-880.0, 0.0), stalled);
+    }
+}
+
+#[test]
+fn through_zero_fm_runs_backwards() {
+    for &mode in MODES.iter() {
+        let mut backwards = Oscillator64::new(mode, -440.0, SAMPLE_RATE);
+        let mut modulated = Oscillator64::new(mode, 440.0, SAMPLE_RATE);
+        modulated.set_fm_mode(FmMode::ThroughZero);
+        for n in 0..4800 {
+            let (x, y) = (backwards.tick_poly_blep(), modulated.tick_modulated(-880.0, 0.0));
+            assert!((x - y).abs() < 1e-9, "sample {}: {} and {}", n, x, y);
+        }
+    }
+}
+
+#[test]
+fn phase_modulation_offsets_the_phase() {
+    let mut sine = Oscillator64::new(OscillatorMode::Sine, EXACT, SAMPLE_RATE);
+    for n in 0..4800 {
+        // Moving the phase on a quarter of a cycle turns the sine into a cosine.
+        let expected = (2.0 * PI * EXACT * n as f64 / SAMPLE_RATE).cos();
+        let x = sine.tick_modulated(0.0, 0.25);
+        assert!((x - expected).abs() < 1e-9, "sample {}: {} vs {}", n, x, expected);
+    }
+}
+
+#[test]
+fn phase_modulated_saw_stays_bounded() {
+    let mut saw = Oscillator64::new(OscillatorMode::Saw, 440.0, SAMPLE_RATE);
+    for n in 0..48000 {
+        let pm = 3.0 * (2.0 * PI * 1234.5 * n as f64 / SAMPLE_RATE).sin();
+        let x = saw.tick_modulated(0.0, pm);
+        assert!(x.is_finite() && x.abs() <= 2.0, "sample {}: {}", n, x);
+    }
+}
+
+fn operator(frequency: OperatorFrequency<f64>, level: f64) -> FmOperator<f64> {
+    let mut operator = FmOperator::new(frequency, SAMPLE_RATE);
+    operator.set_level(level);
+    operator
+}
+
+#[test]
+fn two_operator_stack_matches_closed_form() {
+    let (note, index) = (EXACT, 1.5);
+    let mut voice = FmVoice::new(
+        [
+            operator(OperatorFrequency::Ratio(1.0), 0.8),
+            operator(OperatorFrequency::Ratio(3.0), index),
+        ],
+        Algorithm::stack(),
+    );
+    voice.set_note_frequency(note);
+
+    for n in 0..4800 {
+        let t = n as f64 / SAMPLE_RATE;
+        let modulator = index * (2.0 * PI * 3.0 * note * t).sin();
+        let expected = 0.8 * (2.0 * PI * (note * t + modulator)).sin();
+        let x = voice.tick();
+        // The modulated phase is rounded to 2^-32 of a cycle.
+        assert!((x - expected).abs() < 1e-8, "sample {}: {} vs {}", n, x, expected);
+    }
+}
+
+#[test]
+fn fixed_frequency_ignores_the_note() {
+    let mut fixed = operator(OperatorFrequency::Fixed(1000.0), 1.0);
+    let mut reference = Oscillator64::new(OscillatorMode::Sine, 1000.0, SAMPLE_RATE);
+    fixed.set_note_frequency(123.0);
+    for _ in 0..4800 {
+        assert_eq!(fixed.tick(0.0), reference.tick_poly_blep());
+    }
+}
+
+#[test]
+fn parallel_mixes_carriers_at_equal_levels() {
+    let ratios = [1.0, 2.0, 3.0, 4.0];
+    let mut voice = FmVoice::new(ratios.map(|r| operator(OperatorFrequency::Ratio(r), 1.0)), Algorithm::parallel());
+    voice.set_note_frequency(EXACT);
+
+    for n in 0..4800 {
+        let t = n as f64 / SAMPLE_RATE;
+        let expected: f64 = ratios.iter().map(|r| (2.0 * PI * r * EXACT * t).sin()).sum::<f64>() / 4.0;
+        let x = voice.tick();
+        assert!((x - expected).abs() < 1e-9, "sample {}: {} vs {}", n, x, expected);
+    }
+}
+
+#[test]
+fn algorithm_ignores_upward_connections() {
+    let algorithm = Algorithm::<6>::empty()
+        .with_carrier(0)
+        .with_modulation(2, 0)
+        .with_modulation(0, 3)
+        .with_modulation(4, 4)
+        .with_modulation(9, 1)
+        .with_carrier(8);
+
+    assert!(algorithm.modulates(2, 0));
+    assert!(!algorithm.modulates(0, 3));
+    assert!(!algorithm.modulates(4, 4));
+    assert!(!algorithm.modulates(9, 1));
+    assert!(algorithm.is_carrier(0));
+    assert!(!algorithm.is_carrier(8));
+
+    let pairs = Algorithm::<6>::pairs();
+    assert!(pairs.modulates(1, 0) && pairs.modulates(3, 2) && pairs.modulates(5, 4));
+    assert!(!pairs.modulates(2, 1));
+    assert!((0..6).all(|i| pairs.is_carrier(i) == (i % 2 == 0)));
+}
+
+#[test]
+fn feedback_changes_the_waveform_and_stays_bounded() {
+    let mut plain = operator(OperatorFrequency::Fixed(440.0), 1.0);
+    let mut fed_back = operator(OperatorFrequency::Fixed(440.0), 1.0);
+    fed_back.set_feedback(0.3);
+
+    let mut difference: f64 = 0.0;
+    for _ in 0..48000 {
+        let (x, y) = (plain.tick(0.0), fed_back.tick(0.0));
+        assert!(y.is_finite() && y.abs() <= 1.0);
+        difference = difference.max((x - y).abs());
+    }
+    assert!(difference > 0.1, "feedback made no difference");
+}
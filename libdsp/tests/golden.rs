@@ -0,0 +1,239 @@
+//! Golden-audio regression tests for `Oscillator`.
+//!
+//! Each case renders a short buffer and compares it with a reference in `tests/golden/`, stored as
+//! little-endian `f32`s. After an intentional change to the oscillator output, regenerate the
+//! references with `LIBDSP_BLESS=1 cargo test --test golden` and listen to / review the difference
+//! before committing them.
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use libdsp::oscillators::{Oscillator, OscillatorMode};
+
+const LENGTH: usize = 1024;
+
+/// Largest per-sample difference allowed from the reference.
+const PEAK_TOLERANCE: f32 = 1e-5;
+
+const MODES: [(&str, OscillatorMode); 4] = [
+    ("sine", OscillatorMode::Sine),
+    ("saw", OscillatorMode::Saw),
+    ("square", OscillatorMode::Square),
+    ("triangle", OscillatorMode::Triangle),
+];
+
+const FREQUENCIES: [f32; 3] = [55.0, 440.0, 3520.0];
+const SAMPLE_RATES: [f32; 2] = [44100.0, 48000.0];
+
+#[derive(Clone, Copy)]
+enum Path {
+    Naive,
+    PolyBlep,
+}
+
+impl Path {
+    fn name(self) -> &'static str {
+        match self {
+            Path::Naive => "naive",
+            Path::PolyBlep => "poly_blep",
+        }
+    }
+}
+
+fn render(path: Path, mode: OscillatorMode, frequency: f32, sample_rate: f32) -> Vec<f32> {
+    let mut osc = Oscillator::new(mode, frequency, sample_rate);
+    (0..LENGTH)
+        .map(|_| match path {
+            Path::Naive => osc.tick_naive(),
+            Path::PolyBlep => osc.tick_poly_blep(),
+        })
+        .collect()
+}
+
+fn reference_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("golden")
+        .join(format!("{}.f32", name))
+}
+
+fn read_reference(name: &str) -> Option<Vec<f32>> {
+    let bytes = fs::read(reference_path(name)).ok()?;
+    Some(
+        bytes
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect(),
+    )
+}
+
+fn write_reference(name: &str, samples: &[f32]) {
+    let path = reference_path(name);
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    let bytes: Vec<u8> = samples.iter().flat_map(|x| x.to_le_bytes().to_vec()).collect();
+    fs::write(path, bytes).unwrap();
+}
+
+struct Deviation {
+    peak: f32,
+    rms: f32,
+}
+
+fn deviation(rendered: &[f32], reference: &[f32]) -> Deviation {
+    let mut peak: f32 = 0.0;
+    let mut sum_squares = 0.0f64;
+    for (a, b) in rendered.iter().zip(reference.iter()) {
+        let difference = (a - b).abs();
+        peak = peak.max(difference);
+        sum_squares += (difference as f64).powi(2);
+    }
+
+    Deviation {
+        peak,
+        rms: (sum_squares / rendered.len() as f64).sqrt() as f32,
+    }
+}
+
+fn check_path(path: Path) {
+    let bless = env::var_os("LIBDSP_BLESS").is_some();
+    let mut failures = Vec::new();
+
+    for &(mode_name, mode) in MODES.iter() {
+        for &frequency in FREQUENCIES.iter() {
+            for &sample_rate in SAMPLE_RATES.iter() {
+                let name = format!("{}_{}_{}hz_{}", path.name(), mode_name, frequency, sample_rate);
+                let rendered = render(path, mode, frequency, sample_rate);
+
+                if bless {
+                    write_reference(&name, &rendered);
+                    continue;
+                }
+
+                let reference = match read_reference(&name) {
+                    Some(reference) => reference,
+                    None => {
+                        failures.push(format!("{}: missing reference (run with LIBDSP_BLESS=1)", name));
+                        continue;
+                    }
+                };
+                if reference.len() != rendered.len() {
+                    failures.push(format!("{}: reference has {} samples", name, reference.len()));
+                    continue;
+                }
+
+                let deviation = deviation(&rendered, &reference);
+                println!("{:40} peak {:.3e}  rms {:.3e}", name, deviation.peak, deviation.rms);
+                if deviation.peak > PEAK_TOLERANCE {
+                    failures.push(format!(
+                        "{}: peak deviation {:.3e}, rms {:.3e}",
+                        name, deviation.peak, deviation.rms
+                    ));
+                }
+            }
+        }
+    }
+
+    assert!(failures.is_empty(), "golden mismatches:\n{}", failures.join("\n"));
+}
+
+#[test]
+fn naive_matches_golden() {
+    check_path(Path::Naive);
+}
+
+#[test]
+fn poly_blep_matches_golden() {
+    check_path(Path::PolyBlep);
+}
+
+/// How far the references' phase may stray from the exact phase, in cycles. The phase increment
+/// is rounded to 2^-32 of a cycle, which over the buffer adds up to about 1e-7.
+const PHASE_TOLERANCE: f64 = 2.5e-7;
+
+fn poly_blep(t: f64, dt: f64) -> f64 {
+    if t < dt {
+        let x = t / dt;
+        x + x - x * x - 1.0
+    } else if t > 1.0 - dt {
+        let x = (t - 1.0) / dt;
+        x * x + x + x + 1.0
+    } else {
+        0.0
+    }
+}
+
+/// The references, other than the sines, worked out in f64 from the exact phase of each sample.
+/// Where the phase lands on a discontinuity of a naive waveform, the rounding of the increment
+/// decides which side the reference falls, so that sample is skipped.
+fn exact(path: Path, mode: OscillatorMode, frequency: f32, sample_rate: f32) -> Vec<Option<f64>> {
+    let (frequency, sample_rate) = (frequency as u64, sample_rate as u64);
+    let dt = frequency as f64 / sample_rate as f64;
+    let mut last_output = 0.0;
+
+    (0..LENGTH as u64)
+        .map(|n| {
+            let numerator = n * frequency % sample_rate;
+            let t = numerator as f64 / sample_rate as f64;
+            let on_edge = numerator == 0 || 2 * numerator == sample_rate;
+
+            let square = if t < 0.5 { 1.0 } else { -1.0 };
+            let blep_square = square + poly_blep(t, dt) - poly_blep((t + 0.5).fract(), dt);
+            match (path, mode) {
+                (_, OscillatorMode::Sine) => unreachable!(),
+                (Path::Naive, OscillatorMode::Saw) => Some(2.0 * t - 1.0).filter(|_| !on_edge),
+                (Path::Naive, OscillatorMode::Square) => Some(square).filter(|_| !on_edge),
+                (Path::Naive, OscillatorMode::Triangle) => Some(2.0 * ((2.0 * t - 1.0).abs() - 0.5)),
+                (Path::PolyBlep, OscillatorMode::Saw) => Some(2.0 * t - 1.0 - poly_blep(t, dt)),
+                (Path::PolyBlep, OscillatorMode::Square) => Some(blep_square),
+                (Path::PolyBlep, OscillatorMode::Triangle) => {
+                    let leak = 2.0 * std::f64::consts::PI * dt;
+                    last_output = leak * blep_square + (1.0 - leak) * last_output;
+                    Some(last_output)
+                }
+            }
+        })
+        .collect()
+}
+
+/// Checks the references themselves, so a bless that moves them away from the waveforms they
+/// stand for is caught. The sine references are left out, as they're dominated by the error of
+/// the sine approximation.
+#[test]
+fn references_follow_the_exact_phase() {
+    let mut failures = Vec::new();
+
+    for &(mode_name, mode) in MODES.iter().filter(|&&(_, mode)| mode != OscillatorMode::Sine) {
+        for &path in &[Path::Naive, Path::PolyBlep] {
+            for &frequency in FREQUENCIES.iter() {
+                for &sample_rate in SAMPLE_RATES.iter() {
+                    let name = format!("{}_{}_{}hz_{}", path.name(), mode_name, frequency, sample_rate);
+                    let reference = match read_reference(&name) {
+                        Some(reference) => reference,
+                        None => continue,
+                    };
+
+                    // A phase error moves a sample by the waveform's slope times the error. The
+                    // naive waveforms climb at most 4 a cycle, and a PolyBLEP corner up to 4 / dt.
+                    let dt = frequency as f64 / sample_rate as f64;
+                    let slope = match path {
+                        Path::Naive => 4.0,
+                        Path::PolyBlep => 4.0 / dt,
+                    };
+                    let tolerance = slope * PHASE_TOLERANCE;
+
+                    let worst = reference
+                        .iter()
+                        .zip(exact(path, mode, frequency, sample_rate))
+                        .filter_map(|(&x, exact)| exact.map(|exact| (x as f64 - exact).abs()))
+                        .fold(0.0, f64::max);
+                    if worst > tolerance {
+                        failures.push(format!("{}: {:.3e} from exact, allowed {:.3e}", name, worst, tolerance));
+                    }
+                }
+            }
+        }
+    }
+
+    assert!(failures.is_empty(), "references off the exact phase:\n{}", failures.join("\n"));
+}
@@ -0,0 +1,138 @@
+use libdsp::filters::LadderFilter;
+use libdsp::traits::MonoProcessor;
+use std::f64::consts::PI;
+
+const SAMPLE_RATES: [f32; 3] = [44100.0, 48000.0, 96000.0];
+
+/// Measures the steady-state gain for a quiet sine at `frequency`, by correlating the output
+/// against quadrature sinusoids.
+fn measure_gain(filter: &mut LadderFilter, frequency: f64, sample_rate: f32) -> f64 {
+    // Quiet enough that the saturation stays linear.
+    let amplitude = 1e-3;
+    let settle = sample_rate as usize / 2;
+    let measure = sample_rate as usize / 2;
+    let (mut in_phase, mut quadrature) = (0.0, 0.0);
+    for i in 0..settle + measure {
+        let phase = 2.0 * PI * frequency * i as f64 / sample_rate as f64;
+        let y = filter.tick((amplitude * phase.sin()) as f32) as f64;
+        if i >= settle {
+            in_phase += y * phase.sin();
+            quadrature += y * phase.cos();
+        }
+    }
+
+    2.0 * (in_phase * in_phase + quadrature * quadrature).sqrt() / measure as f64 / amplitude
+}
+
+/// The linear ladder's magnitude, four prewarped one-poles in a loop with feedback `4 * resonance`.
+fn analytic_gain(frequency: f64, cutoff: f64, resonance: f64, rate: f64) -> f64 {
+    let w = (PI * frequency / rate).tan() / (PI * cutoff / rate).tan();
+    // 1 / (1 + jw)^4, then closed around the feedback loop.
+    let (mut re, mut im) = (1.0, 0.0);
+    for _ in 0..4 {
+        let d = 1.0 + w * w;
+        let (r, i) = (re / d, im / d);
+        re = r + i * w;
+        im = i - r * w;
+    }
+    let k = 4.0 * resonance;
+    let (den_re, den_im) = (1.0 + k * re, k * im);
+
+    ((re * re + im * im) / (den_re * den_re + den_im * den_im)).sqrt()
+}
+
+#[test]
+fn small_signal_response_matches_linear_ladder() {
+    let cutoff = 1000.0;
+    for &sample_rate in SAMPLE_RATES.iter() {
+        for &oversample in &[false, true] {
+            for &resonance in &[0.0, 0.5, 0.9] {
+                let rate = if oversample { 2.0 * sample_rate as f64 } else { sample_rate as f64 };
+                for &frequency in &[50.0, 500.0, 1000.0, 2000.0, 4000.0] {
+                    let mut filter = LadderFilter::new(cutoff as f32, resonance as f32, sample_rate);
+                    filter.set_oversampling(oversample);
+
+                    let measured = measure_gain(&mut filter, frequency, sample_rate);
+                    let expected = analytic_gain(frequency, cutoff, resonance, rate);
+                    let difference = 20.0 * (measured / expected).log10();
+                    assert!(
+                        difference.abs() < 0.2,
+                        "{} Hz, oversampling {}, resonance {}, at {} Hz: {:.2} dB off",
+                        sample_rate,
+                        oversample,
+                        resonance,
+                        frequency,
+                        difference
+                    );
+                }
+            }
+        }
+    }
+}
+
+/// Pings the filter and measures the frequency it self-oscillates at, from the zero crossings
+/// over its second second.
+fn self_oscillation_frequency(cutoff: f32, resonance: f32, sample_rate: f32, oversample: bool) -> f32 {
+    let mut filter = LadderFilter::new(cutoff, resonance, sample_rate);
+    filter.set_oversampling(oversample);
+
+    let length = sample_rate as usize * 2;
+    let output: Vec<f32> = (0..length).map(|i| filter.tick(if i == 0 { 1.0 } else { 0.0 })).collect();
+    let tail = &output[length / 2..];
+
+    let crossings: Vec<f32> = (1..tail.len())
+        .filter(|&i| tail[i - 1] < 0.0 && tail[i] >= 0.0)
+        .map(|i| i as f32 - 1.0 - tail[i - 1] / (tail[i] - tail[i - 1]))
+        .collect();
+    assert!(crossings.len() > 10, "{} Hz at {} Hz didn't oscillate", cutoff, sample_rate);
+
+    (crossings.len() - 1) as f32 * sample_rate / (crossings[crossings.len() - 1] - crossings[0])
+}
+
+#[test]
+fn self_oscillation_tracks_cutoff() {
+    for &sample_rate in SAMPLE_RATES.iter() {
+        for &oversample in &[false, true] {
+            for &cutoff in &[100.0, 440.0, 1000.0, 3000.0, 8000.0, 15000.0] {
+                let frequency = self_oscillation_frequency(cutoff, 1.02, sample_rate, oversample);
+                assert!(
+                    (frequency / cutoff - 1.0).abs() < 0.015,
+                    "{} Hz, oversampling {}: cutoff {} oscillates at {}",
+                    sample_rate,
+                    oversample,
+                    cutoff,
+                    frequency
+                );
+            }
+
+            // Driving it harder flattens the pitch, but by the same amount in every octave.
+            let low = self_oscillation_frequency(220.0, 1.2, sample_rate, oversample);
+            for &cutoff in &[440.0, 880.0] {
+                let ratio = self_oscillation_frequency(cutoff, 1.2, sample_rate, oversample) / low;
+                assert!((ratio / (cutoff / 220.0) - 1.0).abs() < 0.02, "{} Hz: ratio {}", sample_rate, ratio);
+            }
+        }
+    }
+}
+
+#[test]
+fn stable_at_high_resonance() {
+    for &sample_rate in SAMPLE_RATES.iter() {
+        for &oversample in &[false, true] {
+            let mut filter = LadderFilter::new(1000.0, 1.2, sample_rate);
+            filter.set_oversampling(oversample);
+            filter.set_drive(4.0);
+
+            for i in 0..sample_rate as usize {
+                // Sweep the cutoff at audio rate from 20 Hz up to near Nyquist.
+                let t = i as f32 / sample_rate;
+                let sweep = 0.5 + 0.5 * (2.0 * std::f32::consts::PI * 500.0 * t).sin();
+                filter.set_cutoff(20.0 + sweep * 0.45 * sample_rate);
+
+                let x = if (i / 37) % 2 == 0 { 1.0 } else { -1.0 };
+                let y = filter.tick(x);
+                assert!(y.is_finite() && y.abs() < 10.0, "{} Hz: {} at sample {}", sample_rate, y, i);
+            }
+        }
+    }
+}
@@ -0,0 +1,180 @@
+use libdsp::lfo::{Lfo, LfoPolarity, LfoRate, LfoShape, NoteDivision, NoteModifier};
+use libdsp::traits::MonoGenerator;
+
+use std::f64::consts::PI;
+
+const SAMPLE_RATE: f64 = 48000.0;
+
+// Exactly 1024 samples per cycle at 48 kHz.
+const RATE: f64 = 46.875;
+const CYCLE: usize = 1024;
+
+const SHAPES: [LfoShape; 6] = [
+    LfoShape::Sine,
+    LfoShape::Triangle,
+    LfoShape::Saw,
+    LfoShape::Square,
+    LfoShape::SampleAndHold,
+    LfoShape::SmoothRandom,
+];
+
+fn render(lfo: &mut Lfo<f64>, length: usize) -> Vec<f64> {
+    let mut buffer = vec![0.0; length];
+    lfo.fill(&mut buffer);
+    buffer
+}
+
+#[test]
+fn shapes_at_quarter_cycles() {
+    let expected = [
+        (LfoShape::Sine, [0.0, 1.0, 0.0, -1.0]),
+        (LfoShape::Triangle, [0.0, 1.0, 0.0, -1.0]),
+        (LfoShape::Saw, [-1.0, -0.5, 0.0, 0.5]),
+        (LfoShape::Square, [1.0, 1.0, -1.0, -1.0]),
+    ];
+
+    for &(shape, values) in expected.iter() {
+        let output = render(&mut Lfo::new(shape, LfoRate::Free(RATE), SAMPLE_RATE), CYCLE);
+        for (quarter, &value) in values.iter().enumerate() {
+            let x = output[quarter * CYCLE / 4];
+            assert!((x - value).abs() < 1e-9, "{:?} at {} quarters: {}", shape, quarter, x);
+        }
+    }
+}
+
+#[test]
+fn every_shape_stays_in_range() {
+    for &shape in SHAPES.iter() {
+        for &polarity in &[LfoPolarity::Bipolar, LfoPolarity::Unipolar] {
+            let mut lfo = Lfo::new(shape, LfoRate::Free(RATE), SAMPLE_RATE);
+            lfo.set_polarity(polarity);
+            let (low, high) = if polarity == LfoPolarity::Bipolar { (-1.0, 1.0) } else { (0.0, 1.0) };
+
+            let output = render(&mut lfo, CYCLE * 50);
+            assert!(output.iter().all(|&x| (low..=high).contains(&x)), "{:?} {:?}", shape, polarity);
+        }
+    }
+}
+
+#[test]
+fn random_shapes_change_once_a_cycle() {
+    let output = render(&mut Lfo::new(LfoShape::SampleAndHold, LfoRate::Free(RATE), SAMPLE_RATE), CYCLE * 10);
+    let changes: Vec<usize> = (1..output.len()).filter(|&n| output[n] != output[n - 1]).collect();
+    assert_eq!(changes, (1..10).map(|n| n * CYCLE).collect::<Vec<_>>());
+
+    // The smooth shape passes through each held value at the start of its cycle, without jumps.
+    let smooth = render(&mut Lfo::new(LfoShape::SmoothRandom, LfoRate::Free(RATE), SAMPLE_RATE), CYCLE * 10);
+    for n in 1..10 {
+        assert!((smooth[n * CYCLE] - output[(n - 1) * CYCLE]).abs() < 1e-9);
+    }
+    assert!(smooth.windows(2).all(|w| (w[1] - w[0]).abs() < 4.0 / CYCLE as f64));
+}
+
+#[test]
+fn phase_offset_shifts_the_cycle() {
+    let mut lfo = Lfo::new(LfoShape::Sine, LfoRate::Free(RATE), SAMPLE_RATE);
+    lfo.set_phase_offset(0.25);
+    let output = render(&mut lfo, CYCLE);
+    for (n, &x) in output.iter().enumerate() {
+        let expected = (2.0 * PI * n as f64 / CYCLE as f64).cos();
+        assert!((x - expected).abs() < 1e-9, "sample {}: {} vs {}", n, x, expected);
+    }
+}
+
+#[test]
+fn note_on_retriggers_and_fades_in() {
+    let mut lfo = Lfo::new(LfoShape::Saw, LfoRate::Free(RATE), SAMPLE_RATE);
+    lfo.set_fade_in(0.01);
+    render(&mut lfo, 300);
+
+    lfo.note_on();
+    let output = render(&mut lfo, 960);
+    for (n, &x) in output.iter().enumerate() {
+        // 480 samples of fade over a ramp starting from -1.
+        let saw = 2.0 * n as f64 / CYCLE as f64 - 1.0;
+        let fade = (n as f64 / 480.0).min(1.0);
+        assert!((x - saw * fade).abs() < 1e-9, "sample {}: {} vs {}", n, x, saw * fade);
+    }
+
+    // Without key retrigger only the fade restarts.
+    lfo.set_retrigger(false);
+    lfo.set_fade_in(0.0);
+    lfo.note_on();
+    let x = lfo.tick();
+    assert!((x - (2.0 * 960.0 / CYCLE as f64 - 1.0)).abs() < 1e-9, "{}", x);
+}
+
+#[test]
+fn one_shot_holds_the_end_of_the_cycle() {
+    let mut lfo = Lfo::new(LfoShape::Saw, LfoRate::Free(RATE), SAMPLE_RATE);
+    lfo.set_one_shot(true);
+    lfo.set_phase_offset(0.5);
+    lfo.note_on();
+
+    let output = render(&mut lfo, CYCLE * 3);
+    assert_eq!(output[0], 0.0);
+    // Half a cycle up to the top, then back round from the bottom to where it started.
+    assert!((output[CYCLE / 2 - 1] - (1.0 - 2.0 / CYCLE as f64)).abs() < 1e-9);
+    assert_eq!(output[CYCLE / 2], -1.0);
+    let end = output[CYCLE - 1];
+    assert!(end < 0.0 && end >= -2.0 / CYCLE as f64, "{}", end);
+    assert!(output[CYCLE..].iter().all(|&x| x.abs() < 1e-9));
+
+    lfo.note_on();
+    assert_eq!(lfo.tick(), 0.0);
+}
+
+#[test]
+fn synced_rate_follows_the_tempo() {
+    let cases = [
+        (NoteDivision::Quarter, NoteModifier::Straight, 2.0),
+        (NoteDivision::Whole, NoteModifier::Straight, 0.5),
+        (NoteDivision::Eighth, NoteModifier::Dotted, 4.0 / 1.5),
+        (NoteDivision::Sixteenth, NoteModifier::Triplet, 8.0 * 1.5),
+        (NoteDivision::FourBars, NoteModifier::Straight, 0.125),
+    ];
+
+    for &(division, modifier, frequency) in cases.iter() {
+        let mut synced = Lfo::new(LfoShape::Sine, LfoRate::Synced(division, modifier), SAMPLE_RATE);
+        let mut free = Lfo::new(LfoShape::Sine, LfoRate::Free(frequency), SAMPLE_RATE);
+        assert_eq!(render(&mut synced, 4800), render(&mut free, 4800), "{:?} {:?}", division, modifier);
+    }
+
+    let mut synced = Lfo::new(LfoShape::Sine, LfoRate::Synced(NoteDivision::Quarter, NoteModifier::Straight), SAMPLE_RATE);
+    let mut free = Lfo::new(LfoShape::Sine, LfoRate::Free(1.5), SAMPLE_RATE);
+    synced.set_tempo(90.0);
+    assert_eq!(render(&mut synced, 4800), render(&mut free, 4800));
+}
+
+#[test]
+fn external_clock_sets_tempo_and_phase() {
+    // 24 pulses per quarter note at 100 BPM is a pulse every 1200 samples.
+    let mut lfo = Lfo::new(LfoShape::Saw, LfoRate::Synced(NoteDivision::Half, NoteModifier::Straight), SAMPLE_RATE);
+    let mut output = Vec::new();
+    for _ in 0..24 * 8 {
+        lfo.clock_pulse();
+        output.extend(render(&mut lfo, 1200));
+    }
+
+    assert!((lfo.tempo() - 100.0).abs() < 1e-9, "tempo {}", lfo.tempo());
+    // Every half note is 48 pulses, and the ramp should restart on each one, at the bottom. Until
+    // the second pulse the tempo is the default, so the first half note runs short.
+    for half_note in 2..4 {
+        let start = half_note * 48 * 1200;
+        assert_eq!(output[start], -1.0, "half note {}", half_note);
+        assert!(output[start - 1] > 0.99, "half note {}: {}", half_note, output[start - 1]);
+    }
+}
+
+#[test]
+fn clock_reset_restarts_on_the_next_pulse() {
+    let mut lfo = Lfo::new(LfoShape::Saw, LfoRate::Synced(NoteDivision::Whole, NoteModifier::Straight), SAMPLE_RATE);
+    for _ in 0..30 {
+        lfo.clock_pulse();
+        render(&mut lfo, 1000);
+    }
+
+    lfo.reset_clock();
+    lfo.clock_pulse();
+    assert_eq!(lfo.tick(), -1.0);
+}
@@ -0,0 +1,419 @@
+use libdsp::modulation::{Chorus, Flanger, ModulationError, Phaser};
+use libdsp::traits::StereoProcessor;
+use std::f64::consts::PI;
+
+const SAMPLE_RATE: f32 = 48000.0;
+
+/// Measures the steady-state gain of each channel for a sine at `frequency` fed to both, by
+/// correlating the outputs against quadrature sinusoids.
+fn measure<P: StereoProcessor>(effect: &mut P, frequency: f64) -> (f64, f64) {
+    let settle = SAMPLE_RATE as usize / 10;
+    let measure = SAMPLE_RATE as usize / 5;
+    let mut sums = [0.0; 4];
+    for i in 0..settle + measure {
+        let phase = 2.0 * PI * frequency * i as f64 / SAMPLE_RATE as f64;
+        let x = phase.sin() as f32;
+        let (left, right) = effect.tick((x, x));
+        if i >= settle {
+            sums[0] += left as f64 * phase.sin();
+            sums[1] += left as f64 * phase.cos();
+            sums[2] += right as f64 * phase.sin();
+            sums[3] += right as f64 * phase.cos();
+        }
+    }
+
+    let gain = |a: f64, b: f64| 2.0 * a.hypot(b) / measure as f64;
+    (gain(sums[0], sums[1]), gain(sums[2], sums[3]))
+}
+
+/// The left channel's level over successive 10 ms windows, for a sine at `frequency`.
+fn envelope<P: StereoProcessor>(effect: &mut P, frequency: f64, seconds: f64) -> Vec<f64> {
+    let window = SAMPLE_RATE as usize / 100;
+    let samples: Vec<f64> = (0..(seconds * SAMPLE_RATE as f64) as usize)
+        .map(|i| {
+            let x = (2.0 * PI * frequency * i as f64 / SAMPLE_RATE as f64).sin() as f32;
+            effect.tick((x, x)).0 as f64
+        })
+        .collect();
+
+    samples
+        .chunks_exact(window)
+        .map(|w| (2.0 * w.iter().map(|x| x * x).sum::<f64>() / window as f64).sqrt())
+        .collect()
+}
+
+/// The time in seconds of the quietest window of an envelope between `from` and `to` seconds.
+fn quietest(envelope: &[f64], from: f64, to: f64) -> (f64, f64) {
+    let (index, &level) = envelope[(from * 100.0) as usize..(to * 100.0) as usize]
+        .iter()
+        .enumerate()
+        .min_by(|a, b| a.1.partial_cmp(b.1).unwrap())
+        .unwrap();
+    (from + (index as f64 + 0.5) / 100.0, level)
+}
+
+type Complex = (f64, f64);
+
+fn multiply(a: Complex, b: Complex) -> Complex {
+    (a.0 * b.0 - a.1 * b.1, a.0 * b.1 + a.1 * b.0)
+}
+
+fn divide(a: Complex, b: Complex) -> Complex {
+    let d = b.0 * b.0 + b.1 * b.1;
+    ((a.0 * b.0 + a.1 * b.1) / d, (a.1 * b.0 - a.0 * b.1) / d)
+}
+
+/// The response of a delay of `samples` at `frequency`.
+fn delay(frequency: f64, samples: f64) -> Complex {
+    let w = 2.0 * PI * frequency / SAMPLE_RATE as f64;
+    ((w * samples).cos(), -(w * samples).sin())
+}
+
+fn magnitude(x: Complex) -> f64 {
+    x.0.hypot(x.1)
+}
+
+#[test]
+fn memory_must_fit() {
+    let mut small = vec![0.0; Chorus::memory_len(SAMPLE_RATE) - 1];
+    assert_eq!(Chorus::new(&mut small, SAMPLE_RATE).err(), Some(ModulationError::MemoryTooSmall));
+
+    let mut small = vec![0.0; Flanger::memory_len(SAMPLE_RATE) - 1];
+    assert_eq!(Flanger::new(&mut small, SAMPLE_RATE).err(), Some(ModulationError::MemoryTooSmall));
+}
+
+#[test]
+fn sample_rate_changes_keep_the_delays_in_seconds() {
+    // Memory for 96 kHz runs at 48 kHz too, but not the other way round.
+    let mut memory = vec![0.0; Flanger::memory_len(96000.0)];
+    let mut flanger = Flanger::new(&mut memory, 96000.0).unwrap();
+    flanger.set_rate(0.0);
+    flanger.set_delay(0.002);
+    flanger.set_depth(0.0);
+    assert_eq!(flanger.set_sample_rate(SAMPLE_RATE), Ok(()));
+    assert!(measure(&mut flanger, 250.0).0 < 0.01);
+    assert!((measure(&mut flanger, 500.0).0 - 1.0).abs() < 0.01);
+
+    let mut memory = vec![0.0; Chorus::memory_len(96000.0)];
+    let mut chorus = Chorus::new(&mut memory, 96000.0).unwrap();
+    chorus.set_rate(0.0);
+    chorus.set_voices(1);
+    chorus.set_delay(0.01);
+    chorus.set_depth(0.0);
+    assert_eq!(chorus.set_sample_rate(SAMPLE_RATE), Ok(()));
+    let centre = std::f64::consts::FRAC_1_SQRT_2;
+    assert!((measure(&mut chorus, 50.0).0 - 0.5 * (1.0 - centre)).abs() < 0.01);
+
+    let mut memory = vec![0.0; Flanger::memory_len(SAMPLE_RATE)];
+    let mut flanger = Flanger::new(&mut memory, SAMPLE_RATE).unwrap();
+    assert_eq!(flanger.set_sample_rate(96000.0), Err(ModulationError::MemoryTooSmall));
+    let mut memory = vec![0.0; Chorus::memory_len(SAMPLE_RATE)];
+    let mut chorus = Chorus::new(&mut memory, SAMPLE_RATE).unwrap();
+    assert_eq!(chorus.set_sample_rate(96000.0), Err(ModulationError::MemoryTooSmall));
+}
+
+#[test]
+fn dry_passes_straight_through() {
+    let mut chorus_memory = vec![0.0; Chorus::memory_len(SAMPLE_RATE)];
+    let mut chorus = Chorus::new(&mut chorus_memory, SAMPLE_RATE).unwrap();
+    chorus.set_mix(0.0);
+    let mut flanger_memory = vec![0.0; Flanger::memory_len(SAMPLE_RATE)];
+    let mut flanger = Flanger::new(&mut flanger_memory, SAMPLE_RATE).unwrap();
+    flanger.set_mix(0.0);
+    let mut phaser = Phaser::new(SAMPLE_RATE);
+    phaser.set_mix(0.0);
+
+    for n in 0..10000 {
+        let x = ((n * 7919) % 2000) as f32 / 1000.0 - 1.0;
+        assert_eq!(chorus.tick((x, -0.5 * x)), (x, -0.5 * x));
+        assert_eq!(flanger.tick((x, -0.5 * x)), (x, -0.5 * x));
+        assert_eq!(phaser.tick((x, -0.5 * x)), (x, -0.5 * x));
+    }
+}
+
+#[test]
+fn flanger_notches_follow_the_delay() {
+    for &seconds in &[0.001, 0.0025, 0.004] {
+        let mut memory = vec![0.0; Flanger::memory_len(SAMPLE_RATE)];
+        let mut flanger = Flanger::new(&mut memory, SAMPLE_RATE).unwrap();
+        flanger.set_rate(0.0);
+        flanger.set_delay(seconds);
+        flanger.set_depth(0.0);
+
+        // Notches at odd multiples of half the delay's frequency, and peaks between them.
+        for k in 0..3 {
+            let notch = (2 * k + 1) as f64 / (2.0 * seconds as f64);
+            let (left, right) = measure(&mut flanger, notch);
+            assert!(left < 0.01 && right < 0.01, "{} s notch {} Hz: {} {}", seconds, notch, left, right);
+
+            let peak = (k + 1) as f64 / seconds as f64;
+            let (left, right) = measure(&mut flanger, peak);
+            assert!((left - 1.0).abs() < 0.01 && (right - 1.0).abs() < 0.01, "{} s peak {} Hz", seconds, peak);
+        }
+    }
+}
+
+#[test]
+fn flanger_feedback_matches_the_comb() {
+    for &feedback in &[0.7f32, -0.7] {
+        let mut memory = vec![0.0; Flanger::memory_len(SAMPLE_RATE)];
+        let mut flanger = Flanger::new(&mut memory, SAMPLE_RATE).unwrap();
+        flanger.set_rate(0.0);
+        flanger.set_delay(0.002);
+        flanger.set_depth(0.0);
+        flanger.set_feedback(feedback);
+
+        let samples = 0.002 * SAMPLE_RATE as f64;
+        for &frequency in &[125.0, 250.0, 400.0, 500.0, 750.0, 1100.0] {
+            let z = delay(frequency, samples);
+            let feedback = feedback as f64;
+            let comb = divide(z, (1.0 - feedback * z.0, -feedback * z.1));
+            let expected = magnitude((0.5 + 0.5 * comb.0, 0.5 * comb.1));
+
+            let (left, _) = measure(&mut flanger, frequency);
+            assert!((left / expected - 1.0).abs() < 0.02, "feedback {} at {} Hz: {} vs {}", feedback, frequency, left, expected);
+        }
+    }
+}
+
+#[test]
+fn through_zero_flanger_passes_its_own_delay() {
+    let mut memory = vec![0.0; Flanger::memory_len(SAMPLE_RATE)];
+    let mut flanger = Flanger::new(&mut memory, SAMPLE_RATE).unwrap();
+    flanger.set_rate(0.0);
+    flanger.set_delay(0.001);
+    flanger.set_depth(0.004);
+    flanger.set_through_zero(true);
+    // The left channel's LFO sits at the middle of the sweep, where the swept and dry paths line
+    // up, and the right's a quarter-cycle on at the top, 2 ms behind the dry path.
+    flanger.set_spread(0.25);
+
+    for &frequency in &[250.0, 750.0, 1250.0] {
+        let (left, right) = measure(&mut flanger, frequency);
+        assert!((left - 1.0).abs() < 0.01, "{} Hz: {}", frequency, left);
+        assert!(right < 0.01, "{} Hz: {}", frequency, right);
+    }
+}
+
+#[test]
+fn through_zero_feedback_only_colours_the_sweep() {
+    for &feedback in &[0.7f32, -0.7] {
+        let mut memory = vec![0.0; Flanger::memory_len(SAMPLE_RATE)];
+        let mut flanger = Flanger::new(&mut memory, SAMPLE_RATE).unwrap();
+        flanger.set_rate(0.0);
+        flanger.set_delay(0.001);
+        flanger.set_depth(0.004);
+        flanger.set_through_zero(true);
+        flanger.set_spread(0.25);
+        flanger.set_feedback(feedback);
+
+        // The dry path is a plain delay to the middle of the sweep, and the swept path a comb, at
+        // the through-zero point on the left and 2 ms behind it on the right.
+        let dry = 0.003 * SAMPLE_RATE as f64;
+        for &frequency in &[125.0, 250.0, 500.0, 750.0, 1000.0, 1250.0] {
+            let feedback = feedback as f64;
+            let comb = |samples: f64| {
+                let z = delay(frequency, samples);
+                divide(z, (1.0 - feedback * z.0, -feedback * z.1))
+            };
+            let expected = |wet: Complex| {
+                let z = delay(frequency, dry);
+                magnitude((0.5 * z.0 + 0.5 * wet.0, 0.5 * z.1 + 0.5 * wet.1))
+            };
+            let (left, right) = measure(&mut flanger, frequency);
+            let (expected_left, expected_right) = (expected(comb(dry)), expected(comb(dry + 96.0)));
+
+            assert!((left / expected_left - 1.0).abs() < 0.02, "feedback {} at {} Hz: left {} vs {}", feedback, frequency, left, expected_left);
+            assert!((right / expected_right - 1.0).abs() < 0.02, "feedback {} at {} Hz: right {} vs {}", feedback, frequency, right, expected_right);
+        }
+    }
+}
+
+#[test]
+fn flanger_notch_sweeps_with_the_lfo() {
+    let mut memory = vec![0.0; Flanger::memory_len(SAMPLE_RATE)];
+    let mut flanger = Flanger::new(&mut memory, SAMPLE_RATE).unwrap();
+    flanger.set_rate(0.5);
+    flanger.set_delay(0.0005);
+    flanger.set_depth(0.004);
+
+    // The first notch crosses 200 Hz when the delay passes 2.5 ms, mid-sweep, which the rising LFO
+    // reaches at the start of each cycle and the falling one halfway through.
+    let envelope = envelope(&mut flanger, 200.0, 2.5);
+    for &expected in &[1.0, 2.0] {
+        let (time, level) = quietest(&envelope, expected - 0.4, expected + 0.4);
+        assert!((time - expected).abs() < 0.02, "notch at {} s, expected {} s", time, expected);
+        assert!(level < 0.1, "level {} at {} s", level, time);
+    }
+
+    // At the shortest delay the notch is far above.
+    assert!(envelope[150] > 0.9, "{}", envelope[150]);
+}
+
+#[test]
+fn chorus_voices_land_where_panned() {
+    for &seconds in &[0.01, 0.02] {
+        let mut memory = vec![0.0; Chorus::memory_len(SAMPLE_RATE)];
+        let mut chorus = Chorus::new(&mut memory, SAMPLE_RATE).unwrap();
+        chorus.set_rate(0.0);
+        chorus.set_voices(4);
+        chorus.set_delay(seconds);
+        chorus.set_depth(0.002);
+        chorus.set_spread(1.0);
+
+        // The frozen LFOs hold the voices at the middle, top, middle and bottom of the swing, and
+        // they're panned from hard left to hard right.
+        let delays = [0.5, 1.0, 0.5, 0.0].map(|s: f64| (seconds as f64 + 0.002 * s) * SAMPLE_RATE as f64);
+        let positions = [-1.0, -1.0 / 3.0, 1.0 / 3.0, 1.0];
+
+        for &frequency in &[50.0, 110.0, 230.0, 470.0] {
+            let (mut left, mut right) = ((0.5, 0.0), (0.5, 0.0));
+            for (&samples, &position) in delays.iter().zip(positions.iter()) {
+                let z = delay(frequency, samples);
+                let angle = 0.25 * PI * (position + 1.0);
+                let gain = 0.5 / 2.0;
+                left = (left.0 + gain * angle.cos() * z.0, left.1 + gain * angle.cos() * z.1);
+                right = (right.0 + gain * angle.sin() * z.0, right.1 + gain * angle.sin() * z.1);
+            }
+
+            let measured = measure(&mut chorus, frequency);
+            assert!((measured.0 - magnitude(left)).abs() < 0.01, "{} s {} Hz left", seconds, frequency);
+            assert!((measured.1 - magnitude(right)).abs() < 0.01, "{} s {} Hz right", seconds, frequency);
+        }
+    }
+}
+
+#[test]
+fn single_voice_chorus_is_a_comb() {
+    let mut memory = vec![0.0; Chorus::memory_len(SAMPLE_RATE)];
+    let mut chorus = Chorus::new(&mut memory, SAMPLE_RATE).unwrap();
+    chorus.set_rate(0.0);
+    chorus.set_voices(1);
+    chorus.set_delay(0.01);
+    chorus.set_depth(0.0);
+    chorus.set_mix(1.0);
+
+    // Only the voice, centred: a pure delay at equal-power level either side.
+    let (left, right) = measure(&mut chorus, 330.0);
+    let centre = std::f64::consts::FRAC_1_SQRT_2;
+    assert!((left - centre).abs() < 0.01 && (right - centre).abs() < 0.01, "{} {}", left, right);
+
+    chorus.set_mix(0.5);
+    let notch = measure(&mut chorus, 50.0).0;
+    let peak = measure(&mut chorus, 100.0).0;
+    assert!((notch - 0.5 * (1.0 - centre)).abs() < 0.01, "notch {}", notch);
+    assert!((peak - 0.5 * (1.0 + centre)).abs() < 0.01, "peak {}", peak);
+}
+
+/// The phase of the phaser's stages at `frequency`, with their corner at `corner`.
+fn stage_phase(frequency: f64, corner: f64) -> f64 {
+    let rate = SAMPLE_RATE as f64;
+    -2.0 * ((PI * frequency / rate).tan() / (PI * corner / rate).tan()).atan()
+}
+
+#[test]
+fn phaser_notches_follow_the_corner() {
+    for &stages in &[4, 6, 8, 12] {
+        for &corner in &[300.0, 1000.0, 4000.0] {
+            let mut phaser = Phaser::new(SAMPLE_RATE);
+            phaser.set_rate(0.0);
+            phaser.set_depth(0.0);
+            phaser.set_stages(stages);
+            phaser.set_frequency(corner as f32);
+
+            // A notch for every two stages, where the chain turns through an odd multiple of pi.
+            let rate = SAMPLE_RATE as f64;
+            for k in 0..stages / 2 {
+                let turn = PI * (2 * k + 1) as f64 / (2 * stages) as f64;
+                let notch = rate / PI * ((PI * corner / rate).tan() * turn.tan()).atan();
+                if notch > 20000.0 {
+                    continue;
+                }
+                let (left, right) = measure(&mut phaser, notch);
+                assert!(left < 0.01 && right < 0.01, "{} stages, {} Hz: notch {} Hz {} {}", stages, corner, notch, left, right);
+            }
+
+            for &frequency in &[100.0, 700.0, 2500.0] {
+                let expected = (0.5 * stages as f64 * stage_phase(frequency, corner)).cos().abs();
+                let (left, _) = measure(&mut phaser, frequency);
+                assert!((left - expected).abs() < 0.01, "{} stages, {} Hz at {} Hz: {} vs {}", stages, corner, frequency, left, expected);
+            }
+        }
+    }
+}
+
+#[test]
+fn phaser_feedback_matches_the_loop() {
+    let mut phaser = Phaser::new(SAMPLE_RATE);
+    phaser.set_rate(0.0);
+    phaser.set_depth(0.0);
+    phaser.set_stages(6);
+    phaser.set_frequency(1000.0);
+    phaser.set_feedback(0.6);
+
+    for &frequency in &[150.0, 400.0, 1000.0, 2200.0, 6000.0] {
+        // The chain, fed back through a sample's delay.
+        let phase = 6.0 * stage_phase(frequency, 1000.0);
+        let chain = (phase.cos(), phase.sin());
+        let round = multiply(chain, delay(frequency, 1.0));
+        let wet = divide(chain, (1.0 - 0.6 * round.0, -0.6 * round.1));
+        let expected = magnitude((0.5 + 0.5 * wet.0, 0.5 * wet.1));
+
+        let (left, _) = measure(&mut phaser, frequency);
+        assert!((left / expected - 1.0).abs() < 0.02, "{} Hz: {} vs {}", frequency, left, expected);
+    }
+}
+
+#[test]
+fn phaser_notch_sweeps_with_the_lfo() {
+    let mut phaser = Phaser::new(SAMPLE_RATE);
+    phaser.set_rate(0.5);
+    phaser.set_stages(4);
+    phaser.set_frequency(1000.0);
+    phaser.set_depth(1.0);
+
+    // Probe the first notch for the centre corner, which the LFO crosses at the start and middle
+    // of each cycle.
+    let rate = SAMPLE_RATE as f64;
+    let probe = rate / PI * ((PI * 1000.0 / rate).tan() * (PI / 8.0).tan()).atan();
+    let envelope = envelope(&mut phaser, probe, 2.5);
+    for &expected in &[1.0, 2.0] {
+        let (time, level) = quietest(&envelope, expected - 0.4, expected + 0.4);
+        assert!((time - expected).abs() < 0.02, "notch at {} s, expected {} s", time, expected);
+        assert!(level < 0.1, "level {} at {} s", level, time);
+    }
+
+    // An octave up or down the notch is well away.
+    assert!(envelope[50] > 0.5 && envelope[150] > 0.5, "{} {}", envelope[50], envelope[150]);
+}
+
+#[test]
+fn stable_with_full_feedback_and_moving_controls() {
+    let mut memory = vec![0.0; Flanger::memory_len(SAMPLE_RATE)];
+    let mut flanger = Flanger::new(&mut memory, SAMPLE_RATE).unwrap();
+    flanger.set_rate(3.0);
+    flanger.set_feedback(1.0);
+    flanger.set_through_zero(true);
+    let mut phaser = Phaser::new(SAMPLE_RATE);
+    phaser.set_rate(3.0);
+    phaser.set_stages(12);
+    phaser.set_depth(4.0);
+    phaser.set_feedback(-1.0);
+
+    let mut seed = 1u32;
+    let mut peak = 0.0f32;
+    for n in 0..48000 * 3 {
+        if n % 4800 == 0 {
+            flanger.set_depth((n / 4800 % 7) as f32 * 0.003);
+            phaser.set_frequency(100.0 + (n / 4800 % 5) as f32 * 2000.0);
+        }
+        seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+        let x = (seed >> 8) as f32 / 8_388_608.0 - 1.0;
+
+        for (l, r) in [flanger.tick((x, x)), phaser.tick((x, x))] {
+            assert!(l.is_finite() && r.is_finite(), "sample {}", n);
+            peak = peak.max(l.abs()).max(r.abs());
+        }
+    }
+    assert!(peak < 40.0, "peak {}", peak);
+}
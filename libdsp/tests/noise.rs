@@ -0,0 +1,84 @@
+use libdsp::noise::{BrownNoise, HoldMode, PinkNoise, SampleAndHold, WhiteNoise, XorShift};
+use libdsp::traits::MonoGenerator;
+
+fn render<G: MonoGenerator>(mut generator: G, length: usize) -> Vec<f32> {
+    let mut buffer = vec![0.0; length];
+    generator.fill(&mut buffer);
+    buffer
+}
+
+fn rms(signal: &[f32]) -> f32 {
+    (signal.iter().map(|x| x * x).sum::<f32>() / signal.len() as f32).sqrt()
+}
+
+#[test]
+fn same_seed_same_noise() {
+    assert_eq!(render(WhiteNoise::new(7), 4800), render(WhiteNoise::new(7), 4800));
+    assert_eq!(render(PinkNoise::new(7), 4800), render(PinkNoise::new(7), 4800));
+    assert_eq!(render(BrownNoise::new(7), 4800), render(BrownNoise::new(7), 4800));
+
+    assert_ne!(render(WhiteNoise::new(7), 4800), render(WhiteNoise::new(8), 4800));
+    assert_ne!(render(PinkNoise::new(7), 4800), render(PinkNoise::new(8), 4800));
+}
+
+#[test]
+fn zero_seed_still_makes_noise() {
+    let mut rng = XorShift::new(0);
+    assert!((0..100).map(|_| rng.next_u32()).any(|x| x != 0));
+    assert!(rms(&render(WhiteNoise::new(0), 4800)) > 0.5);
+}
+
+#[test]
+fn noise_stays_in_range() {
+    for seed in 1..10 {
+        let white = render(WhiteNoise::new(seed), 48000);
+        assert!(white.iter().all(|&x| (-1.0..1.0).contains(&x)));
+        assert!((rms(&white) - 1.0 / 3f32.sqrt()).abs() < 0.01, "white RMS {}", rms(&white));
+
+        let pink = render(PinkNoise::new(seed), 48000);
+        assert!(pink.iter().all(|&x| (-1.0..1.0).contains(&x)));
+
+        // Long enough for the brown noise's slowest wanderings to average out.
+        let brown = render(BrownNoise::new(seed), 480000);
+        assert!((rms(&brown) - 0.25).abs() < 0.05, "brown RMS {}", rms(&brown));
+    }
+}
+
+#[test]
+fn sample_and_hold_changes_on_the_clock() {
+    // 375 Hz is exactly 128 samples per cycle at 48 kHz.
+    let output = render(SampleAndHold::new(375.0, 48000.0, 3), 128 * 10);
+
+    let changes: Vec<usize> = (1..output.len()).filter(|&n| output[n] != output[n - 1]).collect();
+    assert_eq!(changes, (1..10).map(|n| n * 128).collect::<Vec<_>>());
+}
+
+#[test]
+fn sample_and_hold_at_sample_rate_changes_every_sample() {
+    let output = render(SampleAndHold::new(96000.0, 48000.0, 3), 1000);
+    assert!(output.windows(2).all(|w| w[0] != w[1]));
+}
+
+#[test]
+fn trigger_takes_a_new_value() {
+    let mut sh: SampleAndHold<f32> = SampleAndHold::new(0.0, 48000.0, 3);
+    let held = sh.tick();
+    assert!((0..1000).all(|_| sh.tick() == held));
+
+    sh.trigger();
+    assert_ne!(sh.value(), held);
+    assert_eq!(sh.tick(), sh.value());
+}
+
+#[test]
+fn random_walk_takes_bounded_steps() {
+    let mut sh = SampleAndHold::new(48000.0, 48000.0, 5);
+    sh.set_mode(HoldMode::Walk(0.1));
+
+    let output = render(sh, 48000);
+    assert!(output.iter().all(|&x| (-1.0..=1.0).contains(&x)));
+    assert!(output.windows(2).all(|w| (w[1] - w[0]).abs() <= 0.1 + 1e-6));
+    // It should wander across most of the range given enough steps.
+    let (min, max) = output.iter().fold((1.0f32, -1.0f32), |(lo, hi), &x| (lo.min(x), hi.max(x)));
+    assert!(max - min > 1.0, "walked only from {} to {}", min, max);
+}
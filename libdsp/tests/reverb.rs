@@ -0,0 +1,162 @@
+use libdsp::reverb::{Reverb, ReverbError};
+use libdsp::traits::StereoProcessor;
+
+const SAMPLE_RATES: [f32; 3] = [44100.0, 48000.0, 96000.0];
+
+fn memory(sample_rate: f32) -> Vec<f32> {
+    vec![0.0; Reverb::memory_len(sample_rate)]
+}
+
+/// The reverb's response to an impulse in both channels, with only the wet signal out.
+fn impulse_response(reverb: &mut Reverb, length: usize) -> (Vec<f32>, Vec<f32>) {
+    reverb.set_mix(1.0);
+    (0..length)
+        .map(|n| {
+            let x = if n == 0 { 1.0 } else { 0.0 };
+            reverb.tick((x, x))
+        })
+        .unzip()
+}
+
+/// Measures the time a response takes to fall by 60 dB, from the slope of its Schroeder
+/// backward-integrated energy between -5 and -25 dB.
+fn rt60(response: &[f32], sample_rate: f32) -> f32 {
+    let mut energy = vec![0.0f64; response.len()];
+    let mut total = 0.0;
+    for (n, &x) in response.iter().enumerate().rev() {
+        total += (x as f64) * (x as f64);
+        energy[n] = total;
+    }
+    let db: Vec<f64> = energy.iter().map(|&e| 10.0 * (e / total).log10()).collect();
+
+    let start = db.iter().position(|&d| d < -5.0).unwrap();
+    let end = db.iter().position(|&d| d < -25.0).unwrap();
+    let slope = (db[end] - db[start]) / (end - start) as f64;
+
+    (-60.0 / slope) as f32 / sample_rate
+}
+
+#[test]
+fn memory_must_fit() {
+    let mut small = vec![0.0; Reverb::memory_len(48000.0) - 1];
+    assert_eq!(Reverb::new(&mut small, 48000.0).err(), Some(ReverbError::MemoryTooSmall));
+
+    // A rate needs proportionally more memory.
+    let ratio = Reverb::memory_len(96000.0) as f32 / Reverb::memory_len(48000.0) as f32;
+    assert!((ratio - 2.0).abs() < 0.01, "{}", ratio);
+}
+
+#[test]
+fn builds_at_low_sample_rates() {
+    // The shortest diffuser is under a sample long here.
+    let mut memory = memory(200.0);
+    let mut reverb = Reverb::new(&mut memory, 200.0).unwrap();
+    let (left, right) = impulse_response(&mut reverb, 1000);
+    assert!(left.iter().chain(right.iter()).all(|x| x.is_finite()));
+}
+
+#[test]
+fn decay_time_matches_setting() {
+    for &sample_rate in SAMPLE_RATES.iter() {
+        for &(size, decay) in &[(0.3, 0.8), (0.7, 2.0), (1.0, 4.0)] {
+            let mut memory = memory(sample_rate);
+            let mut reverb = Reverb::new(&mut memory, sample_rate).unwrap();
+            reverb.set_size(size);
+            reverb.set_decay(decay);
+            reverb.set_damping(sample_rate);
+
+            let (left, right) = impulse_response(&mut reverb, (sample_rate * decay) as usize);
+            for response in &[left, right] {
+                let measured = rt60(response, sample_rate);
+                assert!(
+                    (measured / decay - 1.0).abs() < 0.05,
+                    "{} Hz, size {}: decay {} measured {}",
+                    sample_rate,
+                    size,
+                    decay,
+                    measured
+                );
+            }
+        }
+    }
+}
+
+/// Zero crossings per second over a stretch of signal, a rough measure of its brightness.
+fn crossing_rate(signal: &[f32], sample_rate: f32) -> f32 {
+    let crossings = signal.windows(2).filter(|w| (w[0] < 0.0) != (w[1] < 0.0)).count();
+    crossings as f32 * sample_rate / signal.len() as f32
+}
+
+#[test]
+fn damping_darkens_the_tail() {
+    let tail = |damping: f32| {
+        let mut memory = memory(48000.0);
+        let mut reverb = Reverb::new(&mut memory, 48000.0).unwrap();
+        reverb.set_decay(3.0);
+        reverb.set_damping(damping);
+        let (left, _) = impulse_response(&mut reverb, 48000);
+        crossing_rate(&left[24000..], 48000.0)
+    };
+
+    let bright = tail(24000.0);
+    let dark = tail(1000.0);
+    assert!(dark < 0.25 * bright, "dark {} vs bright {}", dark, bright);
+}
+
+#[test]
+fn pre_delay_holds_back_the_reverb() {
+    let mut memory = memory(48000.0);
+    let mut reverb = Reverb::new(&mut memory, 48000.0).unwrap();
+    reverb.set_pre_delay(0.1);
+
+    let (left, right) = impulse_response(&mut reverb, 24000);
+    assert!(left[..4800].iter().chain(right[..4800].iter()).all(|&x| x == 0.0));
+    assert!(left[4800..].iter().any(|&x| x != 0.0));
+}
+
+#[test]
+fn dry_passes_straight_through() {
+    let mut memory = memory(48000.0);
+    let mut reverb = Reverb::new(&mut memory, 48000.0).unwrap();
+    reverb.set_mix(0.0);
+
+    for n in 0..10000 {
+        let x = ((n * 7919) % 2000) as f32 / 1000.0 - 1.0;
+        assert_eq!(reverb.tick((x, -0.5 * x)), (x, -0.5 * x));
+    }
+}
+
+#[test]
+fn channels_are_decorrelated() {
+    let mut memory = memory(48000.0);
+    let mut reverb = Reverb::new(&mut memory, 48000.0).unwrap();
+    let (left, right) = impulse_response(&mut reverb, 48000);
+
+    let dot = |a: &[f32], b: &[f32]| a.iter().zip(b).map(|(&x, &y)| x as f64 * y as f64).sum::<f64>();
+    let correlation = dot(&left, &right) / (dot(&left, &left) * dot(&right, &right)).sqrt();
+    assert!(correlation.abs() < 0.2, "correlation {}", correlation);
+}
+
+#[test]
+fn stable_with_long_decay_and_moving_controls() {
+    let mut memory = memory(48000.0);
+    let mut reverb = Reverb::new(&mut memory, 48000.0).unwrap();
+    reverb.set_decay(100.0);
+    reverb.set_modulation(1.0);
+    reverb.set_damping(24000.0);
+
+    let mut seed = 1u32;
+    let mut peak = 0.0f32;
+    for n in 0..48000 * 5 {
+        if n % 4800 == 0 {
+            reverb.set_size((n / 4800 % 10) as f32 / 9.0);
+        }
+        seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+        let x = if n < 48000 { (seed >> 8) as f32 / 8_388_608.0 - 1.0 } else { 0.0 };
+
+        let (l, r) = reverb.tick((x, x));
+        assert!(l.is_finite() && r.is_finite(), "sample {}", n);
+        peak = peak.max(l.abs()).max(r.abs());
+    }
+    assert!(peak < 20.0, "peak {}", peak);
+}
@@ -0,0 +1,109 @@
+use libdsp::biquad::{Biquad, BiquadKind};
+use libdsp::distortion::{Distortion, DistortionKind};
+use libdsp::filters::LadderFilter;
+use libdsp::oscillators::{Oscillator, Oscillator64, OscillatorMode};
+use libdsp::reverb::Reverb;
+use libdsp::traits::{MonoGenerator, MonoProcessor, StereoProcessor};
+
+const MODES: [OscillatorMode; 4] = [
+    OscillatorMode::Sine,
+    OscillatorMode::Saw,
+    OscillatorMode::Square,
+    OscillatorMode::Triangle,
+];
+
+#[test]
+fn f64_oscillator_fill_matches_tick() {
+    for &mode in MODES.iter() {
+        let mut ticked = Oscillator64::new(mode, 440.0, 48000.0);
+        let mut filled = Oscillator64::new(mode, 440.0, 48000.0);
+
+        let mut block = [0.0; 37];
+        for _ in 0..100 {
+            filled.fill(&mut block);
+            for &x in block.iter() {
+                assert_eq!(x, ticked.tick_poly_blep());
+            }
+        }
+    }
+}
+
+/// The f32 oscillator should track the f64 reference over a long render. Both step the same u32
+/// phase, so they can't drift apart: what's left is the f32 rounding of that phase, which only
+/// shows at the band-limited steps, and the fast sine approximation.
+#[test]
+fn f32_oscillator_tracks_f64_reference() {
+    for &mode in MODES.iter() {
+        let mut single = Oscillator::new(mode, 220.0, 48000.0);
+        let mut double = Oscillator64::new(mode, 220.0, 48000.0);
+
+        let mut worst: f64 = 0.0;
+        for _ in 0..480_000 {
+            let (x, y) = (single.tick_poly_blep() as f64, double.tick_poly_blep());
+            worst = worst.max((x - y).abs());
+        }
+
+        let bound = if mode == OscillatorMode::Sine { 1.5e-3 } else { 5e-5 };
+        assert!(worst < bound, "worst difference {}", worst);
+    }
+}
+
+/// Runs the same input through the f32 and f64 builds of a processor and returns their largest
+/// difference.
+fn worst_difference<A, B>(mut single: A, mut double: B) -> f64
+where
+    A: MonoProcessor<f32>,
+    B: MonoProcessor<f64>,
+{
+    let mut worst: f64 = 0.0;
+    for n in 0..48_000 {
+        let x = 0.8 * (2.0 * std::f64::consts::PI * 110.0 * n as f64 / 48000.0).sin();
+        let (a, b) = (single.tick(x as f32) as f64, double.tick(x));
+        worst = worst.max((a - b).abs());
+    }
+
+    worst
+}
+
+/// The processors built on `Sample` should give the same results in either precision, give or
+/// take the f32 rounding.
+#[test]
+fn f32_processors_track_f64_reference() {
+    let ladder = worst_difference(
+        LadderFilter::<f32>::new(800.0, 0.6, 48000.0),
+        LadderFilter::<f64>::new(800.0, 0.6, 48000.0),
+    );
+    assert!(ladder < 1e-3, "ladder worst difference {}", ladder);
+
+    let mut single = Biquad::<f32>::new(BiquadKind::Peaking, 1000.0, 2.0, 48000.0);
+    let mut double = Biquad::<f64>::new(BiquadKind::Peaking, 1000.0, 2.0, 48000.0);
+    single.set_gain(6.0);
+    double.set_gain(6.0);
+    let biquad = worst_difference(single, double);
+    assert!(biquad < 1e-4, "biquad worst difference {}", biquad);
+
+    for &kind in [DistortionKind::Tanh, DistortionKind::Tube, DistortionKind::Wavefolder].iter() {
+        let mut single = Distortion::<f32>::new(kind, 48000.0);
+        let mut double = Distortion::<f64>::new(kind, 48000.0);
+        single.set_drive(4.0);
+        double.set_drive(4.0);
+        let distortion = worst_difference(single, double);
+        assert!(distortion < 1e-3, "distortion worst difference {}", distortion);
+    }
+}
+
+#[test]
+fn f32_reverb_tracks_f64_reference() {
+    let mut single_memory = vec![0.0; Reverb::<f32>::memory_len(48000.0)];
+    let mut double_memory = vec![0.0; Reverb::<f64>::memory_len(48000.0)];
+    let mut single = Reverb::<f32>::new(&mut single_memory, 48000.0).unwrap();
+    let mut double = Reverb::<f64>::new(&mut double_memory, 48000.0).unwrap();
+
+    let mut worst: f64 = 0.0;
+    for n in 0..48_000 {
+        let x = if n == 0 { 1.0 } else { 0.0 };
+        let (a, b) = (single.tick((x as f32, 0.0)), double.tick((x, 0.0)));
+        worst = worst.max((a.0 as f64 - b.0).abs()).max((a.1 as f64 - b.1).abs());
+    }
+    assert!(worst < 1e-4, "worst difference {}", worst);
+}
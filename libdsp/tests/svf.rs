@@ -0,0 +1,93 @@
+use libdsp::filters::{StateVariableFilter, SvfOutput};
+use std::f32::consts::PI;
+
+const SAMPLE_RATE: f32 = 48000.0;
+const CUTOFF: f32 = 1000.0;
+const Q: f32 = std::f32::consts::FRAC_1_SQRT_2;
+
+/// Measures the steady-state gain of one filter output for a sine at `frequency`, by correlating
+/// the output against quadrature sinusoids over a whole number of cycles.
+fn measure_gain(frequency: f32, output: fn(&SvfOutput) -> f32) -> f32 {
+    let mut filter = StateVariableFilter::new(CUTOFF, 0.0, SAMPLE_RATE);
+    filter.set_q(Q);
+
+    let settle = SAMPLE_RATE as usize / 2;
+    let measure = SAMPLE_RATE as usize / 2;
+    let (mut in_phase, mut quadrature) = (0.0f64, 0.0f64);
+    for i in 0..settle + measure {
+        let phase = 2.0 * PI * frequency * i as f32 / SAMPLE_RATE;
+        let y = output(&filter.tick_all(phase.sin()));
+        if i >= settle {
+            in_phase += (y * phase.sin()) as f64;
+            quadrature += (y * phase.cos()) as f64;
+        }
+    }
+
+    (2.0 * (in_phase * in_phase + quadrature * quadrature).sqrt() / measure as f64) as f32
+}
+
+/// Magnitudes of the prototype responses (low, high, band, notch) at `frequency`, using the
+/// bilinear frequency warping the TPT integrator applies.
+fn analytic_gains(frequency: f32) -> (f32, f32, f32, f32) {
+    let w = (PI * frequency / SAMPLE_RATE).tan() / (PI * CUTOFF / SAMPLE_RATE).tan();
+    let k = 1.0 / Q;
+    let denominator = ((1.0 - w * w).powi(2) + (k * w).powi(2)).sqrt();
+
+    (
+        1.0 / denominator,
+        w * w / denominator,
+        w / denominator,
+        (1.0 - w * w).abs() / denominator,
+    )
+}
+
+fn assert_close_db(measured: f32, expected: f32, tolerance_db: f32) {
+    let floor = 1e-4;
+    let difference = 20.0 * (measured.max(floor) / expected.max(floor)).log10();
+    assert!(
+        difference.abs() < tolerance_db,
+        "measured {} expected {} ({} dB off)",
+        measured,
+        expected,
+        difference
+    );
+}
+
+#[test]
+fn responses_match_analytic_magnitudes() {
+    for &frequency in &[100.0, 500.0, 1000.0, 2000.0, 8000.0] {
+        let (low, high, band, notch) = analytic_gains(frequency);
+
+        assert_close_db(measure_gain(frequency, |o| o.lowpass), low, 0.1);
+        assert_close_db(measure_gain(frequency, |o| o.highpass), high, 0.1);
+        assert_close_db(measure_gain(frequency, |o| o.bandpass), band, 0.1);
+        if frequency != CUTOFF {
+            assert_close_db(measure_gain(frequency, |o| o.notch), notch, 0.1);
+        }
+    }
+}
+
+#[test]
+fn cutoff_point_gains() {
+    // At the cutoff the low and high passes sit at Q, the band pass peaks, and the notch nulls.
+    assert_close_db(measure_gain(CUTOFF, |o| o.lowpass), Q, 0.1);
+    assert_close_db(measure_gain(CUTOFF, |o| o.highpass), Q, 0.1);
+    assert!(measure_gain(CUTOFF, |o| o.notch) < 0.01);
+}
+
+#[test]
+fn stable_under_audio_rate_cutoff_modulation() {
+    let mut filter = StateVariableFilter::new(CUTOFF, 0.95, SAMPLE_RATE);
+
+    for i in 0..SAMPLE_RATE as usize * 2 {
+        let t = i as f32 / SAMPLE_RATE;
+        let modulation = (2.0 * PI * 3000.0 * t).sin();
+        filter.set_cutoff(2000.0 + 1900.0 * modulation);
+
+        let x = if (i / 40) % 2 == 0 { 1.0 } else { -1.0 };
+        let y = filter.tick_all(x);
+        for out in &[y.lowpass, y.highpass, y.bandpass, y.notch] {
+            assert!(out.is_finite() && out.abs() < 100.0, "output blew up at sample {}", i);
+        }
+    }
+}
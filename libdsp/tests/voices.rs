@@ -0,0 +1,211 @@
+use libdsp::voices::{NotePriority, StealMode, Voice, VoiceManager, VoiceMode};
+
+/// A voice that records what it's told. It stays active after a note off, as if releasing, until
+/// `finish` is called.
+#[derive(Clone, Copy, Default)]
+struct TestVoice {
+    note: Option<u8>,
+    gate: bool,
+    active: bool,
+    level: f32,
+    note_ons: usize,
+}
+
+impl TestVoice {
+    fn finish(&mut self) {
+        if !self.gate {
+            self.active = false;
+        }
+    }
+}
+
+impl Voice for TestVoice {
+    fn note_on(&mut self, note: u8, _velocity: u8) {
+        self.note = Some(note);
+        self.gate = true;
+        self.active = true;
+        self.note_ons += 1;
+    }
+
+    fn note_off(&mut self) {
+        self.gate = false;
+    }
+
+    fn is_active(&self) -> bool {
+        self.active
+    }
+
+    fn level(&self) -> f32 {
+        self.level
+    }
+}
+
+fn manager<const N: usize>(mode: VoiceMode) -> VoiceManager<TestVoice, N> {
+    VoiceManager::new([TestVoice::default(); N], mode)
+}
+
+/// The note the mono voice is sounding with its gate on, if any.
+fn mono_note(manager: &VoiceManager<TestVoice, 4>) -> Option<u8> {
+    let voice = &manager.voices()[0];
+    if voice.gate {
+        voice.note
+    } else {
+        None
+    }
+}
+
+#[test]
+fn mono_last_note_priority() {
+    let mut manager = manager::<4>(VoiceMode::Mono(NotePriority::Last));
+    for &note in &[60, 64, 67] {
+        manager.note_on(note, 100);
+        assert_eq!(mono_note(&manager), Some(note));
+    }
+
+    // Releasing the newest note falls back to the one held before it, and releasing one that isn't
+    // sounding changes nothing.
+    manager.note_off(67);
+    assert_eq!(mono_note(&manager), Some(64));
+    manager.note_off(60);
+    assert_eq!(mono_note(&manager), Some(64));
+    manager.note_off(64);
+    assert_eq!(mono_note(&manager), None);
+
+    // Only the first voice is ever used.
+    assert!(manager.voices()[1..].iter().all(|v| v.note_ons == 0));
+}
+
+#[test]
+fn mono_lowest_note_priority() {
+    let mut manager = manager::<4>(VoiceMode::Mono(NotePriority::Lowest));
+    manager.note_on(64, 100);
+    assert_eq!(mono_note(&manager), Some(64));
+    manager.note_on(60, 100);
+    assert_eq!(mono_note(&manager), Some(60));
+    manager.note_on(67, 100);
+    assert_eq!(mono_note(&manager), Some(60));
+
+    manager.note_off(60);
+    assert_eq!(mono_note(&manager), Some(64));
+    manager.note_off(64);
+    assert_eq!(mono_note(&manager), Some(67));
+}
+
+#[test]
+fn mono_highest_note_priority() {
+    let mut manager = manager::<4>(VoiceMode::Mono(NotePriority::Highest));
+    manager.note_on(60, 100);
+    manager.note_on(67, 100);
+    manager.note_on(64, 100);
+    assert_eq!(mono_note(&manager), Some(67));
+
+    manager.note_off(67);
+    assert_eq!(mono_note(&manager), Some(64));
+    manager.note_off(64);
+    assert_eq!(mono_note(&manager), Some(60));
+}
+
+#[test]
+fn poly_steals_the_oldest_voice() {
+    let mut manager = manager::<3>(VoiceMode::Poly(StealMode::Oldest));
+    for &note in &[60, 62, 64] {
+        manager.note_on(note, 100);
+    }
+    assert_eq!([manager.note_of(0), manager.note_of(1), manager.note_of(2)], [Some(60), Some(62), Some(64)]);
+
+    manager.note_on(65, 100);
+    assert_eq!(manager.note_of(0), Some(65));
+
+    // A voice whose key is up is taken before an older one that's still held.
+    manager.note_off(62);
+    manager.note_on(67, 100);
+    assert_eq!(manager.note_of(1), Some(67));
+    assert_eq!(manager.note_of(2), Some(64));
+}
+
+#[test]
+fn poly_steals_the_quietest_voice() {
+    let mut manager = manager::<3>(VoiceMode::Poly(StealMode::Quietest));
+    for &note in &[60, 62, 64] {
+        manager.note_on(note, 100);
+    }
+    for (voice, &level) in manager.voices_mut().iter_mut().zip(&[0.5, 0.2, 0.9]) {
+        voice.level = level;
+    }
+
+    manager.note_on(65, 100);
+    assert_eq!(manager.note_of(1), Some(65));
+    assert_eq!(manager.note_of(0), Some(60));
+    assert_eq!(manager.note_of(2), Some(64));
+}
+
+#[test]
+fn poly_prefers_free_voices() {
+    let mut manager = manager::<3>(VoiceMode::Poly(StealMode::Oldest));
+    manager.note_on(60, 100);
+    manager.note_on(62, 100);
+    manager.note_off(60);
+
+    // Voice 0 is still releasing, so the untouched voice 2 is used.
+    manager.note_on(64, 100);
+    assert_eq!(manager.note_of(2), Some(64));
+
+    // Once it's finished, voice 0 is free again.
+    manager.voices_mut()[0].finish();
+    manager.note_on(65, 100);
+    assert_eq!(manager.note_of(0), Some(65));
+}
+
+#[test]
+fn repeated_note_retriggers_its_voice() {
+    let mut manager = manager::<4>(VoiceMode::Poly(StealMode::Oldest));
+    manager.note_on(60, 100);
+    manager.note_on(60, 100);
+    assert_eq!(manager.voices()[0].note_ons, 2);
+
+    // Likewise while the note's only held by the pedal.
+    manager.set_sustain(true);
+    manager.note_off(60);
+    manager.note_on(60, 100);
+    assert_eq!(manager.voices()[0].note_ons, 3);
+
+    assert!(manager.voices()[1..].iter().all(|v| v.note_ons == 0));
+}
+
+#[test]
+fn sustain_pedal_holds_poly_notes() {
+    let mut manager = manager::<4>(VoiceMode::Poly(StealMode::Oldest));
+    manager.note_on(60, 100);
+    manager.note_on(64, 100);
+    manager.set_sustain(true);
+
+    manager.note_off(60);
+    assert!(manager.voices()[0].gate);
+
+    // A note played with the pedal down is held by it too.
+    manager.note_on(67, 100);
+    manager.note_off(67);
+    assert!(manager.voices()[2].gate);
+
+    // Lifting the pedal releases the notes whose keys are up, but not those still held.
+    manager.set_sustain(false);
+    assert!(!manager.voices()[0].gate);
+    assert!(manager.voices()[1].gate);
+    assert!(!manager.voices()[2].gate);
+
+    // With the pedal up, a note off releases straight away.
+    manager.note_off(64);
+    assert!(!manager.voices()[1].gate);
+}
+
+#[test]
+fn sustain_pedal_holds_the_mono_note() {
+    let mut manager = manager::<4>(VoiceMode::Mono(NotePriority::Last));
+    manager.set_sustain(true);
+    manager.note_on(60, 100);
+    manager.note_off(60);
+    assert!(manager.voices()[0].gate);
+
+    manager.set_sustain(false);
+    assert!(!manager.voices()[0].gate);
+}
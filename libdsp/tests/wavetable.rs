@@ -0,0 +1,197 @@
+use libdsp::traits::MonoGenerator;
+use libdsp::wavetable::{Wavetable, WavetableError, WavetableOscillator};
+use std::f64::consts::PI;
+
+const TABLE_SIZE: usize = 256;
+
+/// A saw frame followed by a square frame, each with every harmonic up to Nyquist.
+fn source() -> Vec<f32> {
+    let saw = (0..TABLE_SIZE).map(|i| 2.0 * i as f32 / TABLE_SIZE as f32 - 1.0);
+    let square = (0..TABLE_SIZE).map(|i| if i < TABLE_SIZE / 2 { 1.0 } else { -1.0 });
+    saw.chain(square).collect()
+}
+
+/// The amplitude of each harmonic of a single-cycle table, by direct DFT.
+fn harmonics(table: &[f32]) -> Vec<f64> {
+    let n = table.len();
+    (0..=n / 2)
+        .map(|k| {
+            let (mut re, mut im) = (0.0, 0.0);
+            for (i, &x) in table.iter().enumerate() {
+                let phase = 2.0 * PI * (k * i) as f64 / n as f64;
+                re += x as f64 * phase.cos();
+                im -= x as f64 * phase.sin();
+            }
+            2.0 * re.hypot(im) / n as f64
+        })
+        .collect()
+}
+
+fn build<'a>(storage: &'a mut [f32]) -> Wavetable<'a> {
+    let mut scratch = vec![0.0; Wavetable::<f32>::scratch_len(TABLE_SIZE)];
+    Wavetable::build(&source(), TABLE_SIZE, storage, &mut scratch).unwrap()
+}
+
+#[test]
+fn levels_have_no_harmonics_above_their_limit() {
+    let mut storage = vec![0.0; Wavetable::<f32>::storage_len(TABLE_SIZE, 2)];
+    let table = build(&mut storage);
+    assert_eq!(table.levels(), 8);
+
+    for frame in 0..table.frames() {
+        for level in 0..table.levels() {
+            let limit = (TABLE_SIZE / 2) >> level;
+            let spectrum = harmonics(table.table(frame, level));
+
+            for (harmonic, &amplitude) in spectrum.iter().enumerate().skip(1) {
+                if harmonic > limit || harmonic == TABLE_SIZE / 2 {
+                    assert!(amplitude < 1e-4, "frame {} level {} harmonic {}: {}", frame, level, harmonic, amplitude);
+                }
+            }
+            // The fundamental is kept at every level.
+            assert!(spectrum[1] > 0.5, "frame {} level {}: fundamental {}", frame, level, spectrum[1]);
+        }
+    }
+}
+
+#[test]
+fn chosen_level_stays_below_nyquist() {
+    let mut storage = vec![0.0; Wavetable::<f32>::storage_len(TABLE_SIZE, 2)];
+    let table = build(&mut storage);
+
+    let mut previous = 0;
+    for step in 1..500 {
+        let increment = step as f32 / 1000.0;
+        for &signed in &[increment, -increment] {
+            let level = table.level_for_increment(signed);
+            let highest = ((TABLE_SIZE / 2) >> level) as f32;
+            assert!(highest * increment <= 0.5, "increment {} level {}: top harmonic at {}", signed, level, highest * increment);
+        }
+
+        // And no more band-limited than it needs to be: the level below would alias.
+        let level = table.level_for_increment(increment);
+        if level > 0 {
+            let below = ((TABLE_SIZE / 2) >> (level - 1)) as f32;
+            assert!(below * increment > 0.5, "increment {} could use level {}", increment, level - 1);
+        }
+        assert!(level >= previous);
+        previous = level;
+    }
+}
+
+#[test]
+fn end_positions_reproduce_the_end_frames() {
+    let mut storage = vec![0.0; Wavetable::<f32>::storage_len(TABLE_SIZE, 2)];
+    let table = build(&mut storage);
+    let first: Vec<f32> = table.table(0, 0).to_vec();
+    let last: Vec<f32> = table.table(1, 0).to_vec();
+
+    // Two ticks per table sample, low enough that only the full-bandwidth level is read, so every
+    // other tick lands exactly on one of the table's samples.
+    let mut osc = WavetableOscillator::new(table, 1.0, (2 * TABLE_SIZE) as f32);
+    for (position, expected) in [(0.0, &first), (1.0, &last)] {
+        osc.set_position(position);
+        osc.reset_phase();
+        for (i, &x) in expected.iter().enumerate() {
+            assert!((osc.tick() - x).abs() < 1e-5, "position {} sample {}", position, i);
+            osc.tick();
+        }
+    }
+
+    // Halfway is the average of the two.
+    osc.set_position(0.5);
+    osc.reset_phase();
+    for i in 0..TABLE_SIZE {
+        assert!((osc.tick() - 0.5 * (first[i] + last[i])).abs() < 1e-5, "sample {}", i);
+        osc.tick();
+    }
+}
+
+#[test]
+fn crossfade_is_continuous_and_stays_below_nyquist() {
+    let mut storage = vec![0.0; Wavetable::<f32>::storage_len(TABLE_SIZE, 2)];
+    let table = build(&mut storage);
+
+    // The effective weight of each level, which should change smoothly as the increment rises.
+    let weights = |increment: f32| {
+        let (level, fade) = table.crossfade_for_increment(increment);
+        let mut weights = vec![0.0; table.levels()];
+        weights[level] += 1.0 - fade;
+        if fade > 0.0 {
+            weights[level + 1] += fade;
+        }
+        weights
+    };
+
+    // Sweep from well below the first fade up to Nyquist, 200 steps to the octave.
+    let mut previous = weights(0.0);
+    for step in 0..=2000 {
+        let increment = 2f32.powf(step as f32 / 200.0 - 11.0);
+        let (level, fade) = table.crossfade_for_increment(increment);
+        assert!((0.0..=1.0).contains(&fade));
+        assert_eq!(level, table.level_for_increment(increment));
+        if fade > 0.0 {
+            let highest = ((TABLE_SIZE / 2) >> (level + 1)) as f32;
+            assert!(highest * increment <= 0.5);
+        }
+
+        // Octave boundaries are crossed without a jump in any level's weight.
+        let current = weights(increment);
+        for (a, b) in previous.iter().zip(&current) {
+            assert!((a - b).abs() < 0.01, "increment {}: {:?} -> {:?}", increment, previous, current);
+        }
+        previous = current;
+    }
+
+    // Halfway through an octave in log terms is halfway through the fade.
+    let (level, fade) = table.crossfade_for_increment(2f32.powf(2.5) / TABLE_SIZE as f32);
+    assert_eq!(level, 3);
+    assert!((fade - 0.5).abs() < 1e-4, "{}", fade);
+}
+
+/// Both precisions step the same u32 phase, so over a long render they only differ by rounding.
+#[test]
+fn f32_oscillator_tracks_f64_reference() {
+    let mut storage = vec![0.0; Wavetable::<f32>::storage_len(TABLE_SIZE, 2)];
+    let table = build(&mut storage);
+    let source: Vec<f64> = source().iter().map(|&x| x as f64).collect();
+    let mut storage64 = vec![0.0; Wavetable::<f64>::storage_len(TABLE_SIZE, 2)];
+    let mut scratch64 = vec![0.0; Wavetable::<f64>::scratch_len(TABLE_SIZE)];
+    let table64 = Wavetable::build(&source, TABLE_SIZE, &mut storage64, &mut scratch64).unwrap();
+
+    let mut single = WavetableOscillator::new(table, 220.0, 48000.0);
+    let mut double = WavetableOscillator::new(table64, 220.0, 48000.0);
+    single.set_position(0.3);
+    double.set_position(0.3);
+
+    let mut worst: f64 = 0.0;
+    for _ in 0..480_000 {
+        worst = worst.max((single.tick() as f64 - double.tick()).abs());
+    }
+    assert!(worst < 1e-6, "worst difference {}", worst);
+}
+
+#[test]
+fn build_rejects_bad_arguments() {
+    let source = source();
+    let mut storage = vec![0.0; Wavetable::<f32>::storage_len(TABLE_SIZE, 2)];
+    let mut scratch = vec![0.0; Wavetable::<f32>::scratch_len(TABLE_SIZE)];
+
+    for &size in &[0, 2, 100, 255] {
+        let result = Wavetable::build(&source, size, &mut storage, &mut scratch);
+        assert_eq!(result.err(), Some(WavetableError::InvalidTableSize), "size {}", size);
+    }
+
+    let result = Wavetable::build(&[], TABLE_SIZE, &mut storage, &mut scratch);
+    assert_eq!(result.err(), Some(WavetableError::InvalidSource));
+    let result = Wavetable::build(&source[..TABLE_SIZE + 1], TABLE_SIZE, &mut storage, &mut scratch);
+    assert_eq!(result.err(), Some(WavetableError::InvalidSource));
+
+    let mut small = vec![0.0; Wavetable::<f32>::storage_len(TABLE_SIZE, 2) - 1];
+    let result = Wavetable::build(&source, TABLE_SIZE, &mut small, &mut scratch);
+    assert_eq!(result.err(), Some(WavetableError::StorageTooSmall));
+
+    let mut small = vec![0.0; Wavetable::<f32>::scratch_len(TABLE_SIZE) - 1];
+    let result = Wavetable::build(&source, TABLE_SIZE, &mut storage, &mut small);
+    assert_eq!(result.err(), Some(WavetableError::ScratchTooSmall));
+}